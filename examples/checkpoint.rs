@@ -0,0 +1,108 @@
+use futures::future::{self, BoxFuture};
+use pub_sub_client::{CheckpointStore, Error, PubSubClient};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    env,
+    error::Error as _,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+const SUBSCRIPTION_ID: &str = "test";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    text: String,
+}
+
+/// Checkpoint store appending the IDs of processed messages to a file, one per line.
+struct FileCheckpointStore {
+    file: Mutex<File>,
+    message_ids: Mutex<HashSet<String>>,
+}
+
+impl FileCheckpointStore {
+    fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let message_ids = BufReader::new(&file)
+            .lines()
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(Self {
+            file: Mutex::new(file),
+            message_ids: Mutex::new(message_ids),
+        })
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn is_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+        let processed = self.message_ids.lock().unwrap().contains(message_id);
+        Box::pin(future::ready(Ok(processed)))
+    }
+
+    fn mark_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        let result = writeln!(self.file.lock().unwrap(), "{message_id}")
+            .map_err(|e| Error::Checkpoint(e.into()));
+        if result.is_ok() {
+            self.message_ids
+                .lock()
+                .unwrap()
+                .insert(message_id.to_string());
+        }
+        Box::pin(future::ready(result))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .json()
+        .init();
+
+    if let Err(e) = run().await {
+        eprintln!("ERROR: {e}");
+        if let Some(e) = e.source() {
+            eprintln!("SOURCE: {e}");
+        }
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let key_path = format!("{dir}/secrets/active-road-365118-0214022979ee.json");
+    let pub_sub_client = PubSubClient::new(key_path, Duration::from_secs(30))?;
+
+    let checkpoint_store = FileCheckpointStore::open(env::temp_dir().join("checkpoints"))
+        .map_err(|e| Error::Checkpoint(e.into()))?;
+
+    let pulled_messages = pub_sub_client
+        .pull_unprocessed::<Message>(SUBSCRIPTION_ID, 42, None, &checkpoint_store)
+        .await?;
+
+    for pulled_message in pulled_messages {
+        match pulled_message.message {
+            Ok(m) => println!("pulled message with text \"{}\"", m.text),
+            Err(e) => eprintln!("ERROR: {e}"),
+        }
+
+        checkpoint_store.mark_processed(&pulled_message.id).await?;
+        pub_sub_client
+            .acknowledge(SUBSCRIPTION_ID, vec![&pulled_message.ack_id], None)
+            .await?;
+        println!("acknowledged message with ID {}", pulled_message.id);
+    }
+
+    Ok(())
+}
@@ -30,7 +30,7 @@ async fn run() -> Result<(), Error> {
     let key_path = format!("{dir}/secrets/active-road-365118-0214022979ee.json");
     let pub_sub_client = PubSubClient::new(key_path, Duration::from_secs(30))?;
 
-    let messages = vec!["Hello", "from pub-sub-client"]
+    let messages = ["Hello", "from pub-sub-client"]
         .iter()
         .map(|s| s.to_string())
         .map(|text| Message { text })
@@ -38,7 +38,7 @@ async fn run() -> Result<(), Error> {
     let key_path = format!("{dir}/secrets/active-road-365118-0214022979ee.json");
     let pub_sub_client = PubSubClient::new(key_path, Duration::from_secs(30))?;
 
-    let messages = vec!["Hello", "from pub-sub-client"]
+    let messages = ["Hello", "from pub-sub-client"]
         .iter()
         .map(|s| STANDARD.encode(json!({ "text": s }).to_string()))
         .map(|data| {
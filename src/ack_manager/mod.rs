@@ -0,0 +1,238 @@
+use crate::{
+    batching::{Batch, BatchSettings},
+    error::Error,
+    PubSubClient,
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_BATCH_SIZE: usize = 1_000;
+const DEFAULT_MAX_DELAY: Duration = Duration::from_millis(100);
+
+/// Collects the ACK IDs of handled messages and acknowledges them in batches, once there are
+/// `max_batch_size` of them or the oldest has been waiting for `max_delay`, which considerably
+/// reduces the number of requests for high-throughput subscriptions. As the time threshold is
+/// only checked when adding ACK IDs, use [AckManager::run] to flush periodically and
+/// [AckManager::flush] to flush explicitly, e.g. before shutting down.
+#[derive(Debug)]
+pub struct AckManager<'a> {
+    client: &'a PubSubClient,
+    subscription_id: String,
+    max_batch_size: usize,
+    max_delay: Duration,
+    batch: Mutex<Batch<String>>,
+}
+
+impl<'a> AckManager<'a> {
+    pub fn new<T>(client: &'a PubSubClient, subscription_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            client,
+            subscription_id: subscription_id.into(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_delay: DEFAULT_MAX_DELAY,
+            batch: Mutex::default(),
+        }
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Add the given ACK ID to the current batch and acknowledge the batch if due.
+    pub async fn ack<T>(&self, ack_id: T) -> Result<(), Error>
+    where
+        T: Into<String>,
+    {
+        let ack_ids = {
+            let now = Instant::now();
+            let mut batch = self.batch.lock().expect("lock batch");
+            batch.push(ack_id.into(), now);
+            batch.take_if_due(now, &self.batch_settings())
+        };
+        match ack_ids {
+            Some(ack_ids) => self.acknowledge(ack_ids).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Acknowledge the current batch, if not empty.
+    pub async fn flush(&self) -> Result<(), Error> {
+        let ack_ids = self.batch.lock().expect("lock batch").take();
+        if ack_ids.is_empty() {
+            return Ok(());
+        }
+        self.acknowledge(ack_ids).await
+    }
+
+    /// Flush every `max_delay` until the given token is cancelled, then flush a last time.
+    pub async fn run(&self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        while cancellation_token
+            .run_until_cancelled(tokio::time::sleep(self.max_delay))
+            .await
+            .is_some()
+        {
+            if let Err(error) = self.flush().await {
+                warn!(%error, subscription_id = self.subscription_id, "cannot flush ACK IDs");
+            }
+        }
+        self.flush().await
+    }
+
+    /// ACK IDs are batched by count and age only.
+    fn batch_settings(&self) -> BatchSettings {
+        BatchSettings {
+            max_messages: self.max_batch_size,
+            max_bytes: usize::MAX,
+            max_latency: self.max_delay,
+        }
+    }
+
+    async fn acknowledge(&self, ack_ids: Vec<String>) -> Result<(), Error> {
+        let subscription_id = &self.subscription_id;
+        debug!(
+            subscription_id,
+            count = ack_ids.len(),
+            "acknowledging batch"
+        );
+        let ack_ids = ack_ids.iter().map(|ack_id| ack_id.as_str()).collect();
+        self.client
+            .acknowledge(subscription_id, ack_ids, None)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AckManager;
+    use crate::{Error, PubSubClient};
+    use reqwest::Request;
+    use serde_json::Value;
+    use std::{
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    type Requests = Arc<Mutex<Vec<Vec<String>>>>;
+
+    /// A client recording the ACK IDs of acknowledge requests, which are all rejected.
+    fn client() -> (PubSubClient, Requests) {
+        let requests = Requests::default();
+        let recorded = requests.clone();
+        let record = move |request: &mut Request| -> Result<(), Box<dyn StdError + Send + Sync>> {
+            let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+            let body = serde_json::from_slice::<Value>(body).unwrap();
+            let ack_ids = body["ackIds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|ack_id| ack_id.as_str().unwrap().to_string())
+                .collect();
+            recorded.lock().unwrap().push(ack_ids);
+            Err("rejected".into())
+        };
+        let client = PubSubClient::emulator_builder("test", "http://localhost:8085")
+            .with_interceptor(record)
+            .build()
+            .unwrap();
+        (client, requests)
+    }
+
+    fn taken(requests: &Requests) -> Vec<Vec<String>> {
+        requests.lock().unwrap().drain(..).collect()
+    }
+
+    #[tokio::test]
+    async fn test_flush_at_max_batch_size() {
+        let (client, requests) = client();
+        let ack_manager = AckManager::new(&client, "test")
+            .with_max_batch_size(2)
+            .with_max_delay(Duration::from_secs(60));
+
+        assert!(ack_manager.ack("1").await.is_ok());
+        assert!(taken(&requests).is_empty());
+        assert!(ack_manager.ack("2").await.is_err());
+        assert_eq!(taken(&requests), vec![vec!["1", "2"]]);
+
+        assert!(ack_manager.ack("3").await.is_ok());
+        assert!(taken(&requests).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flush_after_max_delay() {
+        let (client, requests) = client();
+        let ack_manager = AckManager::new(&client, "test")
+            .with_max_batch_size(100)
+            .with_max_delay(Duration::from_millis(10));
+
+        assert!(ack_manager.ack("1").await.is_ok());
+        assert!(taken(&requests).is_empty());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(ack_manager.ack("2").await.is_err());
+        assert_eq!(taken(&requests), vec![vec!["1", "2"]]);
+
+        // Running flushes periodically
+        let cancellation_token = CancellationToken::new();
+        let run = ack_manager.run(cancellation_token.clone());
+        let ack = async {
+            assert!(ack_manager.ack("3").await.is_ok());
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(taken(&requests), vec![vec!["3"]]);
+            cancellation_token.cancel();
+        };
+        let (result, _) = tokio::join!(run, ack);
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush() {
+        let (client, requests) = client();
+        let ack_manager = AckManager::new(&client, "test").with_max_delay(Duration::from_secs(60));
+
+        // Flushing an empty batch sends nothing
+        assert!(ack_manager.flush().await.is_ok());
+        assert!(taken(&requests).is_empty());
+
+        assert!(ack_manager.ack("1").await.is_ok());
+        assert!(ack_manager.ack("2").await.is_ok());
+        assert!(ack_manager.flush().await.is_err());
+        assert_eq!(taken(&requests), vec![vec!["1", "2"]]);
+
+        // Running flushes a last time once cancelled
+        assert!(ack_manager.ack("3").await.is_ok());
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        assert!(ack_manager.run(cancellation_token).await.is_err());
+        assert_eq!(taken(&requests), vec![vec!["3"]]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_acknowledge() {
+        let (client, requests) = client();
+        let ack_manager = AckManager::new(&client, "test").with_max_batch_size(1);
+
+        // The error is returned and the ACK IDs are dropped, i.e. the messages get redelivered
+        let result = ack_manager.ack("1").await;
+        assert!(matches!(result, Err(Error::Interceptor(_))));
+        assert_eq!(taken(&requests), vec![vec!["1"]]);
+
+        assert!(ack_manager.flush().await.is_ok());
+        assert!(taken(&requests).is_empty());
+        assert!(ack_manager.ack("2").await.is_err());
+        assert_eq!(taken(&requests), vec![vec!["2"]]);
+    }
+}
@@ -0,0 +1,610 @@
+use crate::{
+    credentials::{Credentials, SigningKey},
+    error::Error,
+    external_account::ExternalAccount,
+    self_signed_jwt::SelfSignedJwt,
+};
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    env,
+    error::Error as StdError,
+    ffi::OsString,
+    fmt::{self, Debug, Display, Formatter},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::debug;
+
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const REFRESH_TOKEN_GRANT_TYPE: &str = "refresh_token";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+pub(crate) const PUBSUB_SCOPE: &str = "https://www.googleapis.com/auth/pubsub";
+pub(crate) const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+const CREDENTIALS_ENV_VAR: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+const EMULATOR_HOST_ENV_VAR: &str = "PUBSUB_EMULATOR_HOST";
+const METADATA_HOST_ENV_VAR: &str = "GCE_METADATA_HOST";
+const DEFAULT_METADATA_HOST: &str = "metadata.google.internal";
+
+/// Timeout for detecting whether the metadata server is available, which it is not when running
+/// outside of Google Cloud.
+const METADATA_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Google rejects JWTs spanning more than one hour.
+pub(crate) const MAX_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Where to get the credentials for fetching access tokens from.
+#[derive(Debug)]
+pub(crate) enum CredentialsSource {
+    /// Service account key file at the given path.
+    KeyPath(String),
+
+    /// Service account credentials, e.g. from a secrets manager.
+    ServiceAccount(Box<Credentials>),
+
+    /// External account credentials for workload identity federation, e.g. from AWS or an OIDC
+    /// provider like GitHub Actions.
+    ExternalAccount(Box<ExternalAccount>),
+
+    /// User credentials, e.g. from `gcloud auth application-default login`.
+    AuthorizedUser(Box<AuthorizedUser>),
+
+    /// No credentials at all, i.e. requests are not authenticated, e.g. for the emulator.
+    Anonymous,
+
+    /// Instance metadata server, e.g. on GCE, GKE (workload identity) or Cloud Run, for the
+    /// project the instance belongs to.
+    MetadataServer { project_id: String },
+}
+
+impl CredentialsSource {
+    /// Parses credentials JSON of one of the supported types – `service_account`,
+    /// `external_account` or `authorized_user` – as given by its `type` field.
+    pub(crate) fn from_json(json: &str) -> Result<Self, Error> {
+        let malformed = |source: serde_json::Error| Error::Initialization {
+            reason: "malformed credentials JSON".to_string(),
+            source: source.into(),
+        };
+
+        let json = serde_json::from_str::<Value>(json).map_err(malformed)?;
+        match json.get("type").and_then(Value::as_str) {
+            Some("service_account") => serde_json::from_value(json)
+                .map(|credentials| Self::ServiceAccount(Box::new(credentials)))
+                .map_err(malformed),
+            Some("external_account") => serde_json::from_value(json)
+                .map(|external_account| Self::ExternalAccount(Box::new(external_account)))
+                .map_err(malformed),
+            Some("authorized_user") => serde_json::from_value(json)
+                .map(|authorized_user| Self::AuthorizedUser(Box::new(authorized_user)))
+                .map_err(malformed),
+            other => Err(Error::Initialization {
+                reason: "unsupported credentials JSON".to_string(),
+                source: format!("unsupported type `{}`", other.unwrap_or_default()).into(),
+            }),
+        }
+    }
+
+    /// Reads and parses the credentials JSON file at the given path, see
+    /// [CredentialsSource::from_json].
+    pub(crate) fn from_file(path: &str) -> Result<Self, Error> {
+        let json = fs::read_to_string(path).map_err(|source| Error::Initialization {
+            reason: format!("missing credentials file at `{path}`"),
+            source: source.into(),
+        })?;
+        Self::from_json(&json).map_err(|error| match error {
+            Error::Initialization { reason, source } => Error::Initialization {
+                reason: format!("{reason} at `{path}`"),
+                source,
+            },
+            other => other,
+        })
+    }
+
+    /// Uses the key file given by the `GOOGLE_APPLICATION_CREDENTIALS` environment variable, if
+    /// not set but the `PUBSUB_EMULATOR_HOST` environment variable is, no credentials at all.
+    pub(crate) fn from_env() -> Result<Self, Error> {
+        match env::var(CREDENTIALS_ENV_VAR) {
+            Ok(key_path) => Ok(Self::KeyPath(key_path)),
+            Err(_) if emulator_base_url().is_some() => Ok(Self::Anonymous),
+            Err(source) => Err(Error::Initialization {
+                reason: format!("cannot read environment variable `{CREDENTIALS_ENV_VAR}`"),
+                source: source.into(),
+            }),
+        }
+    }
+
+    /// Resolves Application Default Credentials: the key file given by the
+    /// `GOOGLE_APPLICATION_CREDENTIALS` environment variable, else the well-known file written by
+    /// `gcloud auth application-default login`, else the instance metadata server. If the
+    /// `PUBSUB_EMULATOR_HOST` environment variable is set, no credentials are used at all.
+    pub(crate) async fn application_default() -> Result<Self, Error> {
+        if emulator_base_url().is_some() {
+            debug!("using no credentials for emulator");
+            return Ok(Self::Anonymous);
+        }
+
+        if let Ok(key_path) = env::var(CREDENTIALS_ENV_VAR) {
+            debug!(key_path, "using credentials from {CREDENTIALS_ENV_VAR}");
+            return Ok(Self::KeyPath(key_path));
+        }
+
+        if let Some(key_path) = well_known_file(|name| env::var_os(name)).filter(|p| p.is_file()) {
+            let key_path = key_path.to_string_lossy().into_owned();
+            debug!(key_path, "using credentials from well-known file");
+            return Ok(Self::KeyPath(key_path));
+        }
+
+        let project_id = metadata_project_id(&reqwest::Client::new(), Some(METADATA_PROBE_TIMEOUT))
+            .await
+            .map_err(|source| Error::Initialization {
+                reason: "no application default credentials found".to_string(),
+                source: source.into(),
+            })?;
+        debug!(project_id, "using credentials from metadata server");
+        Ok(Self::MetadataServer { project_id })
+    }
+
+    /// Uses the instance metadata server, getting the project ID from there.
+    pub(crate) async fn metadata_server() -> Result<Self, Error> {
+        let project_id = metadata_project_id(&reqwest::Client::new(), None)
+            .await
+            .map_err(|source| Error::Initialization {
+                reason: "cannot get project ID from metadata server".to_string(),
+                source: source.into(),
+            })?;
+        Ok(Self::MetadataServer { project_id })
+    }
+}
+
+/// Fetches access tokens from a [TokenSource] and caches them until `refresh_buffer` before they
+/// expire, which is after `token_lifetime` at the latest.
+pub(crate) struct TokenFetcher {
+    source: TokenSource,
+    refresh_buffer: Duration,
+    token_lifetime: Duration,
+    token_state: Mutex<Option<TokenState>>,
+    reqwest_client: reqwest::Client,
+}
+
+/// How to fetch access tokens.
+pub(crate) enum TokenSource {
+    /// Exchange a JWT signed with the private key of a service account at the token endpoint.
+    ServiceAccount(Box<ServiceAccount>),
+
+    /// Use JWTs signed with the private key of a service account as access tokens.
+    SelfSignedJwt(Box<SelfSignedJwt>),
+
+    /// Get access tokens for the default service account of the instance from the metadata
+    /// server.
+    MetadataServer,
+
+    /// Exchange a subject token from an external identity provider at the Security Token Service.
+    ExternalAccount(Box<ExternalAccount>),
+
+    /// Exchange the refresh token of a user at the token endpoint.
+    AuthorizedUser(Box<AuthorizedUser>),
+}
+
+/// Credentials of type `authorized_user`, i.e. an OAuth 2.0 client and a refresh token for a
+/// user, as written by `gcloud auth application-default login`.
+#[derive(Clone, Deserialize)]
+pub(crate) struct AuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+impl Debug for AuthorizedUser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthorizedUser")
+            .field("client_id", &self.client_id)
+            .field("token_uri", &self.token_uri)
+            .finish()
+    }
+}
+
+/// Access token as returned by the token endpoint, the metadata server and the Security Token
+/// Service.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Token {
+    access_token: String,
+    expires_in: u64,
+}
+
+impl Token {
+    pub(crate) fn new(access_token: String, expires_in: u64) -> Self {
+        Self {
+            access_token,
+            expires_in,
+        }
+    }
+
+    pub(crate) fn access_token(&self) -> &str {
+        &self.access_token
+    }
+}
+
+/// Signs JWTs for the token endpoint, backdating their "issued at" claim by `clock_skew`, such
+/// that hosts with clocks running slightly ahead do not get their JWTs rejected.
+pub(crate) struct ServiceAccount {
+    signing_key: SigningKey,
+    token_uri: String,
+    clock_skew: Duration,
+}
+
+/// Error response of an OAuth 2.0 token endpoint.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenError {
+    error: String,
+    error_description: Option<String>,
+    #[serde(skip)]
+    status: StatusCode,
+}
+
+impl TokenError {
+    pub(crate) fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl Display for TokenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.error_description {
+            Some(error_description) => write!(f, "{}: {error_description}", self.error),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl StdError for TokenError {}
+
+struct TokenState {
+    token: Token,
+    refresh_at: OffsetDateTime,
+}
+
+impl TokenSource {
+    pub(crate) fn service_account(
+        credentials: &Credentials,
+        clock_skew: Duration,
+    ) -> Result<Self, Error> {
+        let signing_key = credentials.signing_key()?;
+        Ok(Self::ServiceAccount(Box::new(ServiceAccount {
+            signing_key,
+            token_uri: credentials.token_uri(),
+            clock_skew,
+        })))
+    }
+}
+
+impl TokenFetcher {
+    pub(crate) fn new(
+        source: TokenSource,
+        refresh_buffer: Duration,
+        token_lifetime: Duration,
+        reqwest_client: reqwest::Client,
+    ) -> Self {
+        Self {
+            source,
+            refresh_buffer,
+            token_lifetime,
+            token_state: Mutex::new(None),
+            reqwest_client,
+        }
+    }
+
+    pub(crate) async fn fetch_token(&self) -> Result<Token, Error> {
+        let now = OffsetDateTime::now_utc();
+
+        if let Some(token_state) = &*self.token_state.lock().unwrap() {
+            if now < token_state.refresh_at {
+                return Ok(token_state.token.clone());
+            }
+        }
+
+        let token = match &self.source {
+            TokenSource::ServiceAccount(service_account) => {
+                self.fetch_service_account_token(now, service_account)
+                    .await?
+            }
+            TokenSource::SelfSignedJwt(self_signed_jwt) => {
+                self_signed_jwt.token(now, self.token_lifetime)?
+            }
+            TokenSource::MetadataServer => self.fetch_metadata_server_token().await?,
+            TokenSource::ExternalAccount(external_account) => {
+                external_account.fetch_token(&self.reqwest_client).await?
+            }
+            TokenSource::AuthorizedUser(authorized_user) => {
+                self.fetch_authorized_user_token(authorized_user).await?
+            }
+        };
+
+        let expires_in = Duration::from_secs(token.expires_in).min(self.token_lifetime);
+        let refresh_at = now + expires_in.saturating_sub(self.refresh_buffer);
+        *self.token_state.lock().unwrap() = Some(TokenState {
+            token: token.clone(),
+            refresh_at,
+        });
+
+        Ok(token)
+    }
+
+    async fn fetch_service_account_token(
+        &self,
+        now: OffsetDateTime,
+        service_account: &ServiceAccount,
+    ) -> Result<Token, Error> {
+        let ServiceAccount {
+            signing_key,
+            token_uri,
+            clock_skew,
+        } = service_account;
+
+        let (iat, exp) = claims_window(now, *clock_skew, self.token_lifetime);
+        let assertion = signing_key.sign(&json!({
+            "iss": signing_key.email(),
+            "scope": PUBSUB_SCOPE,
+            "aud": token_uri,
+            "iat": iat,
+            "exp": exp,
+        }))?;
+
+        debug!(token_uri, "fetching token");
+        let response = self
+            .reqwest_client
+            .post(token_uri)
+            .form(&[
+                ("grant_type", JWT_BEARER_GRANT_TYPE),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+
+        token_from_response(response).await
+    }
+
+    async fn fetch_authorized_user_token(
+        &self,
+        authorized_user: &AuthorizedUser,
+    ) -> Result<Token, Error> {
+        debug!(
+            token_uri = authorized_user.token_uri,
+            "refreshing user token"
+        );
+        let response = self
+            .reqwest_client
+            .post(&authorized_user.token_uri)
+            .form(&[
+                ("grant_type", REFRESH_TOKEN_GRANT_TYPE),
+                ("client_id", &authorized_user.client_id),
+                ("client_secret", &authorized_user.client_secret),
+                ("refresh_token", &authorized_user.refresh_token),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+
+        token_from_response(response).await
+    }
+
+    async fn fetch_metadata_server_token(&self) -> Result<Token, Error> {
+        let path = format!("instance/service-accounts/default/token?scopes={PUBSUB_SCOPE}");
+        debug!(path, "fetching token from metadata server");
+        let token = get_metadata(&self.reqwest_client, &path, None)
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?
+            .json::<Token>()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+        Ok(token)
+    }
+}
+
+/// Returns the [Token] from the given response of an OAuth 2.0 token endpoint, or the error it
+/// contains if not successful.
+pub(crate) async fn token_from_response(response: Response) -> Result<Token, Error> {
+    if response.status().is_success() {
+        let token = response
+            .json::<Token>()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+        Ok(token)
+    } else {
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+        // Server errors, e.g. from a proxy, do not necessarily come with an OAuth 2.0 error body.
+        let token_error = serde_json::from_str::<TokenError>(&body)
+            .map(|token_error| TokenError {
+                status,
+                ..token_error
+            })
+            .unwrap_or(TokenError {
+                error: status.to_string(),
+                error_description: Some(body),
+                status,
+            });
+        Err(Error::TokenFetch(token_error.into()))
+    }
+}
+
+/// Gets the ID of the project the instance belongs to from the metadata server.
+pub(crate) async fn metadata_project_id(
+    reqwest_client: &reqwest::Client,
+    timeout: Option<Duration>,
+) -> Result<String, reqwest::Error> {
+    get_metadata(reqwest_client, "project/project-id", timeout)
+        .await?
+        .text()
+        .await
+}
+
+async fn get_metadata(
+    reqwest_client: &reqwest::Client,
+    path: &str,
+    timeout: Option<Duration>,
+) -> Result<Response, reqwest::Error> {
+    let host =
+        env::var(METADATA_HOST_ENV_VAR).unwrap_or_else(|_| DEFAULT_METADATA_HOST.to_string());
+    let request = reqwest_client
+        .get(format!("http://{host}/computeMetadata/v1/{path}"))
+        .header("Metadata-Flavor", "Google");
+    let request = timeout.into_iter().fold(request, |r, t| r.timeout(t));
+    request.send().await?.error_for_status()
+}
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.to_string()
+}
+
+/// Returns the base URL of the emulator given by the `PUBSUB_EMULATOR_HOST` environment variable,
+/// if set.
+pub(crate) fn emulator_base_url() -> Option<String> {
+    env::var(EMULATOR_HOST_ENV_VAR)
+        .ok()
+        .filter(|host| !host.is_empty())
+        .map(|host| to_base_url(&host))
+}
+
+/// The emulator host usually is given without scheme, e.g. `localhost:8085`.
+fn to_base_url(host: &str) -> String {
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("http://{host}")
+    }
+}
+
+/// Returns the path of the well-known file written by `gcloud auth application-default login`,
+/// looking up environment variables with the given function.
+fn well_known_file<F>(var: F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let config_dir = match var("CLOUDSDK_CONFIG") {
+        Some(config_dir) => PathBuf::from(config_dir),
+        None if cfg!(windows) => PathBuf::from(var("APPDATA")?).join("gcloud"),
+        None => PathBuf::from(var("HOME")?).join(".config").join("gcloud"),
+    };
+    Some(config_dir.join("application_default_credentials.json"))
+}
+
+/// Returns the "issued at" and "expiration" claims for a JWT signed at `now`: the former is
+/// backdated by `clock_skew` and the latter is `token_lifetime` after the former.
+pub(crate) fn claims_window(
+    now: OffsetDateTime,
+    clock_skew: Duration,
+    token_lifetime: Duration,
+) -> (i64, i64) {
+    let iat = (now - clock_skew).unix_timestamp();
+    let exp = iat + token_lifetime.as_secs() as i64;
+    (iat, exp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{claims_window, to_base_url, well_known_file, CredentialsSource, TokenError};
+    use crate::Error;
+    use reqwest::StatusCode;
+    use std::{ffi::OsString, path::PathBuf, time::Duration};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_claims_window() {
+        let now = OffsetDateTime::from_unix_timestamp(1_000_000).unwrap();
+
+        let (iat, exp) = claims_window(now, Duration::ZERO, Duration::from_secs(3600));
+        assert_eq!(iat, 1_000_000);
+        assert_eq!(exp, 1_003_600);
+
+        let (iat, exp) = claims_window(now, Duration::from_secs(60), Duration::from_secs(3600));
+        assert_eq!(iat, 999_940);
+        assert_eq!(exp, 1_003_540);
+    }
+
+    #[test]
+    fn test_token_error_is_transient() {
+        let token_error = |status| {
+            Error::TokenFetch(Box::new(TokenError {
+                error: "error".to_string(),
+                error_description: None,
+                status,
+            }))
+        };
+        assert!(token_error(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(token_error(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(!token_error(StatusCode::BAD_REQUEST).is_transient());
+        assert!(!Error::TokenFetch("malformed".into()).is_transient());
+    }
+
+    #[test]
+    fn test_to_base_url() {
+        assert_eq!(to_base_url("localhost:8085"), "http://localhost:8085");
+        assert_eq!(
+            to_base_url("https://emulator:8085"),
+            "https://emulator:8085"
+        );
+    }
+
+    #[test]
+    fn test_well_known_file() {
+        let path = well_known_file(|name| match name {
+            "CLOUDSDK_CONFIG" => Some(OsString::from("/etc/gcloud")),
+            "HOME" => Some(OsString::from("/home/user")),
+            _ => None,
+        });
+        assert_eq!(
+            path,
+            Some(PathBuf::from(
+                "/etc/gcloud/application_default_credentials.json"
+            ))
+        );
+
+        if !cfg!(windows) {
+            let path =
+                well_known_file(|name| (name == "HOME").then(|| OsString::from("/home/user")));
+            assert_eq!(
+                path,
+                Some(PathBuf::from(
+                    "/home/user/.config/gcloud/application_default_credentials.json"
+                ))
+            );
+        }
+
+        let path = well_known_file(|_| None);
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn test_credentials_source_from_json() {
+        let credentials = CredentialsSource::from_json(
+            r#"{
+                "type": "authorized_user",
+                "client_id": "client-id",
+                "client_secret": "client-secret",
+                "refresh_token": "refresh-token"
+            }"#,
+        );
+        assert!(matches!(
+            credentials,
+            Ok(CredentialsSource::AuthorizedUser(_))
+        ));
+
+        let credentials = CredentialsSource::from_json(r#"{ "type": "authorized_user" }"#);
+        assert!(credentials.is_err());
+
+        let credentials = CredentialsSource::from_json(r#"{ "type": "unknown" }"#);
+        assert!(credentials.is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    error::Error, Decoder, Encoder, PubSubClient, RawPulledMessageEnvelope, SchemaEncoding,
+    SchemaType,
+};
+use apache_avro::Schema;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error::Error as StdError, sync::Arc, time::Duration};
+use tracing::debug;
+
+/// Attribute set by the Pub/Sub service on messages of schema-enabled topics.
+const SCHEMA_ENCODING_ATTRIBUTE: &str = "googclient_schemaencoding";
+
+/// [Encoder] and [Decoder] for messages of topics with an Avro schema, usually obtained via
+/// [PubSubClient::avro_codec].
+///
+/// Messages are encoded with the schema encoding of the topic. Pulled messages are decoded with
+/// the encoding given by their `googclient_schemaencoding` attribute, falling back to the one of
+/// the topic. JSON encoded messages are (de)serialized with serde_json, i.e. without the Avro
+/// specific wrapping of union values.
+#[derive(Debug, Clone)]
+pub struct AvroCodec {
+    schema: Schema,
+    encoding: SchemaEncoding,
+}
+
+impl AvroCodec {
+    /// Create an [AvroCodec] for the given Avro schema in JSON format and encoding.
+    pub fn new(definition: &str, encoding: SchemaEncoding) -> Result<Self, apache_avro::Error> {
+        let schema = Schema::parse_str(definition)?;
+        Ok(Self { schema, encoding })
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn encoding(&self) -> SchemaEncoding {
+        self.encoding
+    }
+}
+
+impl<M> Encoder<M> for AvroCodec
+where
+    M: Serialize,
+{
+    fn encode(&self, message: &M) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync + 'static>> {
+        match self.encoding {
+            SchemaEncoding::Json => serde_json::to_vec(message).map_err(Into::into),
+            _ => {
+                let value = apache_avro::to_value(message)?.resolve(&self.schema)?;
+                apache_avro::to_avro_datum(&self.schema, value).map_err(Into::into)
+            }
+        }
+    }
+}
+
+impl<M> Decoder<M> for AvroCodec
+where
+    M: DeserializeOwned,
+{
+    fn decode(
+        &self,
+        envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>> {
+        let encoding = envelope
+            .message
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.get(SCHEMA_ENCODING_ATTRIBUTE))
+            .map(|encoding| match encoding.as_str() {
+                "JSON" => SchemaEncoding::Json,
+                "BINARY" => SchemaEncoding::Binary,
+                _ => SchemaEncoding::EncodingUnspecified,
+            })
+            .unwrap_or(self.encoding);
+
+        match encoding {
+            SchemaEncoding::Json => serde_json::from_slice(data).map_err(Into::into),
+            _ => {
+                let value = apache_avro::from_avro_datum(&self.schema, &mut &data[..], None)?;
+                apache_avro::from_value(&value).map_err(Into::into)
+            }
+        }
+    }
+}
+
+impl PubSubClient {
+    /// Get the [AvroCodec] for the given topic, which must have an Avro schema. The schema – at
+    /// the last revision allowed by the topic – is fetched from the Pub/Sub schema service once
+    /// and then cached for this topic.
+    #[tracing::instrument]
+    pub async fn avro_codec(
+        &self,
+        topic_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Arc<AvroCodec>, Error> {
+        if let Some(codec) = self
+            .avro_codecs
+            .lock()
+            .expect("lock Avro codecs")
+            .get(topic_id)
+        {
+            return Ok(codec.clone());
+        }
+
+        let avro_schema_error = |reason: String| Error::AvroSchema {
+            topic_id: topic_id.to_string(),
+            reason,
+        };
+
+        let schema_settings = self
+            .get_topic(topic_id, timeout)
+            .await?
+            .schema_settings
+            .ok_or_else(|| avro_schema_error("topic has no schema".to_string()))?;
+        // The schema may belong to another project, hence its fully qualified name is kept.
+        let schema_id = match &schema_settings.last_revision_id {
+            Some(revision_id) => format!("{}@{revision_id}", schema_settings.schema),
+            None => schema_settings.schema.clone(),
+        };
+        let schema = self.get_schema(&schema_id, timeout).await?;
+        if schema.schema_type != SchemaType::Avro {
+            return Err(avro_schema_error(format!(
+                "schema `{}` is no Avro schema",
+                schema.name
+            )));
+        }
+
+        let encoding = schema_settings
+            .encoding
+            .unwrap_or(SchemaEncoding::EncodingUnspecified);
+        let codec = AvroCodec::new(&schema.definition, encoding)
+            .map(Arc::new)
+            .map_err(|error| avro_schema_error(format!("invalid Avro schema: {error}")))?;
+        debug!(topic_id, schema_id, "successfully fetched Avro schema");
+
+        self.avro_codecs
+            .lock()
+            .expect("lock Avro codecs")
+            .insert(topic_id.to_string(), codec.clone());
+        Ok(codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AvroCodec;
+    use crate::{Decoder, Encoder, RawPulledMessage, RawPulledMessageEnvelope, SchemaEncoding};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use time::OffsetDateTime;
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Message",
+        "fields": [{"name": "text", "type": "string"}]
+    }"#;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        text: String,
+    }
+
+    fn envelope(encoding: Option<&str>) -> RawPulledMessageEnvelope {
+        RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: None,
+                attributes: encoding.map(|encoding| {
+                    HashMap::from([(
+                        "googclient_schemaencoding".to_string(),
+                        encoding.to_string(),
+                    )])
+                }),
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let message = Message {
+            text: "test".to_string(),
+        };
+
+        let codec = AvroCodec::new(SCHEMA, SchemaEncoding::Binary).unwrap();
+        let data = codec.encode(&message).unwrap();
+        assert_eq!(data, b"\x08test");
+        let decoded: Message = codec.decode(&envelope(None), &data).unwrap();
+        assert_eq!(decoded, message);
+
+        let codec = AvroCodec::new(SCHEMA, SchemaEncoding::Json).unwrap();
+        let data = codec.encode(&message).unwrap();
+        assert_eq!(data, br#"{"text":"test"}"#);
+        let decoded: Message = codec.decode(&envelope(None), &data).unwrap();
+        assert_eq!(decoded, message);
+
+        // The encoding attribute takes precedence
+        let decoded: Message = codec
+            .decode(&envelope(Some("BINARY")), b"\x08test")
+            .unwrap();
+        assert_eq!(decoded, message);
+
+        assert!(AvroCodec::new("invalid", SchemaEncoding::Binary).is_err());
+    }
+}
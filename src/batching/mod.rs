@@ -0,0 +1,434 @@
+use crate::{error::Error, PubSubClient, RawPublishedMessage};
+use std::{
+    collections::HashMap,
+    mem,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_MESSAGES: usize = 100;
+const DEFAULT_MAX_BYTES: usize = 1_000_000;
+const DEFAULT_MAX_LATENCY: Duration = Duration::from_millis(10);
+
+/// Limits of the Pub/Sub service per publish request; the size is that of the base64 encoded data,
+/// attributes and ordering keys, i.e. roughly that of the request.
+const MAX_REQUEST_MESSAGES: usize = 1_000;
+const MAX_REQUEST_BYTES: usize = 10_000_000;
+
+/// Messages of failed batches are kept as long as at most this many batches are buffered, such
+/// that an outage does not exhaust the memory.
+const MAX_BUFFERED_BATCHES: usize = 10;
+
+/// Thresholds for a [BatchingPublisher]: a batch is published once any of them is hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSettings {
+    /// Maximum number of messages per batch.
+    pub max_messages: usize,
+    /// Maximum size of the data, attributes and ordering keys of the messages per batch.
+    pub max_bytes: usize,
+    /// Maximum time the oldest message of a batch waits for being published.
+    pub max_latency: Duration,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_latency: DEFAULT_MAX_LATENCY,
+        }
+    }
+}
+
+impl BatchSettings {
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency;
+        self
+    }
+}
+
+/// Accepts individual messages for a topic and publishes them in batches according to its
+/// [BatchSettings], which considerably reduces the number of requests for chatty producers. As
+/// the latency threshold is only checked when publishing, use [BatchingPublisher::run] to flush
+/// periodically.
+///
+/// Batches exceeding the limits of the Pub/Sub service per request are published in several
+/// requests. If publishing a batch fails with a transient error (see [Error::is_transient]), its
+/// messages are kept for the next attempt, unless already ten batches are buffered, else they are
+/// dropped.
+///
+/// Buffered messages are lost when a [BatchingPublisher] is dropped, which is logged as a warning:
+/// use [BatchingPublisher::close] or [BatchingPublisher::flush] before shutting down.
+#[derive(Debug)]
+pub struct BatchingPublisher<'a> {
+    client: &'a PubSubClient,
+    topic_id: String,
+    settings: BatchSettings,
+    batch: Mutex<Batch<BatchedMessage>>,
+}
+
+impl<'a> BatchingPublisher<'a> {
+    pub fn new<T>(client: &'a PubSubClient, topic_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            client,
+            topic_id: topic_id.into(),
+            settings: BatchSettings::default(),
+            batch: Mutex::default(),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: BatchSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Add the given message to the current batch and publish the batch if due.
+    pub async fn publish(&self, message: RawPublishedMessage<'_>) -> Result<(), Error> {
+        let messages = {
+            let now = Instant::now();
+            let mut batch = self.batch.lock().expect("lock batch");
+            batch.push(message.into(), now);
+            batch.take_if_due(now, &self.settings)
+        };
+        match messages {
+            Some(messages) => self.send(messages).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Flush every `max_latency` until the given token is cancelled, then flush a last time.
+    pub async fn run(&self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        while cancellation_token
+            .run_until_cancelled(tokio::time::sleep(self.settings.max_latency))
+            .await
+            .is_some()
+        {
+            if let Err(error) = self.flush().await {
+                warn!(%error, topic_id = self.topic_id, "cannot flush batch");
+            }
+        }
+        self.flush().await
+    }
+
+    /// Publish the current batch, if not empty.
+    pub async fn flush(&self) -> Result<(), Error> {
+        let messages = self.batch.lock().expect("lock batch").take();
+        if messages.is_empty() {
+            return Ok(());
+        }
+        self.send(messages).await
+    }
+
+    /// Publish the current batch, if not empty, and close this publisher. Use this when shutting
+    /// down gracefully, else buffered messages are lost.
+    pub async fn close(self) -> Result<(), Error> {
+        self.flush().await
+    }
+
+    async fn send(&self, messages: Vec<BatchedMessage>) -> Result<(), Error> {
+        let topic_id = &self.topic_id;
+        let mut requests = split(messages, MAX_REQUEST_MESSAGES, MAX_REQUEST_BYTES).into_iter();
+
+        while let Some(messages) = requests.next() {
+            debug!(topic_id, count = messages.len(), "publishing batch");
+            let raw_messages = messages.iter().map(|message| RawPublishedMessage {
+                data: message.data.clone(),
+                attributes: message.attributes.clone(),
+                ordering_key: message.ordering_key.as_deref(),
+            });
+            let Err(error) = self.client.publish_raw(topic_id, raw_messages, None).await else {
+                continue;
+            };
+
+            // Messages of the requests not sent yet are kept in any case.
+            let unsent = if error.is_transient() {
+                warn!(%error, topic_id, "publishing batch failed, keeping messages");
+                messages.into_iter().chain(requests.flatten()).collect()
+            } else {
+                requests.flatten().collect()
+            };
+            self.restore(unsent);
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    fn restore(&self, messages: Vec<BatchedMessage>) {
+        let count = messages.len();
+        let max_messages = self
+            .settings
+            .max_messages
+            .saturating_mul(MAX_BUFFERED_BATCHES);
+        let max_bytes = self.settings.max_bytes.saturating_mul(MAX_BUFFERED_BATCHES);
+        let restored =
+            self.batch
+                .lock()
+                .expect("lock batch")
+                .restore(messages, max_messages, max_bytes);
+        if !restored {
+            warn!(
+                topic_id = self.topic_id,
+                count, "too many buffered messages, dropping messages of failed batch"
+            );
+        }
+    }
+}
+
+impl Drop for BatchingPublisher<'_> {
+    fn drop(&mut self) {
+        let unflushed = self
+            .batch
+            .get_mut()
+            .map(|batch| batch.len())
+            .unwrap_or_default();
+        if unflushed > 0 {
+            warn!(
+                topic_id = self.topic_id,
+                count = unflushed,
+                "dropping batching publisher with unflushed messages, use close or flush"
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BatchedMessage {
+    data: Option<String>,
+    attributes: Option<HashMap<String, String>>,
+    ordering_key: Option<String>,
+}
+
+impl BatchItem for BatchedMessage {
+    fn size(&self) -> usize {
+        let data = self.data.as_ref().map(String::len).unwrap_or_default();
+        let attributes = self
+            .attributes
+            .iter()
+            .flatten()
+            .map(|(key, value)| key.len() + value.len())
+            .sum::<usize>();
+        let ordering_key = self
+            .ordering_key
+            .as_ref()
+            .map(String::len)
+            .unwrap_or_default();
+        data + attributes + ordering_key
+    }
+}
+
+impl From<RawPublishedMessage<'_>> for BatchedMessage {
+    fn from(message: RawPublishedMessage<'_>) -> Self {
+        Self {
+            data: message.data,
+            attributes: message.attributes,
+            ordering_key: message.ordering_key.map(ToString::to_string),
+        }
+    }
+}
+
+/// Item of a [Batch], e.g. a message or an ACK ID.
+pub(crate) trait BatchItem {
+    /// Size in bytes counting towards the `max_bytes` threshold.
+    fn size(&self) -> usize;
+}
+
+impl BatchItem for String {
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Items collected until one of the thresholds of the given [BatchSettings] is hit, used for
+/// messages by [BatchingPublisher] and for ACK IDs by [AckManager](crate::AckManager).
+#[derive(Debug)]
+pub(crate) struct Batch<T> {
+    items: Vec<T>,
+    bytes: usize,
+    since: Option<Instant>,
+}
+
+impl<T> Default for Batch<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::default(),
+            bytes: 0,
+            since: None,
+        }
+    }
+}
+
+impl<T> Batch<T>
+where
+    T: BatchItem,
+{
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub(crate) fn push(&mut self, item: T, now: Instant) {
+        self.bytes += item.size();
+        self.items.push(item);
+        self.since.get_or_insert(now);
+    }
+
+    pub(crate) fn take_if_due(&mut self, now: Instant, settings: &BatchSettings) -> Option<Vec<T>> {
+        let due = self.items.len() >= settings.max_messages
+            || self.bytes >= settings.max_bytes
+            || self
+                .since
+                .is_some_and(|since| now.duration_since(since) >= settings.max_latency);
+        due.then(|| self.take())
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<T> {
+        self.bytes = 0;
+        self.since = None;
+        mem::take(&mut self.items)
+    }
+
+    /// Put the given items of a failed batch back in front of the current ones, unless there
+    /// would be more than `max_items` or `max_bytes` in total; returns whether restored.
+    pub(crate) fn restore(
+        &mut self,
+        mut items: Vec<T>,
+        max_items: usize,
+        max_bytes: usize,
+    ) -> bool {
+        if items.is_empty() {
+            return true;
+        }
+
+        let bytes = items.iter().map(T::size).sum::<usize>();
+        if self.items.len() + items.len() > max_items || self.bytes + bytes > max_bytes {
+            return false;
+        }
+
+        self.bytes += bytes;
+        items.append(&mut self.items);
+        self.items = items;
+        self.since.get_or_insert_with(Instant::now);
+        true
+    }
+}
+
+/// Split the given items into chunks of at most `max_items` and – unless a single item is larger
+/// – `max_bytes`, keeping their order.
+fn split<T>(items: Vec<T>, max_items: usize, max_bytes: usize) -> Vec<Vec<T>>
+where
+    T: BatchItem,
+{
+    let mut chunks = Vec::<Vec<T>>::new();
+    let mut bytes = 0;
+    for item in items {
+        let size = item.size();
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() < max_items && bytes + size <= max_bytes => {
+                bytes += size;
+                chunk.push(item);
+            }
+            _ => {
+                bytes = size;
+                chunks.push(vec![item]);
+            }
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, Batch, BatchSettings, BatchedMessage};
+    use crate::RawPublishedMessage;
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_take_if_due() {
+        let settings = BatchSettings::default()
+            .with_max_messages(3)
+            .with_max_bytes(10)
+            .with_max_latency(Duration::from_secs(1));
+        let now = Instant::now();
+        let mut batch = Batch::<BatchedMessage>::default();
+
+        batch.push(RawPublishedMessage::new("a".to_string()).into(), now);
+        batch.push(RawPublishedMessage::new("b".to_string()).into(), now);
+        assert!(batch.take_if_due(now, &settings).is_none());
+        batch.push(RawPublishedMessage::new("c".to_string()).into(), now);
+        assert_eq!(batch.take_if_due(now, &settings).map(|m| m.len()), Some(3));
+
+        let message = RawPublishedMessage::new("abcd".to_string())
+            .with_attributes(HashMap::from([("key".to_string(), "val".to_string())]))
+            .with_ordering_key("xyz");
+        batch.push(message.into(), now);
+        assert_eq!(batch.take_if_due(now, &settings).map(|m| m.len()), Some(1));
+
+        batch.push(RawPublishedMessage::new("a".to_string()).into(), now);
+        assert!(batch.take_if_due(now, &settings).is_none());
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            batch.take_if_due(later, &settings).map(|m| m.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_restore() {
+        let now = Instant::now();
+        let mut batch = Batch::<BatchedMessage>::default();
+        batch.push(RawPublishedMessage::new("a".to_string()).into(), now);
+        let failed = batch.take();
+        batch.push(RawPublishedMessage::new("b".to_string()).into(), now);
+
+        assert!(batch.restore(failed, 2, 10));
+        let messages = batch.take();
+        let data = messages
+            .iter()
+            .map(|message| message.data.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec!["a", "b"]);
+
+        batch.push(RawPublishedMessage::new("a".to_string()).into(), now);
+        let failed = batch.take();
+        batch.push(RawPublishedMessage::new("b".to_string()).into(), now);
+        assert!(!batch.restore(failed, 1, 10));
+        assert_eq!(batch.len(), 1);
+    }
+
+    #[test]
+    fn test_split() {
+        let items = ["a", "bb", "c", "dddd", "e"]
+            .map(ToString::to_string)
+            .to_vec();
+
+        let chunks = split(items.clone(), 2, 10);
+        assert_eq!(chunks, vec![vec!["a", "bb"], vec!["c", "dddd"], vec!["e"]]);
+
+        let chunks = split(items, 10, 3);
+        assert_eq!(
+            chunks,
+            vec![vec!["a", "bb"], vec!["c"], vec!["dddd"], vec!["e"]]
+        );
+
+        let chunks = split(Vec::<String>::new(), 10, 3);
+        assert!(chunks.is_empty());
+    }
+}
@@ -0,0 +1,512 @@
+use crate::{
+    auth::{emulator_base_url, CredentialsSource, TokenFetcher, TokenSource, MAX_TOKEN_LIFETIME},
+    compression::CompressionConfig,
+    encryption::{Cipher, PayloadCipher},
+    error::Error,
+    interceptor::{Interceptor, Interceptors},
+    publisher::{PublishRetry, PublishRetryConfig},
+    retry::RequestRetryPolicy,
+    self_signed_jwt::SelfSignedJwt,
+    spool::Spool,
+    timeouts::DefaultTimeouts,
+    PubSubClient,
+};
+use futures::StreamExt;
+use std::{env, path::PathBuf, pin::pin, sync::Arc, time::Duration};
+use tracing::debug;
+
+const BASE_URL_ENV_VAR: &str = "PUB_SUB_BASE_URL";
+const DEFAULT_BASE_URL: &str = "https://pubsub.googleapis.com";
+const PROJECT_ENV_VAR: &str = "GOOGLE_CLOUD_PROJECT";
+const DEFAULT_REFRESH_BUFFER: Duration = Duration::from_secs(30);
+
+/// Builder for [PubSubClient], created via [PubSubClient::builder] or one of the other
+/// `*_builder` functions of [PubSubClient] for different sources of credentials.
+#[derive(Debug)]
+pub struct PubSubClientBuilder {
+    credentials: CredentialsSource,
+    project_id: Option<String>,
+    base_url: Option<String>,
+    region: Option<String>,
+    quota_project_id: Option<String>,
+    application_name: Option<String>,
+    refresh_buffer: Duration,
+    clock_skew: Duration,
+    token_lifetime: Duration,
+    spool: Option<(PathBuf, u64)>,
+    publish_retry: Option<PublishRetryConfig>,
+    request_retry: Option<RequestRetryPolicy>,
+    compression: Option<CompressionConfig>,
+    cipher: Option<Cipher>,
+    crc32c: bool,
+    default_timeouts: DefaultTimeouts,
+    interceptors: Interceptors,
+    self_signed_jwt: bool,
+    reqwest_client: Option<reqwest::Client>,
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    root_certificates: Vec<reqwest::Certificate>,
+}
+
+impl PubSubClientBuilder {
+    pub(crate) fn new(credentials: CredentialsSource) -> Self {
+        Self {
+            credentials,
+            project_id: None,
+            base_url: None,
+            region: None,
+            quota_project_id: None,
+            application_name: None,
+            refresh_buffer: DEFAULT_REFRESH_BUFFER,
+            clock_skew: Duration::ZERO,
+            token_lifetime: MAX_TOKEN_LIFETIME,
+            spool: None,
+            publish_retry: None,
+            request_retry: None,
+            compression: None,
+            cipher: None,
+            crc32c: false,
+            default_timeouts: DefaultTimeouts::default(),
+            interceptors: Interceptors::default(),
+            self_signed_jwt: false,
+            reqwest_client: None,
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            root_certificates: vec![],
+        }
+    }
+
+    /// ID of the project to use instead of the one of the credentials. Required for credentials
+    /// without a project ID, i.e. external account and authorized user credentials, unless given
+    /// by the `GOOGLE_CLOUD_PROJECT` environment variable.
+    pub fn with_project_id<T>(mut self, project_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    /// Base URL of the Pub/Sub service, e.g. `http://localhost:8085`, to use instead of the emulator
+    /// given by the `PUBSUB_EMULATOR_HOST` environment variable, the one given by the
+    /// `PUB_SUB_BASE_URL` environment variable, the regional endpoint or the default one. Other
+    /// than these process-wide environment variables, this allows for clients in the same process
+    /// to target different endpoints.
+    pub fn with_base_url<T>(mut self, base_url: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Use the regional endpoint for the given region, e.g. `us-central1`, which guarantees that
+    /// messages are stored in that region and is required for topics with a message storage
+    /// policy. Overridden by an explicit base URL and the base URL environment variables.
+    pub fn with_region<T>(mut self, region: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// ID of the project to use for quota and billing, sent as `x-goog-user-project` header with
+    /// every request; required e.g. for authorized user credentials or for consuming topics or
+    /// subscriptions of another project with the quota of the given one. Not set by default.
+    pub fn with_quota_project_id<T>(mut self, quota_project_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.quota_project_id = Some(quota_project_id.into());
+        self
+    }
+
+    /// Identifier of the application, e.g. `my-app/1.2.3`, appended to the `User-Agent` and
+    /// `x-goog-api-client` headers sent with every request, such that traffic can be attributed,
+    /// e.g. in audit logs.
+    pub fn with_application_name<T>(mut self, application_name: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.application_name = Some(application_name.into());
+        self
+    }
+
+    /// Duration to refresh access tokens before they expire, 30 seconds by default.
+    pub fn with_refresh_buffer(mut self, refresh_buffer: Duration) -> Self {
+        self.refresh_buffer = refresh_buffer;
+        self
+    }
+
+    /// Tolerated clock skew: the "issued at" claim of the JWT used to fetch access tokens is
+    /// backdated by this duration, such that hosts with clocks running slightly ahead do not fail
+    /// with "JWT not yet valid"; zero by default.
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Requested lifetime of access tokens, at most and by default one hour. Tokens are refreshed
+    /// after this lifetime (minus the refresh buffer) even if Google issued them for longer, which
+    /// allows for enforcing short-lived tokens.
+    pub fn with_token_lifetime(mut self, token_lifetime: Duration) -> Self {
+        self.token_lifetime = token_lifetime;
+        self
+    }
+
+    /// Store-and-forward mode: publish requests failing because the Pub/Sub service cannot be
+    /// reached are appended to the spool file at the given path, holding at most `max_bytes`, and
+    /// get published in order before subsequent publish requests or by
+    /// [PubSubClient::run_spool]. As timed out requests are spooled, too, messages might get
+    /// published more than once.
+    pub fn with_spool<P>(mut self, path: P, max_bytes: u64) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.spool = Some((path.into(), max_bytes));
+        self
+    }
+
+    /// Retry publish requests failing with a transient error, e.g. 429, 5xx or connection errors,
+    /// according to the given settings; not retried by default. Takes precedence over the retry
+    /// policy, if any, for publish requests.
+    pub fn with_publish_retry(mut self, publish_retry: PublishRetryConfig) -> Self {
+        self.publish_retry = Some(publish_retry);
+        self
+    }
+
+    /// Retry all requests – publishing, pulling, acknowledging and all others – according to the
+    /// given policy; not retried by default. Can be overridden for single calls with
+    /// [PubSubClient::with_request_retry]. Notice that retrying non-idempotent requests, e.g.
+    /// creating a topic, may fail with a conflict if the original request actually succeeded.
+    pub fn with_request_retry(mut self, request_retry: RequestRetryPolicy) -> Self {
+        self.request_retry = Some(request_retry);
+        self
+    }
+
+    /// Compress the data of messages published via [PubSubClient::publish] and its typed variants
+    /// as well as [PubSubClient::publish_bytes], but not [PubSubClient::publish_raw], according to
+    /// the given settings; not compressed by default. Pulled messages are decompressed according
+    /// to their `content-encoding` attribute regardless of this setting.
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Encrypt the data of all published messages with the given [PayloadCipher] and decrypt the
+    /// data of pulled messages marked as encrypted; compression, if any, is applied before
+    /// encrypting. Pulled messages which cannot be decrypted fail with [Error::Decrypt].
+    pub fn with_cipher<C>(mut self, cipher: C) -> Self
+    where
+        C: PayloadCipher + 'static,
+    {
+        self.cipher = Some(Cipher::new(cipher));
+        self
+    }
+
+    /// Attach the CRC32C checksum of the data – after compressing, but before encrypting – to all
+    /// published messages as `crc32c` attribute; disabled by default. Pulled messages with this
+    /// attribute are verified regardless of this setting and fail with
+    /// [Error::IntegrityCheckFailed] if corrupted.
+    pub fn with_crc32c(mut self, crc32c: bool) -> Self {
+        self.crc32c = crc32c;
+        self
+    }
+
+    /// Use the given default timeouts for requests for which no timeout is given, separately for
+    /// publishing, pulling, acknowledging and all other requests; by default only the timeout of
+    /// the HTTP client, if any, applies.
+    pub fn with_default_timeouts(mut self, default_timeouts: DefaultTimeouts) -> Self {
+        self.default_timeouts = default_timeouts;
+        self
+    }
+
+    /// Apply the given [Interceptor] to all requests to the Pub/Sub service – but not to requests
+    /// for fetching access tokens – e.g. to add audit headers or to inject failures for chaos
+    /// testing. Can be called multiple times; interceptors are applied in the order they were
+    /// added.
+    pub fn with_interceptor<I>(mut self, interceptor: I) -> Self
+    where
+        I: Interceptor + 'static,
+    {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Use JWTs signed with the private key of the service account directly as access tokens
+    /// instead of exchanging them at the token endpoint, which saves a round trip, e.g. for fast
+    /// cold starts, and works without access to `oauth2.googleapis.com`; disabled by default.
+    /// Only applies to service account credentials.
+    pub fn with_self_signed_jwt(mut self, self_signed_jwt: bool) -> Self {
+        self.self_signed_jwt = self_signed_jwt;
+        self
+    }
+
+    /// Use the given – preconfigured, e.g. with a proxy or connection pool limits – HTTP client for
+    /// all requests, including fetching access tokens, instead of a default one.
+    pub fn with_reqwest_client(mut self, reqwest_client: reqwest::Client) -> Self {
+        self.reqwest_client = Some(reqwest_client);
+        self
+    }
+
+    /// Trust the given root certificate in addition to the ones of the TLS backend – selected via
+    /// the `native-tls` (default) or `rustls-tls` feature – e.g. for a TLS-intercepting proxy. Can
+    /// be called multiple times, but cannot be combined with [Self::with_reqwest_client].
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn with_root_certificate(mut self, root_certificate: reqwest::Certificate) -> Self {
+        self.root_certificates.push(root_certificate);
+        self
+    }
+
+    /// Build the [PubSubClient] and verify it, i.e. fetch an access token and get the topic with
+    /// the given ID – or, if not given, list at most one topic – such that bad credentials or
+    /// missing IAM roles fail at startup with [Error::Initialization] rather than when publishing
+    /// or pulling for the first time, see [PubSubClient::verify].
+    pub async fn build_verified(
+        self,
+        topic_id: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<PubSubClient, Error> {
+        let client = self.build()?;
+
+        let verified = async {
+            client.verify(topic_id, timeout).await?;
+            if topic_id.is_none() {
+                let topics = client.list_topics(Some(1), timeout);
+                pin!(topics).next().await.transpose()?;
+            }
+            Ok::<_, Error>(())
+        };
+        verified.await.map_err(|error| Error::Initialization {
+            reason: "verifying credentials and access failed".to_string(),
+            source: error.into(),
+        })?;
+
+        Ok(client)
+    }
+
+    pub fn build(self) -> Result<PubSubClient, Error> {
+        let Self {
+            credentials,
+            project_id,
+            base_url,
+            region,
+            quota_project_id,
+            application_name,
+            refresh_buffer,
+            clock_skew,
+            token_lifetime,
+            spool,
+            publish_retry,
+            request_retry,
+            compression,
+            cipher,
+            crc32c,
+            default_timeouts,
+            interceptors,
+            self_signed_jwt,
+            reqwest_client,
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            root_certificates,
+        } = self;
+
+        if token_lifetime.is_zero() || token_lifetime > MAX_TOKEN_LIFETIME {
+            return Err(Error::Initialization {
+                reason: format!("invalid token_lifetime `{token_lifetime:?}`"),
+                source: format!("must be positive and at most `{MAX_TOKEN_LIFETIME:?}`").into(),
+            });
+        }
+        if refresh_buffer >= token_lifetime {
+            return Err(Error::Initialization {
+                reason: format!("invalid refresh_buffer `{refresh_buffer:?}`"),
+                source: format!("must be less than token lifetime `{token_lifetime:?}`").into(),
+            });
+        }
+        if clock_skew >= token_lifetime {
+            return Err(Error::Initialization {
+                reason: format!("invalid clock_skew `{clock_skew:?}`"),
+                source: format!("must be less than token lifetime `{token_lifetime:?}`").into(),
+            });
+        }
+
+        if let Some(region) = &region {
+            if !is_valid_region(region) {
+                return Err(Error::Initialization {
+                    reason: format!("invalid region `{region}`"),
+                    source:
+                        "must be non-empty and only contain lowercase letters, digits and hyphens"
+                            .into(),
+                });
+            }
+        }
+
+        let (credentials_project_id, token_source) =
+            token_source(credentials, clock_skew, self_signed_jwt)?;
+        let project_id = project_id
+            .or(credentials_project_id)
+            .or_else(|| env::var(PROJECT_ENV_VAR).ok())
+            .ok_or_else(|| Error::Initialization {
+                reason: "missing project ID".to_string(),
+                source: format!(
+                    "credentials contain no project ID, hence it must be given via `with_project_id` or `{PROJECT_ENV_VAR}`"
+                )
+                .into(),
+            })?;
+
+        // Like all official Pub/Sub clients, use the emulator without authentication if
+        // `PUBSUB_EMULATOR_HOST` is set, unless the base URL is given explicitly.
+        let (base_url, token_source) = match (base_url, emulator_base_url()) {
+            (Some(base_url), _) => (base_url, token_source),
+            (None, Some(emulator_base_url)) => {
+                debug!(emulator_base_url, "using emulator without authentication");
+                (emulator_base_url, None)
+            }
+            (None, None) => {
+                let base_url = env::var(BASE_URL_ENV_VAR)
+                    .ok()
+                    .or_else(|| region.as_deref().map(regional_base_url))
+                    .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+                (base_url, token_source)
+            }
+        };
+        let base_url = base_url.trim_end_matches('/');
+        let api_url = format!("{base_url}/v1");
+        let project_url = format!("{api_url}/projects/{project_id}");
+
+        let (user_agent, api_client) = client_headers(application_name.as_deref());
+        #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+        let reqwest_client = match reqwest_client {
+            Some(_) if !root_certificates.is_empty() => {
+                return Err(Error::Initialization {
+                    reason: "root certificates given along with a preconfigured HTTP client"
+                        .to_string(),
+                    source: "root certificates must be added to the preconfigured HTTP client"
+                        .into(),
+                })
+            }
+            Some(reqwest_client) => reqwest_client,
+            None => root_certificates
+                .into_iter()
+                .fold(reqwest::Client::builder(), |b, c| b.add_root_certificate(c))
+                .build()
+                .map_err(|source| Error::Initialization {
+                    reason: "cannot create HTTP client".to_string(),
+                    source: source.into(),
+                })?,
+        };
+        #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+        let reqwest_client = reqwest_client.unwrap_or_default();
+        Ok(PubSubClient {
+            project_id,
+            api_url,
+            project_url,
+            token_fetcher: token_source.map(|token_source| {
+                Arc::new(TokenFetcher::new(
+                    token_source,
+                    refresh_buffer,
+                    token_lifetime,
+                    reqwest_client.clone(),
+                ))
+            }),
+            reqwest_client,
+            spool: spool.map(|(path, max_bytes)| Arc::new(Spool::new(path, max_bytes))),
+            publish_retry: publish_retry.map(|config| Arc::new(PublishRetry::new(config))),
+            request_retry: request_retry.map(Arc::new),
+            compression,
+            cipher,
+            crc32c,
+            default_timeouts,
+            interceptors,
+            region,
+            quota_project_id,
+            user_agent,
+            api_client,
+            #[cfg(feature = "avro")]
+            avro_codecs: Default::default(),
+        })
+    }
+}
+
+/// Returns the values for the `User-Agent` and `x-goog-api-client` headers.
+fn client_headers(application_name: Option<&str>) -> (String, String) {
+    let version = env!("CARGO_PKG_VERSION");
+    let user_agent = format!("{}/{version}", env!("CARGO_PKG_NAME"));
+    let api_client = format!("gccl/{version}");
+    match application_name {
+        Some(application_name) => (
+            format!("{user_agent} {application_name}"),
+            format!("{api_client} {application_name}"),
+        ),
+        None => (user_agent, api_client),
+    }
+}
+
+fn is_valid_region(region: &str) -> bool {
+    !region.is_empty()
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn regional_base_url(region: &str) -> String {
+    format!("https://{region}-pubsub.googleapis.com")
+}
+
+/// Returns the project ID, if known, and the [TokenSource], if any, for the given credentials.
+fn token_source(
+    credentials: CredentialsSource,
+    clock_skew: Duration,
+    self_signed_jwt: bool,
+) -> Result<(Option<String>, Option<TokenSource>), Error> {
+    match credentials {
+        CredentialsSource::KeyPath(key_path) => token_source(
+            CredentialsSource::from_file(&key_path)?,
+            clock_skew,
+            self_signed_jwt,
+        ),
+
+        CredentialsSource::ServiceAccount(credentials) if self_signed_jwt => {
+            let self_signed_jwt = SelfSignedJwt::new(&credentials, clock_skew)?;
+            Ok((
+                Some(credentials.project()),
+                Some(TokenSource::SelfSignedJwt(Box::new(self_signed_jwt))),
+            ))
+        }
+
+        CredentialsSource::ServiceAccount(credentials) => {
+            let token_source = TokenSource::service_account(&credentials, clock_skew)?;
+            Ok((Some(credentials.project()), Some(token_source)))
+        }
+
+        CredentialsSource::ExternalAccount(external_account) => {
+            Ok((None, Some(TokenSource::ExternalAccount(external_account))))
+        }
+
+        CredentialsSource::AuthorizedUser(authorized_user) => {
+            Ok((None, Some(TokenSource::AuthorizedUser(authorized_user))))
+        }
+
+        CredentialsSource::Anonymous => Ok((None, None)),
+
+        CredentialsSource::MetadataServer { project_id } => {
+            Ok((Some(project_id), Some(TokenSource::MetadataServer)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_headers;
+
+    #[test]
+    fn test_client_headers() {
+        let version = env!("CARGO_PKG_VERSION");
+
+        let (user_agent, api_client) = client_headers(None);
+        assert_eq!(user_agent, format!("pub-sub-client/{version}"));
+        assert_eq!(api_client, format!("gccl/{version}"));
+
+        let (user_agent, api_client) = client_headers(Some("my-app/1.2.3"));
+        assert_eq!(user_agent, format!("pub-sub-client/{version} my-app/1.2.3"));
+        assert_eq!(api_client, format!("gccl/{version} my-app/1.2.3"));
+    }
+}
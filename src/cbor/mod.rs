@@ -0,0 +1,71 @@
+use crate::{Decoder, Encoder, RawPulledMessageEnvelope};
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error as StdError;
+
+/// [Encoder] and [Decoder] for CBOR, to be used with
+/// [PubSubClient::publish_with_encoder](crate::PubSubClient::publish_with_encoder) and
+/// [PubSubClient::pull_with_decoder](crate::PubSubClient::pull_with_decoder).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+impl<M> Encoder<M> for CborCodec
+where
+    M: Serialize,
+{
+    fn encode(&self, message: &M) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync + 'static>> {
+        let mut data = Vec::new();
+        ciborium::into_writer(message, &mut data)?;
+        Ok(data)
+    }
+}
+
+impl<M> Decoder<M> for CborCodec
+where
+    M: DeserializeOwned,
+{
+    fn decode(
+        &self,
+        _envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>> {
+        ciborium::from_reader(data).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CborCodec;
+    use crate::{Decoder, Encoder, RawPulledMessage, RawPulledMessageEnvelope};
+    use serde::{Deserialize, Serialize};
+    use time::OffsetDateTime;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        text: String,
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let envelope = RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: None,
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let message = Message {
+            text: "test".to_string(),
+        };
+
+        let data = CborCodec.encode(&message).unwrap();
+        let decoded: Message = CborCodec.decode(&envelope, &data).unwrap();
+        assert_eq!(decoded, message);
+
+        let decoded: Result<Message, _> = CborCodec.decode(&envelope, &[0xff]);
+        assert!(decoded.is_err());
+    }
+}
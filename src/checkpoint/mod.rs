@@ -0,0 +1,132 @@
+use crate::{
+    dedup::{DedupConfig, Deduplicator},
+    error::Error,
+    PubSubClient, PulledMessage,
+};
+use futures::future::{self, BoxFuture};
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Mutex,
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_ENTRIES: usize = 100_000;
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Persists the IDs of processed messages, such that messages redelivered by Pub/Sub, e.g. after a
+/// restart, can be skipped, enabling effectively-once processing for non-idempotent handlers. Can
+/// be used with [Consumer::with_checkpoint_store](crate::Consumer::with_checkpoint_store) and
+/// [StreamOptions::with_checkpoint_store](crate::StreamOptions::with_checkpoint_store).
+pub trait CheckpointStore: Send + Sync {
+    /// Whether the message with the given ID has already been processed.
+    fn is_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<bool, Error>>;
+
+    /// Record the message with the given ID as processed.
+    fn mark_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<(), Error>>;
+}
+
+impl Debug for dyn CheckpointStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CheckpointStore").finish_non_exhaustive()
+    }
+}
+
+/// [CheckpointStore] keeping the processed message IDs in memory, i.e. not across restarts,
+/// bounded like the deduplication window by a [DedupConfig]: by default at most 100,000 IDs
+/// processed within the last hour are remembered.
+#[derive(Debug)]
+pub struct InMemoryCheckpointStore {
+    message_ids: Mutex<Deduplicator>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new(config: DedupConfig) -> Self {
+        Self {
+            message_ids: Mutex::new(Deduplicator::new(config)),
+        }
+    }
+}
+
+impl Default for InMemoryCheckpointStore {
+    fn default() -> Self {
+        Self::new(DedupConfig::new(DEFAULT_MAX_ENTRIES, DEFAULT_TTL))
+    }
+}
+
+impl CheckpointStore for InMemoryCheckpointStore {
+    fn is_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+        let processed = self
+            .message_ids
+            .lock()
+            .expect("lock message IDs")
+            .is_duplicate(message_id);
+        Box::pin(future::ready(Ok(processed)))
+    }
+
+    fn mark_processed<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        self.message_ids
+            .lock()
+            .expect("lock message IDs")
+            .record(message_id);
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+impl PubSubClient {
+    /// Return the given messages which have not been processed according to the given
+    /// [CheckpointStore] and acknowledge the processed ones. Messages for which the store fails
+    /// are neither returned nor acknowledged, i.e. they get redelivered.
+    pub(crate) async fn drop_processed<M>(
+        &self,
+        subscription_id: &str,
+        messages: Vec<PulledMessage<M>>,
+        checkpoint_store: &dyn CheckpointStore,
+    ) -> Vec<PulledMessage<M>> {
+        let mut unprocessed = Vec::with_capacity(messages.len());
+        let mut processed_ack_ids = vec![];
+        for message in messages {
+            match checkpoint_store.is_processed(&message.id).await {
+                Ok(false) => unprocessed.push(message),
+                Ok(true) => {
+                    debug!(id = message.id, "skipping processed message");
+                    processed_ack_ids.push(message.ack_id);
+                }
+                Err(error) => warn!(%error, id = message.id, "cannot check checkpoint store"),
+            }
+        }
+
+        if !processed_ack_ids.is_empty() {
+            let ack_ids = processed_ack_ids.iter().map(|id| &id[..]).collect();
+            if let Err(error) = self.acknowledge(subscription_id, ack_ids, None).await {
+                warn!(%error, subscription_id, "cannot acknowledge processed messages");
+            }
+        }
+
+        unprocessed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckpointStore, InMemoryCheckpointStore};
+    use crate::DedupConfig;
+    use futures::executor::block_on;
+    use std::time::Duration;
+
+    #[test]
+    fn test_in_memory_checkpoint_store() {
+        let store = InMemoryCheckpointStore::default();
+        assert!(!block_on(store.is_processed("id")).unwrap());
+        assert!(block_on(store.mark_processed("id")).is_ok());
+        assert!(block_on(store.is_processed("id")).unwrap());
+        assert!(!block_on(store.is_processed("other")).unwrap());
+
+        // Bounded, hence the oldest IDs are forgotten
+        let store = InMemoryCheckpointStore::new(DedupConfig::new(1, Duration::from_secs(60)));
+        assert!(block_on(store.mark_processed("1")).is_ok());
+        assert!(block_on(store.mark_processed("2")).is_ok());
+        assert!(!block_on(store.is_processed("1")).unwrap());
+        assert!(block_on(store.is_processed("2")).unwrap());
+    }
+}
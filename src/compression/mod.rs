@@ -0,0 +1,168 @@
+use crate::error::Error;
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+};
+
+/// Attribute marking the compression of the data of a message, e.g. `gzip` or `zstd`.
+pub const CONTENT_ENCODING_ATTRIBUTE: &str = "content-encoding";
+
+type Attributes = Option<HashMap<String, String>>;
+
+/// Compression algorithm for the data of published messages; available via the `gzip` and `zstd`
+/// features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Value of the [CONTENT_ENCODING_ATTRIBUTE] for this compression.
+    pub fn content_encoding(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    fn compress(&self, _data: &[u8]) -> io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(_data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::encode_all(_data, 0),
+        }
+    }
+}
+
+/// Settings for compressing the data of published messages, see
+/// [PubSubClientBuilder::with_compression](crate::PubSubClientBuilder::with_compression): data of
+/// at least `threshold` bytes is compressed and marked via the [CONTENT_ENCODING_ATTRIBUTE].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    pub compression: Compression,
+    pub threshold: usize,
+}
+
+impl CompressionConfig {
+    pub fn new(compression: Compression, threshold: usize) -> Self {
+        Self {
+            compression,
+            threshold,
+        }
+    }
+}
+
+/// Compress the given data according to the given settings, if any, and mark it in the given
+/// attributes.
+pub(crate) fn compress(
+    config: Option<&CompressionConfig>,
+    data: Vec<u8>,
+    attributes: Attributes,
+) -> Result<(Vec<u8>, Attributes), Error> {
+    match config {
+        Some(config) if data.len() >= config.threshold => {
+            let data = config
+                .compression
+                .compress(&data)
+                .map_err(Error::Compress)?;
+            let mut attributes = attributes.unwrap_or_default();
+            attributes.insert(
+                CONTENT_ENCODING_ATTRIBUTE.to_string(),
+                config.compression.content_encoding().to_string(),
+            );
+            Ok((data, Some(attributes)))
+        }
+        _ => Ok((data, attributes)),
+    }
+}
+
+/// Decompress the given data according to the [CONTENT_ENCODING_ATTRIBUTE] of the given
+/// attributes, if any.
+pub(crate) fn decompress(
+    attributes: Option<&HashMap<String, String>>,
+    data: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    let content_encoding = attributes
+        .and_then(|attributes| attributes.get(CONTENT_ENCODING_ATTRIBUTE))
+        .map(String::as_str);
+    match content_encoding {
+        None | Some("identity") => Ok(data),
+
+        #[cfg(feature = "gzip")]
+        Some("gzip") => {
+            use std::io::Read;
+            let mut decompressed = Vec::new();
+            flate2::read::GzDecoder::new(data.as_slice())
+                .read_to_end(&mut decompressed)
+                .map_err(Error::Decompress)?;
+            Ok(decompressed)
+        }
+
+        #[cfg(feature = "zstd")]
+        Some("zstd") => zstd::decode_all(data.as_slice()).map_err(Error::Decompress),
+
+        Some(content_encoding) => Err(Error::Decompress(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("unsupported content encoding `{content_encoding}`"),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, CONTENT_ENCODING_ATTRIBUTE};
+    use crate::Error;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_decompress_uncompressed() {
+        let data = b"test".to_vec();
+        assert_eq!(decompress(None, data.clone()).unwrap(), data);
+
+        let attributes = HashMap::from([(
+            CONTENT_ENCODING_ATTRIBUTE.to_string(),
+            "unknown".to_string(),
+        )]);
+        let result = decompress(Some(&attributes), data);
+        assert!(matches!(result, Err(Error::Decompress(_))));
+    }
+
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn test_compress_decompress() {
+        use super::{compress, Compression, CompressionConfig};
+
+        let data = b"test test test test".to_vec();
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let config = CompressionConfig::new(compression, 10);
+            let (compressed, attributes) = compress(Some(&config), data.clone(), None).unwrap();
+            assert_ne!(compressed, data);
+            assert_eq!(
+                attributes
+                    .as_ref()
+                    .and_then(|a| a.get(CONTENT_ENCODING_ATTRIBUTE))
+                    .map(String::as_str),
+                Some(compression.content_encoding())
+            );
+            assert_eq!(decompress(attributes.as_ref(), compressed).unwrap(), data);
+
+            // Below threshold
+            let (uncompressed, attributes) =
+                compress(Some(&config), b"test".to_vec(), None).unwrap();
+            assert_eq!(uncompressed, b"test");
+            assert_eq!(attributes, None);
+        }
+    }
+}
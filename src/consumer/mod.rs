@@ -0,0 +1,524 @@
+#[cfg(feature = "avro")]
+use crate::AvroCodec;
+#[cfg(feature = "cbor")]
+use crate::CborCodec;
+#[cfg(feature = "prost")]
+use crate::ProtobufDecoder;
+use crate::{
+    checkpoint::CheckpointStore,
+    decoder::{decode, Decoder, JsonDecoder},
+    error::Error,
+    publisher::publish_request_body,
+    retry::RetryConfig,
+    subscribe::unless_cancelled,
+    PubSubClient, PulledMessage, RawPublishedMessage, RawPulledMessageEnvelope,
+};
+use futures::{stream, StreamExt};
+use serde::de::DeserializeOwned;
+#[cfg(feature = "config")]
+use serde::Deserialize;
+use std::{
+    error::Error as StdError,
+    fmt::{Debug, Display},
+    future::Future,
+    slice,
+    sync::Arc,
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+const DEFAULT_CONCURRENCY: usize = 1;
+const DEFAULT_MAX_MESSAGES: u32 = 100;
+
+/// Settings for a [Consumer]. With the `config` feature these can be deserialized, e.g. from the
+/// TOML or YAML configuration of an application.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+pub struct ConsumerConfig {
+    pub subscription_id: String,
+    /// Maximum number of messages handled concurrently.
+    #[cfg_attr(feature = "config", serde(default = "default_concurrency"))]
+    pub concurrency: usize,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub flow_control: FlowControlConfig,
+    /// Retry settings for pulling.
+    #[cfg_attr(feature = "config", serde(default))]
+    pub retry: RetryConfig,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub codec: Codec,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub dead_letter: Option<DeadLetterConfig>,
+}
+
+impl ConsumerConfig {
+    pub fn new<T>(subscription_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            subscription_id: subscription_id.into(),
+            concurrency: DEFAULT_CONCURRENCY,
+            flow_control: FlowControlConfig::default(),
+            retry: RetryConfig::default(),
+            codec: Codec::default(),
+            dead_letter: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+#[cfg_attr(feature = "config", serde(default))]
+pub struct FlowControlConfig {
+    /// Maximum number of messages per pull request.
+    pub max_messages: u32,
+    /// Timeout for pull requests.
+    #[cfg_attr(feature = "config", serde(with = "humantime_serde"))]
+    pub pull_timeout: Option<Duration>,
+}
+
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            pull_timeout: None,
+        }
+    }
+}
+
+/// Format of the data of pulled messages, determining the [Decoder] a [Consumer] uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+#[cfg_attr(feature = "config", serde(rename_all = "snake_case"))]
+pub enum Codec {
+    /// JSON, see [JsonDecoder].
+    #[default]
+    Json,
+    /// CBOR, see [CborCodec].
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// Protobuf, see [ProtobufDecoder]; requires [Consumer::run_protobuf].
+    #[cfg(feature = "prost")]
+    Protobuf,
+    /// Avro with the schema of the topic of the subscription, see [PubSubClient::avro_codec].
+    #[cfg(feature = "avro")]
+    Avro,
+}
+
+/// [Decoder] for the [Codec]s of serde types.
+enum SerdeDecoder {
+    Json(JsonDecoder),
+    #[cfg(feature = "cbor")]
+    Cbor(CborCodec),
+    #[cfg(feature = "avro")]
+    Avro(Arc<AvroCodec>),
+}
+
+impl<M> Decoder<M> for SerdeDecoder
+where
+    M: DeserializeOwned,
+{
+    fn decode(
+        &self,
+        envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>> {
+        match self {
+            SerdeDecoder::Json(decoder) => decoder.decode(envelope, data),
+            #[cfg(feature = "cbor")]
+            SerdeDecoder::Cbor(decoder) => decoder.decode(envelope, data),
+            #[cfg(feature = "avro")]
+            SerdeDecoder::Avro(decoder) => decoder.decode(envelope, data),
+        }
+    }
+}
+
+/// Client-side dead-letter policy: messages which cannot be deserialized or have exceeded the
+/// maximum number of delivery attempts are published to the dead-letter topic and acknowledged.
+///
+/// Notice that Pub/Sub only counts delivery attempts for subscriptions with a dead-letter policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+pub struct DeadLetterConfig {
+    pub topic_id: String,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub max_delivery_attempts: Option<u32>,
+}
+
+#[cfg(feature = "config")]
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+/// Continuously pulls messages from a subscription and hands them to a handler according to its
+/// [ConsumerConfig].
+#[derive(Debug)]
+pub struct Consumer<'a> {
+    client: &'a PubSubClient,
+    config: ConsumerConfig,
+    cancellation_token: Option<CancellationToken>,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl<'a> Consumer<'a> {
+    pub fn from_config(client: &'a PubSubClient, config: ConsumerConfig) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::Initialization {
+            reason: "invalid consumer config".to_string(),
+            source: reason.into(),
+        };
+        if config.subscription_id.is_empty() {
+            return Err(invalid("subscription_id must not be empty"));
+        }
+        if config.concurrency == 0 {
+            return Err(invalid("concurrency must be positive"));
+        }
+        if config.flow_control.max_messages == 0 {
+            return Err(invalid("flow_control.max_messages must be positive"));
+        }
+
+        Ok(Self {
+            client,
+            config,
+            cancellation_token: None,
+            checkpoint_store: None,
+        })
+    }
+
+    /// Gracefully shut down once the given token is cancelled: pulling is stopped, messages
+    /// already pulled are handled and acknowledged and then [Consumer::run] resolves.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Skip and acknowledge messages already processed according to the given [CheckpointStore]
+    /// and mark handled messages as processed before acknowledging them. Messages for which the
+    /// store fails are not acknowledged, i.e. they get redelivered.
+    pub fn with_checkpoint_store(mut self, checkpoint_store: Arc<dyn CheckpointStore>) -> Self {
+        self.checkpoint_store = Some(checkpoint_store);
+        self
+    }
+
+    pub fn config(&self) -> &ConsumerConfig {
+        &self.config
+    }
+
+    /// Pull messages, decode them with the configured [Codec] and hand them to the given handler
+    /// until pulling fails even after retrying. Messages are acknowledged if the handler succeeds,
+    /// else they get redelivered after their acknowledge deadline has expired. If a cancellation
+    /// token is set, this also resolves once it is cancelled.
+    pub async fn run<M, H, F, E>(&self, handler: H) -> Result<(), Error>
+    where
+        M: DeserializeOwned,
+        H: Fn(PulledMessage<M>) -> F,
+        F: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let decoder = self.serde_decoder().await?;
+        self.run_with_decoder(&decoder, handler).await
+    }
+
+    /// Like [Consumer::run], but for protobuf messages, which requires the [Codec::Protobuf].
+    #[cfg(feature = "prost")]
+    pub async fn run_protobuf<M, H, F, E>(&self, handler: H) -> Result<(), Error>
+    where
+        M: prost::Message + Default,
+        H: Fn(PulledMessage<M>) -> F,
+        F: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        if self.config.codec != Codec::Protobuf {
+            return Err(Error::Initialization {
+                reason: "invalid consumer config".to_string(),
+                source: "codec must be protobuf for Consumer::run_protobuf".into(),
+            });
+        }
+        self.run_with_decoder(&ProtobufDecoder, handler).await
+    }
+
+    /// Returns the [Decoder] for the configured [Codec], getting the Avro schema of the topic of
+    /// the subscription if needed.
+    async fn serde_decoder(&self) -> Result<SerdeDecoder, Error> {
+        match self.config.codec {
+            Codec::Json => Ok(SerdeDecoder::Json(JsonDecoder)),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => Ok(SerdeDecoder::Cbor(CborCodec)),
+            #[cfg(feature = "prost")]
+            Codec::Protobuf => Err(Error::Initialization {
+                reason: "invalid consumer config".to_string(),
+                source: "codec protobuf requires Consumer::run_protobuf".into(),
+            }),
+            #[cfg(feature = "avro")]
+            Codec::Avro => {
+                let subscription = self
+                    .client
+                    .get_subscription(&self.config.subscription_id, None)
+                    .await?;
+                let codec = self.client.avro_codec(&subscription.topic, None).await?;
+                Ok(SerdeDecoder::Avro(codec))
+            }
+        }
+    }
+
+    async fn run_with_decoder<M, D, H, F, E>(&self, decoder: &D, handler: H) -> Result<(), Error>
+    where
+        D: Decoder<M>,
+        H: Fn(PulledMessage<M>) -> F,
+        F: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let ConsumerConfig {
+            subscription_id,
+            concurrency,
+            flow_control,
+            retry,
+            ..
+        } = &self.config;
+
+        let mut retries = 0;
+        loop {
+            let pull = self.client.pull_raw(
+                subscription_id,
+                flow_control.max_messages,
+                flow_control.pull_timeout,
+            );
+            let Some(result) = unless_cancelled(self.cancellation_token.as_ref(), pull).await
+            else {
+                debug!(subscription_id, "cancelled, stopping consumer");
+                return Ok(());
+            };
+            let envelopes = match result {
+                Ok(envelopes) => {
+                    retries = 0;
+                    envelopes
+                }
+                Err(error) => match retry.backoff(retries) {
+                    Some(backoff) => {
+                        warn!(%error, ?backoff, subscription_id, "pulling failed, retrying");
+                        tokio::time::sleep(backoff).await;
+                        retries += 1;
+                        continue;
+                    }
+                    None => return Err(error),
+                },
+            };
+
+            stream::iter(envelopes)
+                .for_each_concurrent(*concurrency, |envelope| {
+                    self.handle(envelope, decoder, &handler)
+                })
+                .await;
+        }
+    }
+
+    async fn handle<M, D, H, F, E>(
+        &self,
+        envelope: RawPulledMessageEnvelope,
+        decoder: &D,
+        handler: &H,
+    ) where
+        D: Decoder<M>,
+        H: Fn(PulledMessage<M>) -> F,
+        F: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let ack_id = envelope.ack_id.clone();
+        let id = envelope.message.id.clone();
+
+        if let Some(checkpoint_store) = &self.checkpoint_store {
+            match checkpoint_store.is_processed(&id).await {
+                Ok(false) => (),
+                Ok(true) => {
+                    debug!(id, "skipping processed message");
+                    self.acknowledge(&ack_id, &id).await;
+                    return;
+                }
+                Err(error) => {
+                    warn!(%error, id, "cannot check checkpoint store");
+                    return;
+                }
+            }
+        }
+
+        let dead_letter = self.config.dead_letter.as_ref().map(|dead_letter| {
+            let message = RawPublishedMessage {
+                data: envelope.message.data.clone(),
+                attributes: envelope.message.attributes.clone(),
+                ordering_key: None,
+            };
+            (dead_letter, message)
+        });
+
+        if let Some((dead_letter, message)) = &dead_letter {
+            if dead_letter
+                .max_delivery_attempts
+                .is_some_and(|max| envelope.delivery_attempt > max)
+            {
+                self.dead_letter(&dead_letter.topic_id, message, &ack_id, &id)
+                    .await;
+                return;
+            }
+        }
+
+        let pulled_message = decode(vec![envelope], decoder)
+            .pop()
+            .expect("one envelope is decoded into one message");
+
+        if let (Err(error), Some((dead_letter, message))) = (&pulled_message.message, &dead_letter)
+        {
+            warn!(%error, id, "cannot decode message");
+            self.dead_letter(&dead_letter.topic_id, message, &ack_id, &id)
+                .await;
+            return;
+        }
+
+        match handler(pulled_message).await {
+            Ok(()) => {
+                if let Some(checkpoint_store) = &self.checkpoint_store {
+                    if let Err(error) = checkpoint_store.mark_processed(&id).await {
+                        warn!(%error, id, "cannot mark message as processed");
+                        return;
+                    }
+                }
+                self.acknowledge(&ack_id, &id).await;
+            }
+            Err(error) => warn!(%error, id, "handling message failed"),
+        }
+    }
+
+    async fn acknowledge(&self, ack_id: &str, id: &str) {
+        if let Err(error) = self
+            .client
+            .acknowledge(&self.config.subscription_id, vec![ack_id], None)
+            .await
+        {
+            warn!(%error, id, "cannot acknowledge message");
+        }
+    }
+
+    async fn dead_letter(
+        &self,
+        topic_id: &str,
+        message: &RawPublishedMessage<'_>,
+        ack_id: &str,
+        id: &str,
+    ) {
+        // Forward data and attributes unchanged, i.e. neither validate nor process them like
+        // `publish_raw`, such that e.g. `goog*` attributes of schema-enabled topics are kept.
+        let result = match publish_request_body(slice::from_ref(message)) {
+            Ok(body) => self.client.send_publish_request(topic_id, body, None).await,
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(_) => {
+                debug!(id, topic_id, "published message to dead-letter topic");
+                if let Err(error) = self
+                    .client
+                    .acknowledge(&self.config.subscription_id, vec![ack_id], None)
+                    .await
+                {
+                    warn!(%error, id, "cannot acknowledge dead-lettered message");
+                }
+            }
+            Err(error) => warn!(%error, id, topic_id, "cannot publish to dead-letter topic"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "config"))]
+mod tests {
+    use super::{Codec, Consumer, ConsumerConfig, DeadLetterConfig};
+    use crate::{Error, PubSubClient};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_deserialize_consumer_config() {
+        let config = json!({
+            "subscription_id": "test",
+            "concurrency": 4,
+            "flow_control": { "max_messages": 10, "pull_timeout": "45s" },
+            "retry": { "max_retries": 3, "initial_backoff": "500ms" },
+            "codec": "json",
+            "dead_letter": { "topic_id": "dead-letters", "max_delivery_attempts": 5 }
+        });
+        let config = serde_json::from_value::<ConsumerConfig>(config).unwrap();
+        assert_eq!(config.subscription_id, "test");
+        assert_eq!(config.concurrency, 4);
+        assert_eq!(config.flow_control.max_messages, 10);
+        assert_eq!(
+            config.flow_control.pull_timeout,
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(config.retry.max_retries, 3);
+        assert_eq!(config.retry.initial_backoff, Duration::from_millis(500));
+        assert_eq!(config.retry.max_backoff, Duration::from_secs(60));
+        assert_eq!(config.codec, Codec::Json);
+        assert_eq!(
+            config.dead_letter,
+            Some(DeadLetterConfig {
+                topic_id: "dead-letters".to_string(),
+                max_delivery_attempts: Some(5)
+            })
+        );
+
+        let config = serde_json::from_value::<ConsumerConfig>(json!({ "subscription_id": "test" }));
+        assert_eq!(config.unwrap(), ConsumerConfig::new("test"));
+    }
+
+    #[test]
+    fn test_from_config_invalid_multiplier() {
+        let client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        let config = serde_json::from_value::<ConsumerConfig>(json!({
+            "subscription_id": "test",
+            "retry": { "initial_backoff": "1s", "multiplier": -1.5 }
+        }))
+        .unwrap();
+        let consumer = Consumer::from_config(&client, config).unwrap();
+        assert_eq!(
+            consumer.config().retry.backoff(2),
+            Some(Duration::from_secs(1))
+        );
+
+        let config = ConsumerConfig::new("");
+        let result = Consumer::from_config(&client, config);
+        assert!(matches!(result, Err(Error::Initialization { .. })));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn test_from_config_cbor_codec() {
+        use super::SerdeDecoder;
+        use crate::{Decoder, RawPulledMessage, RawPulledMessageEnvelope};
+        use time::OffsetDateTime;
+
+        let client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        let config = serde_json::from_value::<ConsumerConfig>(json!({
+            "subscription_id": "test",
+            "codec": "cbor"
+        }))
+        .unwrap();
+        assert_eq!(config.codec, Codec::Cbor);
+
+        let consumer = Consumer::from_config(&client, config).unwrap();
+        let decoder = consumer.serde_decoder().await.unwrap();
+        assert!(matches!(decoder, SerdeDecoder::Cbor(_)));
+
+        let mut data = Vec::new();
+        ciborium::into_writer("test", &mut data).unwrap();
+        let envelope = RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: None,
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let message: String = decoder.decode(&envelope, &data).unwrap();
+        assert_eq!(message, "test");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    error::Error, subscriber::pulled_message, PubSubClient, PulledMessage, RawPulledMessageEnvelope,
+};
+use serde::de::DeserializeOwned;
+use std::{error::Error as StdError, time::Duration};
+
+/// Decodes the data of pulled messages, e.g. from protobuf, Avro or MessagePack, see
+/// [PubSubClient::pull_with_decoder].
+pub trait Decoder<M> {
+    /// Decode the given – already Base64 decoded – data of the given envelope.
+    fn decode(
+        &self,
+        envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>>;
+}
+
+/// [Decoder] for JSON, which is what [PubSubClient::pull] uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonDecoder;
+
+impl<M> Decoder<M> for JsonDecoder
+where
+    M: DeserializeOwned,
+{
+    fn decode(
+        &self,
+        _envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>> {
+        serde_json::from_slice(data).map_err(Into::into)
+    }
+}
+
+impl PubSubClient {
+    /// Like [PubSubClient::pull], but the data of the pulled messages is decoded with the given
+    /// [Decoder] instead of being deserialized from JSON. Messages without data are decoded from
+    /// empty data.
+    #[tracing::instrument(skip(decoder))]
+    pub async fn pull_with_decoder<M, D>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        decoder: &D,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        D: Decoder<M>,
+    {
+        let envelopes = self
+            .pull_raw(subscription_id, max_messages, timeout)
+            .await?;
+        Ok(decode(envelopes, decoder))
+    }
+}
+
+pub(crate) fn decode<M, D>(
+    envelopes: Vec<RawPulledMessageEnvelope>,
+    decoder: &D,
+) -> Vec<PulledMessage<M>>
+where
+    D: Decoder<M>,
+{
+    envelopes
+        .into_iter()
+        .map(|envelope| {
+            let message = envelope
+                .message
+                .data_bytes()
+                .and_then(|data| decoder.decode(&envelope, &data).map_err(Error::Decode));
+            pulled_message(envelope, message)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Decoder, JsonDecoder};
+    use crate::{Error, RawPulledMessage, RawPulledMessageEnvelope};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde_json::{json, Value};
+    use std::error::Error as StdError;
+    use time::OffsetDateTime;
+
+    struct Utf8Decoder;
+
+    impl Decoder<String> for Utf8Decoder {
+        fn decode(
+            &self,
+            _envelope: &RawPulledMessageEnvelope,
+            data: &[u8],
+        ) -> Result<String, Box<dyn StdError + Send + Sync + 'static>> {
+            String::from_utf8(data.to_vec()).map_err(Into::into)
+        }
+    }
+
+    fn envelope(data: Option<&[u8]>) -> RawPulledMessageEnvelope {
+        RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: data.map(|data| STANDARD.encode(data)),
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let envelopes = vec![
+            envelope(Some(b"test")),
+            envelope(None),
+            envelope(Some(&[0xff])),
+        ];
+        let messages = decode(envelopes, &Utf8Decoder);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].message.as_ref().unwrap(), "test");
+        assert_eq!(messages[1].message.as_ref().unwrap(), "");
+        assert!(matches!(messages[2].message, Err(Error::Decode(_))));
+
+        let envelopes = vec![envelope(Some(
+            json!({"text": "test"}).to_string().as_bytes(),
+        ))];
+        let messages = decode::<Value, _>(envelopes, &JsonDecoder);
+        assert_eq!(
+            messages[0].message.as_ref().unwrap(),
+            &json!({"text": "test"})
+        );
+    }
+}
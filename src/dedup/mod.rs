@@ -0,0 +1,158 @@
+use crate::{PubSubClient, PulledMessage};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Settings for deduplicating pulled messages by their message ID, see
+/// [StreamOptions::with_dedup](crate::StreamOptions::with_dedup): the IDs of the messages
+/// successfully acknowledged via [ReceivedMessage::ack](crate::ReceivedMessage::ack) within the
+/// time to live are remembered, but at most the given number of them, forgetting the oldest ones
+/// first.
+///
+/// Messages redelivered because they have been negatively acknowledged or their ACK deadline has
+/// expired are not considered duplicates, because they have not been acknowledged. Messages
+/// acknowledged otherwise, e.g. after
+/// [ReceivedMessage::into_message](crate::ReceivedMessage::into_message), are not remembered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupConfig {
+    pub max_entries: usize,
+    pub ttl: Duration,
+}
+
+impl DedupConfig {
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        Self { max_entries, ttl }
+    }
+}
+
+/// Remembers recently acknowledged message IDs according to a [DedupConfig].
+#[derive(Debug)]
+pub(crate) struct Deduplicator {
+    config: DedupConfig,
+    seen: HashMap<String, Instant>,
+    order: VecDeque<(String, Instant)>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(config: DedupConfig) -> Self {
+        Self {
+            config,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Whether the given message ID has been acknowledged recently.
+    pub(crate) fn is_duplicate(&mut self, id: &str) -> bool {
+        self.is_duplicate_at(id, Instant::now())
+    }
+
+    /// Remember the given message ID as successfully acknowledged.
+    pub(crate) fn record(&mut self, id: &str) {
+        self.record_at(id, Instant::now())
+    }
+
+    fn is_duplicate_at(&mut self, id: &str, now: Instant) -> bool {
+        self.evict(now);
+        self.seen.contains_key(id)
+    }
+
+    fn record_at(&mut self, id: &str, now: Instant) {
+        if self.config.max_entries > 0 && !self.seen.contains_key(id) {
+            self.seen.insert(id.to_string(), now);
+            self.order.push_back((id.to_string(), now));
+        }
+        self.evict(now);
+    }
+
+    fn evict(&mut self, now: Instant) {
+        while let Some((id, seen_at)) = self.order.front() {
+            let expired = now.duration_since(*seen_at) >= self.config.ttl;
+            if !expired && self.order.len() <= self.config.max_entries {
+                break;
+            }
+            self.seen.remove(id);
+            self.order.pop_front();
+        }
+    }
+}
+
+impl PubSubClient {
+    /// Return the given messages which are no duplicates according to the given [Deduplicator]
+    /// and acknowledge the duplicates.
+    pub(crate) async fn drop_duplicates<M>(
+        &self,
+        subscription_id: &str,
+        messages: Vec<PulledMessage<M>>,
+        deduplicator: &Mutex<Deduplicator>,
+    ) -> Vec<PulledMessage<M>> {
+        let (duplicates, messages) = {
+            let mut deduplicator = deduplicator.lock().expect("lock deduplicator");
+            messages
+                .into_iter()
+                .partition::<Vec<_>, _>(|message| deduplicator.is_duplicate(&message.id))
+        };
+        if duplicates.is_empty() {
+            return messages;
+        }
+
+        debug!(
+            subscription_id,
+            count = duplicates.len(),
+            "dropping duplicate messages"
+        );
+        let ack_ids = duplicates
+            .iter()
+            .map(|message| message.ack_id.as_str())
+            .collect();
+        if let Err(error) = self.acknowledge(subscription_id, ack_ids, None).await {
+            warn!(%error, subscription_id, "cannot acknowledge duplicate messages");
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupConfig, Deduplicator};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_is_duplicate() {
+        let mut deduplicator = Deduplicator::new(DedupConfig::new(2, Duration::from_secs(60)));
+        let now = Instant::now();
+
+        assert!(!deduplicator.is_duplicate_at("1", now));
+        deduplicator.record_at("1", now);
+        assert!(deduplicator.is_duplicate_at("1", now));
+        deduplicator.record_at("2", now);
+        assert!(deduplicator.is_duplicate_at("1", now));
+
+        // Capacity exceeded, hence "1" is forgotten
+        deduplicator.record_at("3", now);
+        assert!(!deduplicator.is_duplicate_at("1", now));
+        assert!(deduplicator.is_duplicate_at("3", now));
+
+        // Time to live exceeded, hence all are forgotten
+        let later = now + Duration::from_secs(60);
+        assert!(!deduplicator.is_duplicate_at("3", later));
+    }
+
+    #[test]
+    fn test_is_duplicate_redelivered() {
+        let mut deduplicator = Deduplicator::new(DedupConfig::new(2, Duration::from_secs(60)));
+        let now = Instant::now();
+
+        // Negatively acknowledged, hence not recorded and the redelivery is no duplicate
+        assert!(!deduplicator.is_duplicate_at("1", now));
+        assert!(!deduplicator.is_duplicate_at("1", now));
+
+        // Acknowledged after the redelivery, hence further redeliveries are duplicates
+        deduplicator.record_at("1", now);
+        assert!(deduplicator.is_duplicate_at("1", now));
+    }
+}
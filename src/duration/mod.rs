@@ -0,0 +1,101 @@
+//! Serde support for [Duration]s in the JSON representation of protobuf durations, i.e. seconds
+//! with up to nine fractional digits followed by "s", e.g. `"3.5s"`.
+
+use serde::Serializer;
+use std::time::Duration;
+
+pub(crate) fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format(duration))
+}
+
+pub(crate) mod option {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => super::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| super::parse(&s).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Format the given duration like protobuf does, i.e. with 0, 3, 6 or 9 fractional digits.
+pub(crate) fn format(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+    if nanos == 0 {
+        format!("{secs}s")
+    } else if nanos.is_multiple_of(1_000_000) {
+        format!("{secs}.{:03}s", nanos / 1_000_000)
+    } else if nanos.is_multiple_of(1_000) {
+        format!("{secs}.{:06}s", nanos / 1_000)
+    } else {
+        format!("{secs}.{nanos:09}s")
+    }
+}
+
+pub(crate) fn parse(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{s}`");
+
+    let s_without_unit = s.strip_suffix('s').ok_or_else(invalid)?;
+    let (secs, fraction) = s_without_unit
+        .split_once('.')
+        .unwrap_or((s_without_unit, ""));
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let secs = secs.parse::<u64>().map_err(|_| invalid())?;
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        format!("{fraction:0<9}")
+            .parse::<u32>()
+            .map_err(|_| invalid())?
+    };
+    Ok(Duration::new(secs, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, parse};
+    use std::time::Duration;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(&Duration::from_secs(600)), "600s");
+        assert_eq!(format(&Duration::from_millis(3500)), "3.500s");
+        assert_eq!(format(&Duration::from_micros(1_000_001)), "1.000001s");
+        assert_eq!(format(&Duration::from_nanos(1)), "0.000000001s");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("600s"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse("3.5s"), Ok(Duration::from_millis(3500)));
+        assert_eq!(parse("0.000000001s"), Ok(Duration::from_nanos(1)));
+        assert!(parse("600").is_err());
+        assert!(parse("-1s").is_err());
+        assert!(parse("1.0000000001s").is_err());
+        assert!(parse("1.5e3s").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{error::Error, PubSubClient, PublishedMessageEnvelope};
+use serde::Serialize;
+use std::{error::Error as StdError, time::Duration};
+
+/// Encodes the data of messages to be published, e.g. to protobuf, Avro or MessagePack, see
+/// [PubSubClient::publish_with_encoder].
+pub trait Encoder<M> {
+    /// Encode the given message; the result is Base64 encoded when publishing.
+    fn encode(&self, message: &M) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync + 'static>>;
+}
+
+/// [Encoder] for JSON, which is what [PubSubClient::publish] uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonEncoder;
+
+impl<M> Encoder<M> for JsonEncoder
+where
+    M: Serialize,
+{
+    fn encode(&self, message: &M) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync + 'static>> {
+        serde_json::to_vec(message).map_err(Into::into)
+    }
+}
+
+impl PubSubClient {
+    /// Like [PubSubClient::publish], but the messages are encoded with the given [Encoder]
+    /// instead of being serialized to JSON.
+    #[tracing::instrument(skip(envelopes, encoder))]
+    pub async fn publish_with_encoder<M, E, C>(
+        &self,
+        topic_id: &str,
+        envelopes: impl IntoIterator<Item = E>,
+        ordering_key: Option<&'_ str>,
+        timeout: Option<Duration>,
+        encoder: &C,
+    ) -> Result<Vec<String>, Error>
+    where
+        E: Into<PublishedMessageEnvelope<M>>,
+        C: Encoder<M>,
+    {
+        self.publish_encoded(topic_id, envelopes, ordering_key, timeout, |message| {
+            encoder.encode(message).map_err(Error::Encode)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoder, JsonEncoder};
+    use serde_json::json;
+
+    #[test]
+    fn test_json_encoder() {
+        let bytes = JsonEncoder.encode(&json!({"text": "test"})).unwrap();
+        assert_eq!(bytes, br#"{"text":"test"}"#);
+    }
+}
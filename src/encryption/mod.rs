@@ -0,0 +1,174 @@
+use crate::{error::Error, RawPublishedMessage, RawPulledMessageEnvelope};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+use tracing::warn;
+
+/// Attribute marking the data of a message as encrypted by a [PayloadCipher].
+pub const ENCRYPTED_ATTRIBUTE: &str = "encrypted";
+
+/// Symmetric encryption of the data of messages, e.g. KMS-backed envelope encryption, see
+/// [PubSubClientBuilder::with_cipher](crate::PubSubClientBuilder::with_cipher). A pair of
+/// functions, the first encrypting and the second decrypting, is a [PayloadCipher], too.
+pub trait PayloadCipher: Send + Sync {
+    fn encrypt(&self, data: Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>>;
+
+    fn decrypt(&self, data: Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>>;
+}
+
+impl<E, D> PayloadCipher for (E, D)
+where
+    E: Fn(Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>> + Send + Sync,
+    D: Fn(Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>> + Send + Sync,
+{
+    fn encrypt(&self, data: Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>> {
+        (self.0)(data)
+    }
+
+    fn decrypt(&self, data: Bytes) -> Result<Bytes, Box<dyn StdError + Send + Sync + 'static>> {
+        (self.1)(data)
+    }
+}
+
+/// A shared [PayloadCipher].
+#[derive(Clone)]
+pub(crate) struct Cipher(Arc<dyn PayloadCipher>);
+
+impl Cipher {
+    pub(crate) fn new<C>(cipher: C) -> Self
+    where
+        C: PayloadCipher + 'static,
+    {
+        Self(Arc::new(cipher))
+    }
+
+    /// Encrypt the data of the given message and mark it via the [ENCRYPTED_ATTRIBUTE].
+    pub(crate) fn encrypt<'a>(
+        &self,
+        mut message: RawPublishedMessage<'a>,
+    ) -> Result<RawPublishedMessage<'a>, Error> {
+        let data = message
+            .data
+            .as_ref()
+            .map(|data| STANDARD.decode(data))
+            .transpose()
+            .map_err(|error| Error::Encrypt(error.into()))?
+            .unwrap_or_default();
+        let data = self.0.encrypt(data.into()).map_err(Error::Encrypt)?;
+        message.data = Some(STANDARD.encode(data));
+        message
+            .attributes
+            .get_or_insert_with(HashMap::new)
+            .insert(ENCRYPTED_ATTRIBUTE.to_string(), "true".to_string());
+        Ok(message)
+    }
+
+    /// Decrypt the data of the given envelopes marked via the [ENCRYPTED_ATTRIBUTE] and remove
+    /// that attribute. Envelopes failing to be decrypted are left unchanged, such that
+    /// [check_decrypted] fails for them.
+    pub(crate) fn decrypt(&self, envelopes: &mut [RawPulledMessageEnvelope]) {
+        for envelope in envelopes {
+            let message = &mut envelope.message;
+            let Some(attributes) = message.attributes.as_mut() else {
+                continue;
+            };
+            if !attributes.contains_key(ENCRYPTED_ATTRIBUTE) {
+                continue;
+            }
+
+            let data = message
+                .data
+                .as_ref()
+                .map(|data| STANDARD.decode(data))
+                .transpose()
+                .map_err(Into::into)
+                .and_then(|data| self.0.decrypt(data.unwrap_or_default().into()));
+            match data {
+                Ok(data) => {
+                    message.data = Some(STANDARD.encode(data));
+                    attributes.remove(ENCRYPTED_ATTRIBUTE);
+                }
+                Err(error) => warn!(error, message.id, "cannot decrypt message"),
+            }
+        }
+    }
+}
+
+impl Debug for Cipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Cipher").finish_non_exhaustive()
+    }
+}
+
+/// Fail if the given attributes mark the data of a message as still encrypted, i.e. no cipher is
+/// configured or decrypting has failed.
+pub(crate) fn check_decrypted(attributes: Option<&HashMap<String, String>>) -> Result<(), Error> {
+    if attributes.is_some_and(|attributes| attributes.contains_key(ENCRYPTED_ATTRIBUTE)) {
+        return Err(Error::Decrypt(
+            "message is encrypted, but has not been decrypted".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_decrypted, Cipher, ENCRYPTED_ATTRIBUTE};
+    use crate::{Error, RawPublishedMessage, RawPulledMessage, RawPulledMessageEnvelope};
+    use bytes::Bytes;
+    use std::error::Error as StdError;
+    use time::OffsetDateTime;
+
+    type BoxError = Box<dyn StdError + Send + Sync + 'static>;
+
+    fn reverse(data: Bytes) -> Result<Bytes, BoxError> {
+        Ok(data.iter().rev().copied().collect::<Vec<_>>().into())
+    }
+
+    fn fail(_data: Bytes) -> Result<Bytes, BoxError> {
+        Err("cannot decrypt".into())
+    }
+
+    fn envelope(message: RawPublishedMessage<'_>) -> RawPulledMessageEnvelope {
+        RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: message.data,
+                attributes: message.attributes,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = Cipher::new((reverse, reverse));
+        let message = cipher
+            .encrypt(RawPublishedMessage::new("dGVzdA==".to_string()))
+            .unwrap();
+        assert_eq!(message.data.as_deref(), Some("dHNldA=="));
+        let attributes = message.attributes.as_ref();
+        assert!(attributes.is_some_and(|a| a.contains_key(ENCRYPTED_ATTRIBUTE)));
+        assert!(matches!(
+            check_decrypted(attributes),
+            Err(Error::Decrypt(_))
+        ));
+
+        let mut envelopes = vec![envelope(message)];
+        Cipher::new((reverse, fail)).decrypt(&mut envelopes);
+        assert_eq!(envelopes[0].message.data.as_deref(), Some("dHNldA=="));
+        assert!(check_decrypted(envelopes[0].message.attributes.as_ref()).is_err());
+
+        cipher.decrypt(&mut envelopes);
+        assert_eq!(envelopes[0].message.data.as_deref(), Some("dGVzdA=="));
+        assert!(check_decrypted(envelopes[0].message.attributes.as_ref()).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
-use reqwest::Response;
+use crate::auth::TokenError;
+use reqwest::{Response, StatusCode};
 use serde_json::Value;
-use std::{convert::identity, error::Error as StdError};
+use std::{convert::identity, error::Error as StdError, io};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,7 +13,11 @@ pub enum Error {
     },
 
     #[error("getting authentication token failed")]
-    TokenFetch(#[from] Box<goauth::GoErr>),
+    TokenFetch(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("signing JWT with the private key of the service account failed")]
+    JwtSigning(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("getting subject token for external account credentials failed")]
+    SubjectToken(#[source] Box<dyn StdError + Send + Sync + 'static>),
 
     #[error("HTTP communication with Pub/Sub service failed")]
     HttpServiceCommunication(#[source] reqwest::Error),
@@ -20,6 +25,23 @@ pub enum Error {
     UnexpectedHttpStatusCode(reqwest::StatusCode, String),
     #[error("unexpected HTTP response from Pub/Sub service")]
     UnexpectedHttpResponse(#[source] reqwest::Error),
+    #[error("interceptor rejected request to Pub/Sub service")]
+    Interceptor(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("publish response from Pub/Sub service contains no message ID")]
+    NoMessageId,
+    #[error("region `{region}` of the endpoint is not allowed by the message storage policy of the topic: {}", allowed_regions.join(", "))]
+    RegionNotAllowed {
+        region: String,
+        allowed_regions: Vec<String>,
+    },
+    #[error("publishing with ordering key `{0}` is paused after a failed publish")]
+    OrderingKeyPaused(String),
+    #[error("{} of {total} chunked requests failed", failed.len())]
+    ChunkedRequest {
+        total: usize,
+        /// The failed chunks, i.e. their ACK IDs, and their errors.
+        failed: Vec<(Vec<String>, Error)>,
+    },
 
     #[error("decoding data of received message as Base64 failed")]
     DecodeBase64(#[source] base64::DecodeError),
@@ -27,26 +49,104 @@ pub enum Error {
     NoData,
     #[error("deserializing data of received message failed")]
     Deserialize(#[source] serde_json::Error),
+    #[error("deserializing attributes of received message failed")]
+    DeserializeAttributes(#[source] serde_json::Error),
     #[error("serializing of message to be published failed")]
     Serialize(#[source] serde_json::Error),
+    #[error("encoding of message to be published failed")]
+    Encode(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("compressing data of message to be published failed")]
+    Compress(#[source] io::Error),
+    #[error("decompressing data of received message failed")]
+    Decompress(#[source] io::Error),
+    #[error("encrypting data of message to be published failed")]
+    Encrypt(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("decrypting data of received message failed")]
+    Decrypt(#[source] Box<dyn StdError + Send + Sync + 'static>),
+    #[error("integrity check of received message failed: expected CRC32C `{expected}`, but was `{actual}`")]
+    IntegrityCheckFailed { expected: String, actual: u32 },
+    #[cfg(feature = "json-schema")]
+    #[error("invalid JSON schema: {0}")]
+    InvalidJsonSchema(String),
+    #[cfg(feature = "json-schema")]
+    #[error("message {index} to be published does not match JSON schema: {}", errors.join("; "))]
+    JsonSchemaValidation { index: usize, errors: Vec<String> },
+    #[error("message {index} to be published is invalid: {reason}")]
+    InvalidMessage { index: usize, reason: String },
+    #[error("decoding data of received message failed")]
+    Decode(#[source] Box<dyn StdError + Send + Sync + 'static>),
     #[error("failed to transform JSON value")]
     Transform(#[source] Box<dyn StdError + Send + Sync + 'static>),
+
+    /// The messages have not been published, but accepted for later delivery: they are delivered
+    /// by flushing the spool, hence this error is not transient, i.e. publishing them must not be
+    /// retried.
+    #[error("Pub/Sub service could not be reached, messages have been spooled for later delivery")]
+    Spooled(#[source] reqwest::Error),
+    #[error("accessing the spool failed")]
+    Spool(#[source] io::Error),
+
+    #[cfg(feature = "avro")]
+    #[error("Avro schema for topic `{topic_id}` not available: {reason}")]
+    AvroSchema { topic_id: String, reason: String },
+
+    #[error("accessing the checkpoint store failed")]
+    Checkpoint(#[source] Box<dyn StdError + Send + Sync + 'static>),
 }
 
 impl Error {
+    /// Whether this error is likely to be resolved by retrying, e.g. a network failure or a server
+    /// error, also when refreshing the authentication token. Notice that [Error::Spooled] is not
+    /// transient, because the messages have been accepted for later delivery.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::HttpServiceCommunication(_) => true,
+            Error::TokenFetch(source) => is_transient_token_fetch(source.as_ref()),
+            Error::UnexpectedHttpStatusCode(status, _) => is_transient_status(*status),
+            Error::ChunkedRequest { failed, .. } => {
+                failed.iter().all(|(_, error)| error.is_transient())
+            }
+            _ => false,
+        }
+    }
+
     pub async fn unexpected_http_status_code(response: Response) -> Error {
+        let status = response.status();
+        match response.text().await {
+            Ok(text) => Error::unexpected_http_status_code_from_body(status, &text),
+            Err(e) => Error::UnexpectedHttpStatusCode(
+                status,
+                format!("failed to get response body as text: {e}"),
+            ),
+        }
+    }
+
+    pub(crate) fn unexpected_http_status_code_from_body(status: StatusCode, body: &str) -> Error {
         Error::UnexpectedHttpStatusCode(
-            response.status(),
-            response
-                .text()
-                .await
-                .map_err(|e| format!("failed to get response body as text: {e}"))
-                .and_then(|text| {
-                    serde_json::from_str::<Value>(&text)
-                        .map_err(|e| format!("failed to parse error response: {e}"))
-                        .map(|v| v["error"]["message"].to_string())
-                })
+            status,
+            serde_json::from_str::<Value>(body)
+                .map_err(|e| format!("failed to parse error response: {e}"))
+                .map(|v| v["error"]["message"].to_string())
                 .unwrap_or_else(identity),
         )
     }
 }
+
+/// Failures to reach the token endpoint and its server errors are transient, but not e.g. invalid
+/// grants or malformed responses.
+fn is_transient_token_fetch(source: &(dyn StdError + Send + Sync + 'static)) -> bool {
+    if let Some(error) = source.downcast_ref::<reqwest::Error>() {
+        match error.status() {
+            Some(status) => is_transient_status(status),
+            None => error.is_timeout() || error.is_connect() || error.is_request(),
+        }
+    } else if let Some(error) = source.downcast_ref::<TokenError>() {
+        is_transient_status(error.status())
+    } else {
+        false
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
@@ -0,0 +1,220 @@
+//! Acknowledging for subscriptions with exactly-once delivery, for which single ACK IDs of a
+//! request can fail, some of them transiently.
+
+use crate::{
+    error::Error,
+    retry::RetryConfig,
+    subscriber::{chunk_ack_ids, AcknowledgeRequest, ModifyAckDeadlineRequest},
+    timeouts::Operation,
+    PubSubClient,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+use tracing::{debug, warn};
+
+const TRANSIENT_FAILURE_PREFIX: &str = "TRANSIENT_FAILURE_";
+
+/// Per ACK ID results of [PubSubClient::acknowledge_with_response] or
+/// [PubSubClient::modify_ack_deadline_with_response].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AckResponse {
+    pub succeeded: Vec<String>,
+    /// Failed ACK IDs with their reasons, e.g. `"PERMANENT_FAILURE_INVALID_ACK_ID"`; transient
+    /// failures are only reported if retrying has been exhausted.
+    pub failed: HashMap<String, String>,
+}
+
+impl AckResponse {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl PubSubClient {
+    /// Acknowledge like [PubSubClient::acknowledge], but for subscriptions with exactly-once
+    /// delivery: transient failures of single ACK IDs are retried according to the given
+    /// [RetryConfig] and the results are reported per ACK ID. Errors not related to single ACK
+    /// IDs fail the whole call.
+    pub async fn acknowledge_with_response(
+        &self,
+        subscription_id: &str,
+        ack_ids: Vec<&str>,
+        retry: &RetryConfig,
+        timeout: Option<Duration>,
+    ) -> Result<AckResponse, Error> {
+        self.send_with_response(
+            subscription_id,
+            "acknowledge",
+            ack_ids,
+            |ack_ids| AcknowledgeRequest { ack_ids },
+            retry,
+            timeout,
+        )
+        .await
+    }
+
+    /// Modify the acknowledge deadline like [PubSubClient::modify_ack_deadline], but for
+    /// subscriptions with exactly-once delivery, see [PubSubClient::acknowledge_with_response].
+    pub async fn modify_ack_deadline_with_response(
+        &self,
+        subscription_id: &str,
+        ack_ids: Vec<&str>,
+        ack_deadline_seconds: u32,
+        retry: &RetryConfig,
+        timeout: Option<Duration>,
+    ) -> Result<AckResponse, Error> {
+        self.send_with_response(
+            subscription_id,
+            "modifyAckDeadline",
+            ack_ids,
+            |ack_ids| ModifyAckDeadlineRequest {
+                ack_ids,
+                ack_deadline_seconds,
+            },
+            retry,
+            timeout,
+        )
+        .await
+    }
+
+    async fn send_with_response<'a, R, F>(
+        &self,
+        subscription_id: &str,
+        action: &str,
+        ack_ids: Vec<&'a str>,
+        request: F,
+        retry: &RetryConfig,
+        timeout: Option<Duration>,
+    ) -> Result<AckResponse, Error>
+    where
+        R: Serialize,
+        F: Fn(Vec<&'a str>) -> R,
+    {
+        let url = self.subscription_url(subscription_id, action);
+        let mut response = AckResponse::default();
+        let mut pending = ack_ids;
+        let mut retries = 0;
+
+        loop {
+            let mut transient = HashMap::new();
+            for chunk in chunk_ack_ids(pending) {
+                let http_response = self
+                    .send_request(&url, &request(chunk.clone()), timeout, Operation::Ack)
+                    .await?;
+                if http_response.status().is_success() {
+                    response
+                        .succeeded
+                        .extend(chunk.iter().map(|id| id.to_string()));
+                    continue;
+                }
+
+                let status = http_response.status();
+                let body = http_response
+                    .text()
+                    .await
+                    .map_err(Error::UnexpectedHttpResponse)?;
+                let failures = ack_id_failures(&body)
+                    .ok_or_else(|| Error::unexpected_http_status_code_from_body(status, &body))?;
+                for ack_id in chunk {
+                    match failures.get(ack_id) {
+                        Some(reason) if reason.starts_with(TRANSIENT_FAILURE_PREFIX) => {
+                            transient.insert(ack_id, reason.to_owned());
+                        }
+                        Some(reason) => {
+                            response
+                                .failed
+                                .insert(ack_id.to_string(), reason.to_owned());
+                        }
+                        None => response.succeeded.push(ack_id.to_string()),
+                    }
+                }
+            }
+
+            if transient.is_empty() {
+                return Ok(response);
+            }
+
+            match retry.backoff(retries) {
+                Some(backoff) => {
+                    debug!(
+                        count = transient.len(),
+                        ?backoff,
+                        action,
+                        "retrying transient failures"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    retries += 1;
+                    pending = transient.into_keys().collect();
+                }
+                None => {
+                    warn!(
+                        count = transient.len(),
+                        action, "retrying transient failures exhausted"
+                    );
+                    response.failed.extend(
+                        transient
+                            .into_iter()
+                            .map(|(ack_id, reason)| (ack_id.to_string(), reason)),
+                    );
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+/// The failures per ACK ID from the `ErrorInfo` details of the given error response, if any.
+fn ack_id_failures(body: &str) -> Option<HashMap<String, String>> {
+    let body = serde_json::from_str::<Value>(body).ok()?;
+    body["error"]["details"]
+        .as_array()?
+        .iter()
+        .find(|detail| detail["reason"] == "EXACTLY_ONCE_ACKID_FAILURE")
+        .and_then(|detail| serde_json::from_value(detail["metadata"].clone()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ack_id_failures;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_ack_id_failures() {
+        let body = json!({
+            "error": {
+                "code": 400,
+                "message": "Some acknowledgement ids in the request were invalid.",
+                "status": "INVALID_ARGUMENT",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                        "reason": "EXACTLY_ONCE_ACKID_FAILURE",
+                        "domain": "pubsub.googleapis.com",
+                        "metadata": {
+                            "ack-id-1": "PERMANENT_FAILURE_INVALID_ACK_ID",
+                            "ack-id-2": "TRANSIENT_FAILURE_UNORDERED_ACK_ID"
+                        }
+                    }
+                ]
+            }
+        });
+        assert_eq!(
+            ack_id_failures(&body.to_string()),
+            Some(HashMap::from([
+                (
+                    "ack-id-1".to_string(),
+                    "PERMANENT_FAILURE_INVALID_ACK_ID".to_string()
+                ),
+                (
+                    "ack-id-2".to_string(),
+                    "TRANSIENT_FAILURE_UNORDERED_ACK_ID".to_string()
+                )
+            ]))
+        );
+
+        let body = json!({ "error": { "code": 400, "message": "Invalid ack ID." } });
+        assert_eq!(ack_id_failures(&body.to_string()), None);
+    }
+}
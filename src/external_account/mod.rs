@@ -0,0 +1,576 @@
+use crate::{
+    auth::{token_from_response, Token, CLOUD_PLATFORM_SCOPE, PUBSUB_SCOPE},
+    error::Error,
+};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, env, fs};
+use time::OffsetDateTime;
+use tracing::debug;
+
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+const IMPERSONATED_TOKEN_LIFETIME: &str = "3600s";
+
+const AWS_SIGNING_ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const AWS_IMDS_TOKEN_TTL: &str = "300";
+
+/// Characters not encoded by Python's `urllib.parse.quote`, which Google's client libraries use
+/// to encode AWS subject tokens.
+const SUBJECT_TOKEN_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Characters not encoded in canonical AWS Signature Version 4 query strings.
+const AWS_QUERY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Credentials of type `external_account` for workload identity federation: a subject token of
+/// an external identity provider is exchanged for a Google access token at the Security Token
+/// Service (STS), which optionally is used to impersonate a service account.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+    token_url: String,
+    service_account_impersonation_url: Option<String>,
+    credential_source: CredentialSource,
+}
+
+/// Where to get the subject token from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum CredentialSource {
+    Aws {
+        environment_id: String,
+        region_url: Option<String>,
+        url: Option<String>,
+        regional_cred_verification_url: String,
+        imdsv2_session_token_url: Option<String>,
+    },
+
+    File {
+        file: String,
+        #[serde(default)]
+        format: Format,
+    },
+
+    Url {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        #[serde(default)]
+        format: Format,
+    },
+}
+
+/// Format of file or URL sourced subject tokens.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Format {
+    #[default]
+    Text,
+    Json {
+        subject_token_field_name: String,
+    },
+}
+
+/// AWS security credentials used to sign the `GetCallerIdentity` request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImpersonatedToken {
+    access_token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    expire_time: OffsetDateTime,
+}
+
+impl ExternalAccount {
+    pub(crate) async fn fetch_token(
+        &self,
+        reqwest_client: &reqwest::Client,
+    ) -> Result<Token, Error> {
+        let subject_token = self
+            .credential_source
+            .subject_token(reqwest_client, &self.audience)
+            .await?;
+
+        // Impersonating a service account requires the cloud-platform scope.
+        let scope = match self.service_account_impersonation_url {
+            Some(_) => CLOUD_PLATFORM_SCOPE,
+            None => PUBSUB_SCOPE,
+        };
+
+        debug!(token_url = self.token_url, "exchanging subject token");
+        let response = reqwest_client
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+                ("audience", &self.audience),
+                ("scope", scope),
+                ("requested_token_type", ACCESS_TOKEN_TYPE),
+                ("subject_token_type", &self.subject_token_type),
+                ("subject_token", &subject_token),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::TokenFetch(e.into()))?;
+        let token = token_from_response(response).await?;
+
+        match &self.service_account_impersonation_url {
+            Some(url) => impersonate(reqwest_client, url, &token).await,
+            None => Ok(token),
+        }
+    }
+}
+
+impl CredentialSource {
+    async fn subject_token(
+        &self,
+        reqwest_client: &reqwest::Client,
+        audience: &str,
+    ) -> Result<String, Error> {
+        match self {
+            CredentialSource::Aws {
+                environment_id,
+                region_url,
+                url,
+                regional_cred_verification_url,
+                imdsv2_session_token_url,
+            } => {
+                if !environment_id.starts_with("aws") {
+                    return Err(Error::SubjectToken(
+                        format!("unsupported environment ID `{environment_id}`").into(),
+                    ));
+                }
+
+                let imds_token = match imdsv2_session_token_url {
+                    Some(url) if aws_needs_imds() => {
+                        Some(aws_imds_session_token(reqwest_client, url).await?)
+                    }
+                    _ => None,
+                };
+                let region =
+                    aws_region(reqwest_client, region_url.as_deref(), imds_token.as_deref())
+                        .await?;
+                let credentials =
+                    aws_credentials(reqwest_client, url.as_deref(), imds_token.as_deref()).await?;
+
+                let url = regional_cred_verification_url.replace("{region}", &region);
+                aws_subject_token(
+                    &url,
+                    &region,
+                    audience,
+                    &credentials,
+                    OffsetDateTime::now_utc(),
+                )
+            }
+
+            CredentialSource::File { file, format } => {
+                let content =
+                    fs::read_to_string(file).map_err(|e| Error::SubjectToken(e.into()))?;
+                format.subject_token(&content)
+            }
+
+            CredentialSource::Url {
+                url,
+                headers,
+                format,
+            } => {
+                let request = headers
+                    .iter()
+                    .fold(reqwest_client.get(url), |r, (k, v)| r.header(k, v));
+                let content = request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| Error::SubjectToken(e.into()))?
+                    .text()
+                    .await
+                    .map_err(|e| Error::SubjectToken(e.into()))?;
+                format.subject_token(&content)
+            }
+        }
+    }
+}
+
+impl Format {
+    fn subject_token(&self, content: &str) -> Result<String, Error> {
+        match self {
+            Format::Text => Ok(content.trim().to_string()),
+
+            Format::Json {
+                subject_token_field_name,
+            } => serde_json::from_str::<Value>(content)
+                .map_err(|e| Error::SubjectToken(e.into()))?
+                .get(subject_token_field_name)
+                .and_then(Value::as_str)
+                .map(ToString::to_string)
+                .ok_or_else(|| {
+                    Error::SubjectToken(
+                        format!("missing string field `{subject_token_field_name}`").into(),
+                    )
+                }),
+        }
+    }
+}
+
+/// Exchanges the given federated token for an access token of the service account at the given
+/// impersonation URL.
+async fn impersonate(
+    reqwest_client: &reqwest::Client,
+    url: &str,
+    token: &Token,
+) -> Result<Token, Error> {
+    debug!(url, "impersonating service account");
+    let impersonated_token = reqwest_client
+        .post(url)
+        .bearer_auth(token.access_token())
+        .json(&json!({
+            "scope": [PUBSUB_SCOPE],
+            "lifetime": IMPERSONATED_TOKEN_LIFETIME,
+        }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::TokenFetch(e.into()))?
+        .json::<ImpersonatedToken>()
+        .await
+        .map_err(|e| Error::TokenFetch(e.into()))?;
+
+    let expires_in = (impersonated_token.expire_time - OffsetDateTime::now_utc())
+        .whole_seconds()
+        .max(0) as u64;
+    Ok(Token::new(impersonated_token.access_token, expires_in))
+}
+
+/// The AWS instance metadata service is only needed if region or credentials are not given by
+/// environment variables.
+fn aws_needs_imds() -> bool {
+    let has_region = env::var("AWS_REGION").is_ok() || env::var("AWS_DEFAULT_REGION").is_ok();
+    let has_credentials =
+        env::var("AWS_ACCESS_KEY_ID").is_ok() && env::var("AWS_SECRET_ACCESS_KEY").is_ok();
+    !(has_region && has_credentials)
+}
+
+async fn aws_imds_session_token(
+    reqwest_client: &reqwest::Client,
+    url: &str,
+) -> Result<String, Error> {
+    reqwest_client
+        .put(url)
+        .header("X-aws-ec2-metadata-token-ttl-seconds", AWS_IMDS_TOKEN_TTL)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::SubjectToken(e.into()))?
+        .text()
+        .await
+        .map_err(|e| Error::SubjectToken(e.into()))
+}
+
+async fn aws_region(
+    reqwest_client: &reqwest::Client,
+    region_url: Option<&str>,
+    imds_token: Option<&str>,
+) -> Result<String, Error> {
+    if let Ok(region) = env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION")) {
+        return Ok(region);
+    }
+
+    let region_url = region_url
+        .ok_or_else(|| Error::SubjectToken("neither AWS region nor region URL given".into()))?;
+    let mut zone = aws_imds_get(reqwest_client, region_url, imds_token).await?;
+
+    // The metadata service returns the availability zone, e.g. `us-east-1a` for `us-east-1`.
+    zone.pop();
+    Ok(zone)
+}
+
+async fn aws_credentials(
+    reqwest_client: &reqwest::Client,
+    url: Option<&str>,
+    imds_token: Option<&str>,
+) -> Result<AwsCredentials, Error> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        env::var("AWS_ACCESS_KEY_ID"),
+        env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key,
+            token: env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+
+    let url = url.ok_or_else(|| {
+        Error::SubjectToken("neither AWS credentials nor security credentials URL given".into())
+    })?;
+    let role_name = aws_imds_get(reqwest_client, url, imds_token).await?;
+    let credentials = aws_imds_get(
+        reqwest_client,
+        &format!("{}/{}", url.trim_end_matches('/'), role_name.trim()),
+        imds_token,
+    )
+    .await?;
+    serde_json::from_str(&credentials).map_err(|e| Error::SubjectToken(e.into()))
+}
+
+async fn aws_imds_get(
+    reqwest_client: &reqwest::Client,
+    url: &str,
+    imds_token: Option<&str>,
+) -> Result<String, Error> {
+    let request = reqwest_client.get(url);
+    let request = imds_token
+        .into_iter()
+        .fold(request, |r, t| r.header("X-aws-ec2-metadata-token", t));
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::SubjectToken(e.into()))?
+        .text()
+        .await
+        .map_err(|e| Error::SubjectToken(e.into()))
+}
+
+/// Returns the subject token for AWS: a `GetCallerIdentity` request signed with AWS Signature
+/// Version 4, serialized as JSON and URL encoded, which the STS forwards to AWS for verifying the
+/// identity of the caller.
+fn aws_subject_token(
+    url: &str,
+    region: &str,
+    audience: &str,
+    credentials: &AwsCredentials,
+    now: OffsetDateTime,
+) -> Result<String, Error> {
+    let url = Url::parse(url).map_err(|e| Error::SubjectToken(e.into()))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::SubjectToken(format!("URL `{url}` has no host").into()))?;
+
+    let mut headers = vec![
+        ("host", host.to_string()),
+        ("x-amz-date", aws_date_time(now)),
+        ("x-goog-cloud-target-resource", audience.to_string()),
+    ];
+    if let Some(token) = &credentials.token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    let authorization = aws_authorization("POST", &url, &headers, region, "sts", credentials, now);
+    headers.push(("authorization", authorization));
+    headers.sort();
+
+    let headers = headers
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": value }))
+        .collect::<Vec<_>>();
+    let request = json!({ "url": url.as_str(), "method": "POST", "headers": headers });
+    Ok(utf8_percent_encode(&request.to_string(), SUBJECT_TOKEN_ENCODE_SET).to_string())
+}
+
+/// Returns the value of the `Authorization` header for a request with an empty body, signed with
+/// AWS Signature Version 4. The given headers must all be signed and must include `host` and
+/// `x-amz-date`.
+fn aws_authorization(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, String)],
+    region: &str,
+    service: &str,
+    credentials: &AwsCredentials,
+    now: OffsetDateTime,
+) -> String {
+    let mut query = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let key = utf8_percent_encode(&key, AWS_QUERY_ENCODE_SET).to_string();
+            let value = utf8_percent_encode(&value, AWS_QUERY_ENCODE_SET).to_string();
+            (key, value)
+        })
+        .collect::<Vec<_>>();
+    query.sort();
+    let query = query
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut headers = headers
+        .iter()
+        .map(|(key, value)| (key.to_lowercase(), value.trim()))
+        .collect::<Vec<_>>();
+    headers.sort();
+    let canonical_headers = headers
+        .iter()
+        .map(|(key, value)| format!("{key}:{value}\n"))
+        .collect::<String>();
+    let signed_headers = headers
+        .iter()
+        .map(|(key, _)| key.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{method}\n{}\n{query}\n{canonical_headers}\n{signed_headers}\n{}",
+        url.path(),
+        hex(&Sha256::digest(b""))
+    );
+
+    let date = &aws_date_time(now)[..8];
+    let credential_scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "{AWS_SIGNING_ALGORITHM}\n{}\n{credential_scope}\n{}",
+        aws_date_time(now),
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "{AWS_SIGNING_ALGORITHM} Credential={}/{credential_scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+/// Formats the given time as AWS "basic format" timestamp, e.g. `20150830T123600Z`.
+fn aws_date_time(time: OffsetDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aws_authorization, AwsCredentials, CredentialSource, ExternalAccount, Format};
+    use reqwest::Url;
+    use serde_json::json;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_deserialize_credential_source() {
+        let external_account = serde_json::from_value::<ExternalAccount>(json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/p/providers/q",
+            "subject_token_type": "urn:ietf:params:aws:token-type:aws4_request",
+            "token_url": "https://sts.googleapis.com/v1/token",
+            "credential_source": {
+                "environment_id": "aws1",
+                "region_url": "http://169.254.169.254/latest/meta-data/placement/availability-zone",
+                "url": "http://169.254.169.254/latest/meta-data/iam/security-credentials",
+                "regional_cred_verification_url": "https://sts.{region}.amazonaws.com?Action=GetCallerIdentity&Version=2011-06-15"
+            }
+        }));
+        assert!(matches!(
+            external_account.unwrap().credential_source,
+            CredentialSource::Aws { .. }
+        ));
+
+        let credential_source = serde_json::from_value::<CredentialSource>(json!({
+            "file": "/var/run/token",
+        }));
+        assert!(matches!(
+            credential_source.unwrap(),
+            CredentialSource::File {
+                format: Format::Text,
+                ..
+            }
+        ));
+
+        let credential_source = serde_json::from_value::<CredentialSource>(json!({
+            "url": "https://example.com/token",
+            "headers": { "Authorization": "Bearer secret" },
+            "format": { "type": "json", "subject_token_field_name": "value" }
+        }));
+        assert!(matches!(
+            credential_source.unwrap(),
+            CredentialSource::Url {
+                format: Format::Json { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_format_subject_token() {
+        let subject_token = Format::Text.subject_token("token\n");
+        assert_eq!(subject_token.unwrap(), "token");
+
+        let format = Format::Json {
+            subject_token_field_name: "value".to_string(),
+        };
+        let subject_token = format.subject_token(r#"{"value":"token"}"#);
+        assert_eq!(subject_token.unwrap(), "token");
+        let subject_token = format.subject_token(r#"{"other":"token"}"#);
+        assert!(subject_token.is_err());
+    }
+
+    #[test]
+    fn test_aws_authorization() {
+        // "get-vanilla" example of the AWS Signature Version 4 test suite.
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_440_938_160).unwrap();
+        let headers = [
+            ("Host", "example.amazonaws.com".to_string()),
+            ("X-Amz-Date", "20150830T123600Z".to_string()),
+        ];
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            token: None,
+        };
+
+        let authorization = aws_authorization(
+            "GET",
+            &url,
+            &headers,
+            "us-east-1",
+            "service",
+            &credentials,
+            now,
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Typed builder for subscription filter expressions, see
+//! <https://cloud.google.com/pubsub/docs/subscription-message-filter>.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Not,
+};
+
+/// Filter expression for a subscription, rendered to valid filter syntax via [Display] or
+/// [String::from], e.g. `Filter::attr("type").eq("Foo").and(Filter::attr("version").exists())`.
+///
+/// Keys and values are quoted and escaped as needed and mixed conjunctions and disjunctions are
+/// parenthesized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter(Expr);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Eq(String, String),
+    Ne(String, String),
+    HasPrefix(String, String),
+    Exists(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+/// Attribute of a message to filter on, see [Filter::attr].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute(String);
+
+impl Filter {
+    pub fn attr<T>(key: T) -> Attribute
+    where
+        T: Into<String>,
+    {
+        Attribute(key.into())
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        match self.0 {
+            Expr::And(mut exprs) => {
+                exprs.push(other.0);
+                Filter(Expr::And(exprs))
+            }
+            expr => Filter(Expr::And(vec![expr, other.0])),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        match self.0 {
+            Expr::Or(mut exprs) => {
+                exprs.push(other.0);
+                Filter(Expr::Or(exprs))
+            }
+            expr => Filter(Expr::Or(vec![expr, other.0])),
+        }
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter(Expr::Not(Box::new(self.0)))
+    }
+}
+
+impl Attribute {
+    /// The attribute has the given value.
+    pub fn eq<T>(self, value: T) -> Filter
+    where
+        T: Into<String>,
+    {
+        Filter(Expr::Eq(self.0, value.into()))
+    }
+
+    /// The attribute does not have the given value, which includes not being present.
+    pub fn ne<T>(self, value: T) -> Filter
+    where
+        T: Into<String>,
+    {
+        Filter(Expr::Ne(self.0, value.into()))
+    }
+
+    /// The value of the attribute starts with the given prefix.
+    pub fn has_prefix<T>(self, prefix: T) -> Filter
+    where
+        T: Into<String>,
+    {
+        Filter(Expr::HasPrefix(self.0, prefix.into()))
+    }
+
+    /// The attribute is present.
+    pub fn exists(self) -> Filter {
+        Filter(Expr::Exists(self.0))
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Filter> for String {
+    fn from(filter: Filter) -> Self {
+        filter.to_string()
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Eq(key, value) => write!(f, "attributes.{} = {}", render_key(key), quote(value)),
+            Expr::Ne(key, value) => write!(f, "attributes.{} != {}", render_key(key), quote(value)),
+            Expr::HasPrefix(key, prefix) => {
+                write!(
+                    f,
+                    "hasPrefix(attributes.{}, {})",
+                    render_key(key),
+                    quote(prefix)
+                )
+            }
+            Expr::Exists(key) => write!(f, "attributes:{}", render_key(key)),
+            Expr::Not(expr) => match **expr {
+                Expr::And(_) | Expr::Or(_) => write!(f, "NOT ({expr})"),
+                _ => write!(f, "NOT {expr}"),
+            },
+            Expr::And(exprs) => join(f, exprs, " AND "),
+            Expr::Or(exprs) => join(f, exprs, " OR "),
+        }
+    }
+}
+
+/// Join the given expressions with the given operator, parenthesizing nested conjunctions and
+/// disjunctions, because these must not be mixed without parentheses.
+fn join(f: &mut Formatter<'_>, exprs: &[Expr], operator: &str) -> fmt::Result {
+    for (n, expr) in exprs.iter().enumerate() {
+        if n > 0 {
+            f.write_str(operator)?;
+        }
+        match expr {
+            Expr::And(_) | Expr::Or(_) => write!(f, "({expr})")?,
+            _ => write!(f, "{expr}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Keys other than identifiers must be quoted.
+fn render_key(key: &str) -> String {
+    let is_identifier = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_identifier {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Filter::attr("type").eq("Foo").to_string(),
+            r#"attributes.type = "Foo""#
+        );
+        assert_eq!(
+            Filter::attr("my-key").ne(r#"say "hi"\"#).to_string(),
+            r#"attributes."my-key" != "say \"hi\"\\""#
+        );
+
+        let filter = Filter::attr("type")
+            .eq("Foo")
+            .and(Filter::attr("version").exists())
+            .and(
+                Filter::attr("region")
+                    .has_prefix("eu-")
+                    .or(!Filter::attr("region").exists()),
+            );
+        assert_eq!(
+            String::from(filter),
+            r#"attributes.type = "Foo" AND attributes:version AND (hasPrefix(attributes.region, "eu-") OR NOT attributes:region)"#
+        );
+
+        let filter = !Filter::attr("a").eq("1").or(Filter::attr("b").eq("2"));
+        assert_eq!(
+            filter.to_string(),
+            r#"NOT (attributes.a = "1" OR attributes.b = "2")"#
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{error::Error, resource_path, PubSubClient};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+/// Resource with an IAM policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IamResource<'a> {
+    Topic(&'a str),
+    Subscription(&'a str),
+}
+
+impl IamResource<'_> {
+    fn path(&self) -> String {
+        match self {
+            IamResource::Topic(topic_id) => resource_path("topics", topic_id),
+            IamResource::Subscription(subscription_id) => {
+                resource_path("subscriptions", subscription_id)
+            }
+        }
+    }
+}
+
+/// IAM policy, i.e. bindings of members to roles.
+///
+/// For read-modify-write get the policy, change it and set it: the `etag` of the policy read is
+/// sent along and setting fails with 409 Conflict if the policy has been changed concurrently.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Policy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bindings: Vec<Binding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+impl Policy {
+    /// Add the given member, e.g. `"serviceAccount:foo@bar.iam.gserviceaccount.com"`, to the
+    /// binding for the given role, e.g. `"roles/pubsub.publisher"`, which is created if needed.
+    pub fn add_member<R, M>(&mut self, role: R, member: M)
+    where
+        R: Into<String>,
+        M: Into<String>,
+    {
+        let role = role.into();
+        let member = member.into();
+        match self
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.role == role && binding.condition.is_none())
+        {
+            Some(binding) => {
+                if !binding.members.contains(&member) {
+                    binding.members.push(member);
+                }
+            }
+            None => self.bindings.push(Binding {
+                role,
+                members: vec![member],
+                condition: None,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Binding {
+    pub role: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Expr>,
+}
+
+/// Condition of a binding in Common Expression Language syntax.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Expr {
+    pub expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SetIamPolicyRequest<'a> {
+    policy: &'a Policy,
+}
+
+impl PubSubClient {
+    #[tracing::instrument]
+    pub async fn get_iam_policy(
+        &self,
+        resource: IamResource<'_>,
+        timeout: Option<Duration>,
+    ) -> Result<Policy, Error> {
+        self.send_authenticated::<()>(
+            Method::GET,
+            &format!("{}:getIamPolicy", resource.path()),
+            None,
+            timeout,
+        )
+        .await?
+        .json::<Policy>()
+        .await
+        .map_err(Error::UnexpectedHttpResponse)
+    }
+
+    /// Set the IAM policy of the given resource, replacing any existing one.
+    #[tracing::instrument]
+    pub async fn set_iam_policy(
+        &self,
+        resource: IamResource<'_>,
+        policy: &Policy,
+        timeout: Option<Duration>,
+    ) -> Result<Policy, Error> {
+        let policy = self
+            .send_authenticated(
+                Method::POST,
+                &format!("{}:setIamPolicy", resource.path()),
+                Some(&SetIamPolicyRequest { policy }),
+                timeout,
+            )
+            .await?
+            .json::<Policy>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(?resource, "successfully set IAM policy");
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Binding, IamResource, Policy, SetIamPolicyRequest};
+    use serde_json::json;
+
+    #[test]
+    fn test_path() {
+        assert_eq!(IamResource::Topic("test").path(), "topics/test");
+        assert_eq!(
+            IamResource::Topic("projects/other/topics/test").path(),
+            "projects/other/topics/test"
+        );
+        assert_eq!(
+            IamResource::Subscription("test").path(),
+            "subscriptions/test"
+        );
+    }
+
+    #[test]
+    fn test_add_member() {
+        let policy = json!({
+            "version": 1,
+            "bindings": [{ "role": "roles/pubsub.viewer", "members": ["user:a@example.com"] }],
+            "etag": "BwXhqDo9yLQ="
+        });
+        let mut policy = serde_json::from_value::<Policy>(policy).unwrap();
+
+        policy.add_member("roles/pubsub.publisher", "user:a@example.com");
+        policy.add_member("roles/pubsub.publisher", "user:b@example.com");
+        policy.add_member("roles/pubsub.publisher", "user:b@example.com");
+        assert_eq!(
+            policy.bindings[1],
+            Binding {
+                role: "roles/pubsub.publisher".to_string(),
+                members: vec![
+                    "user:a@example.com".to_string(),
+                    "user:b@example.com".to_string()
+                ],
+                condition: None,
+            }
+        );
+
+        assert_eq!(
+            serde_json::to_value(SetIamPolicyRequest { policy: &policy }).unwrap(),
+            json!({
+                "policy": {
+                    "version": 1,
+                    "bindings": [
+                        { "role": "roles/pubsub.viewer", "members": ["user:a@example.com"] },
+                        {
+                            "role": "roles/pubsub.publisher",
+                            "members": ["user:a@example.com", "user:b@example.com"]
+                        }
+                    ],
+                    "etag": "BwXhqDo9yLQ="
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{error::Error, RawPublishedMessage};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::collections::HashMap;
+
+/// Attribute holding the CRC32C checksum of the data of a message as decimal number, see
+/// [PubSubClientBuilder::with_crc32c](crate::PubSubClientBuilder::with_crc32c).
+pub const CRC32C_ATTRIBUTE: &str = "crc32c";
+
+/// Add the CRC32C checksum of the data of the given message with the given index as
+/// [CRC32C_ATTRIBUTE].
+pub(crate) fn add_checksum(
+    index: usize,
+    mut message: RawPublishedMessage<'_>,
+) -> Result<RawPublishedMessage<'_>, Error> {
+    let data = message
+        .data
+        .as_ref()
+        .map(|data| STANDARD.decode(data))
+        .transpose()
+        .map_err(|error| Error::InvalidMessage {
+            index,
+            reason: format!("data is not Base64 encoded: {error}"),
+        })?
+        .unwrap_or_default();
+    message.attributes.get_or_insert_with(HashMap::new).insert(
+        CRC32C_ATTRIBUTE.to_string(),
+        crc32c::crc32c(&data).to_string(),
+    );
+    Ok(message)
+}
+
+/// Verify the given data against the [CRC32C_ATTRIBUTE] of the given attributes, if any.
+pub(crate) fn verify_checksum(
+    attributes: Option<&HashMap<String, String>>,
+    data: &[u8],
+) -> Result<(), Error> {
+    let Some(expected) = attributes.and_then(|attributes| attributes.get(CRC32C_ATTRIBUTE)) else {
+        return Ok(());
+    };
+    let actual = crc32c::crc32c(data);
+    if expected.parse::<u32>().ok() != Some(actual) {
+        return Err(Error::IntegrityCheckFailed {
+            expected: expected.to_owned(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_checksum, verify_checksum, CRC32C_ATTRIBUTE};
+    use crate::{Error, RawPublishedMessage};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    #[test]
+    fn test_add_verify_checksums() {
+        let message = add_checksum(0, RawPublishedMessage::new(STANDARD.encode("test"))).unwrap();
+        let attributes = message.attributes.as_ref();
+        assert_eq!(
+            attributes.and_then(|a| a.get(CRC32C_ATTRIBUTE)),
+            Some(&crc32c::crc32c(b"test").to_string())
+        );
+
+        assert!(verify_checksum(attributes, b"test").is_ok());
+        assert!(matches!(
+            verify_checksum(attributes, b"tset"),
+            Err(Error::IntegrityCheckFailed { .. })
+        ));
+        let message = add_checksum(1, RawPublishedMessage::default()).unwrap();
+        assert!(verify_checksum(message.attributes.as_ref(), b"").is_ok());
+        assert!(verify_checksum(None, b"test").is_ok());
+
+        let result = add_checksum(2, RawPublishedMessage::new("!".to_string()));
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 2, .. })
+        ));
+    }
+}
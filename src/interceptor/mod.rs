@@ -0,0 +1,106 @@
+use reqwest::{Request, Response};
+use std::{
+    error::Error as StdError,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+/// Hook into all HTTP requests to the Pub/Sub service, see
+/// [PubSubClientBuilder::with_interceptor](crate::PubSubClientBuilder::with_interceptor), e.g. to
+/// add audit headers, sign requests, inject failures for chaos testing or log requests and
+/// responses. A function mutating the request is an [Interceptor], too.
+pub trait Interceptor: Send + Sync {
+    /// Inspect and mutate the given request – including the authorization header, if any – right
+    /// before it is sent; each retry is intercepted, too. Returning an error aborts the request
+    /// with [Error::Interceptor](crate::Error::Interceptor).
+    fn on_request(
+        &self,
+        request: &mut Request,
+    ) -> Result<(), Box<dyn StdError + Send + Sync + 'static>>;
+
+    /// Observe the response to a request; does nothing by default.
+    fn on_response(&self, _response: &Response) {}
+}
+
+impl<F> Interceptor for F
+where
+    F: Fn(&mut Request) -> Result<(), Box<dyn StdError + Send + Sync + 'static>> + Send + Sync,
+{
+    fn on_request(
+        &self,
+        request: &mut Request,
+    ) -> Result<(), Box<dyn StdError + Send + Sync + 'static>> {
+        self(request)
+    }
+}
+
+/// The shared [Interceptor]s of a client, applied in the order they were added.
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors(Vec<Arc<dyn Interceptor>>);
+
+impl Interceptors {
+    pub(crate) fn push<I>(&mut self, interceptor: I)
+    where
+        I: Interceptor + 'static,
+    {
+        self.0.push(Arc::new(interceptor));
+    }
+
+    pub(crate) fn on_request(
+        &self,
+        request: &mut Request,
+    ) -> Result<(), Box<dyn StdError + Send + Sync + 'static>> {
+        self.0
+            .iter()
+            .try_for_each(|interceptor| interceptor.on_request(request))
+    }
+
+    pub(crate) fn on_response(&self, response: &Response) {
+        for interceptor in self.0.iter() {
+            interceptor.on_response(response);
+        }
+    }
+}
+
+impl Debug for Interceptors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Interceptors").field(&self.0.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interceptors;
+    use reqwest::{header::HeaderValue, Method, Request};
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_on_request() {
+        let audit = |request: &mut Request| -> Result<(), Box<dyn StdError + Send + Sync>> {
+            request
+                .headers_mut()
+                .insert("x-audit", HeaderValue::from_static("test"));
+            Ok(())
+        };
+        let reject = |request: &mut Request| -> Result<(), Box<dyn StdError + Send + Sync>> {
+            if request.method() == Method::DELETE {
+                Err("deleting is not allowed".into())
+            } else {
+                Ok(())
+            }
+        };
+        let mut interceptors = Interceptors::default();
+        interceptors.push(audit);
+        interceptors.push(reject);
+
+        let url = "http://localhost:8085/v1/projects/test/topics/test"
+            .parse()
+            .unwrap();
+        let mut request = Request::new(Method::GET, url);
+        assert!(interceptors.on_request(&mut request).is_ok());
+        assert_eq!(request.headers()["x-audit"], "test");
+
+        *request.method_mut() = Method::DELETE;
+        assert!(interceptors.on_request(&mut request).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::Error;
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// JSON Schema for validating messages locally before publishing them, see
+/// [TopicHandle::with_json_schema](crate::TopicHandle::with_json_schema).
+///
+/// As the Pub/Sub schema service only supports Avro and protocol buffer schemas, JSON schemas are
+/// always provided locally; for topics with an Avro schema use the `AvroCodec` of the `avro`
+/// feature instead.
+#[derive(Debug, Clone)]
+pub struct JsonSchema(Arc<Validator>);
+
+impl JsonSchema {
+    pub fn new(schema: &Value) -> Result<Self, Error> {
+        jsonschema::validator_for(schema)
+            .map(|validator| Self(Arc::new(validator)))
+            .map_err(|error| Error::InvalidJsonSchema(error.to_string()))
+    }
+
+    /// Serialize the given message with the given index to JSON and validate it against this
+    /// schema.
+    pub(crate) fn encode<M>(&self, message: &M, index: usize) -> Result<Vec<u8>, Error>
+    where
+        M: Serialize,
+    {
+        let value = serde_json::to_value(message).map_err(Error::Serialize)?;
+        self.validate(&value, index)?;
+        serde_json::to_vec(&value).map_err(Error::Serialize)
+    }
+
+    fn validate(&self, value: &Value, index: usize) -> Result<(), Error> {
+        let errors = self
+            .0
+            .iter_errors(value)
+            .map(|error| format!("{error} at `{}`", error.instance_path))
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(Error::JsonSchemaValidation { index, errors });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonSchema;
+    use crate::Error;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode() {
+        let json_schema = JsonSchema::new(&json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+            "required": ["text"]
+        }))
+        .unwrap();
+
+        let bytes = json_schema.encode(&json!({"text": "test"}), 0).unwrap();
+        assert_eq!(bytes, br#"{"text":"test"}"#);
+
+        let result = json_schema.encode(&json!({"text": 42}), 1);
+        assert!(matches!(
+            result,
+            Err(Error::JsonSchemaValidation { index: 1, errors }) if errors.len() == 1
+        ));
+
+        let result = JsonSchema::new(&json!({"type": "invalid"}));
+        assert!(matches!(result, Err(Error::InvalidJsonSchema(_))));
+    }
+}
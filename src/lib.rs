@@ -1,28 +1,148 @@
+mod ack_manager;
+mod auth;
+#[cfg(feature = "avro")]
+mod avro;
+mod batching;
+mod builder;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod checkpoint;
+mod compression;
+mod consumer;
+mod credentials;
+mod decoder;
+mod dedup;
+mod duration;
+mod encoder;
+mod encryption;
 mod error;
+mod exactly_once;
+mod external_account;
+mod filter;
+mod iam;
+mod integrity;
+mod interceptor;
+#[cfg(feature = "json-schema")]
+mod json_schema;
+mod ordering;
+mod pagination;
+mod patch;
+#[cfg(feature = "prost")]
+mod protobuf;
 mod publisher;
+mod retry;
+mod schemas;
+mod self_signed_jwt;
+mod snapshots;
+mod spool;
+mod streaming;
+mod subscribe;
 mod subscriber;
+mod subscriptions;
+mod supervisor;
+mod timeouts;
+mod topic_handle;
+mod topics;
+#[cfg(feature = "opentelemetry")]
+mod trace_context;
+mod transform;
 
+pub use ack_manager::*;
+#[cfg(feature = "avro")]
+pub use avro::*;
+pub use batching::*;
+pub use builder::*;
+#[cfg(feature = "cbor")]
+pub use cbor::*;
+pub use checkpoint::*;
+pub use compression::*;
+pub use consumer::*;
+pub use credentials::*;
+pub use decoder::*;
+pub use dedup::*;
+pub use encoder::*;
+pub use encryption::*;
 pub use error::*;
+pub use exactly_once::*;
+pub use filter::*;
+pub use iam::*;
+pub use integrity::*;
+pub use interceptor::*;
+#[cfg(feature = "json-schema")]
+pub use json_schema::*;
+pub use ordering::*;
+#[cfg(feature = "prost")]
+pub use protobuf::*;
 pub use publisher::*;
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+pub use reqwest::Certificate;
+pub use reqwest::Method;
+pub use retry::*;
+pub use schemas::*;
+pub use snapshots::*;
+pub use streaming::*;
+pub use subscribe::*;
 pub use subscriber::*;
+pub use subscriptions::*;
+pub use supervisor::*;
+pub use timeouts::*;
+pub use tokio_util::sync::CancellationToken;
+pub use topic_handle::*;
+pub use topics::*;
+pub use transform::*;
 
-use goauth::{auth::JwtClaims, credentials::Credentials, fetcher::TokenFetcher, scopes::Scope};
-use reqwest::Response;
+use auth::{CredentialsSource, TokenFetcher};
+use bytes::Bytes;
+use encryption::Cipher;
+use interceptor::Interceptors;
+use publisher::PublishRetry;
+use reqwest::{
+    header::{CONTENT_TYPE, USER_AGENT},
+    Response,
+};
 use serde::Serialize;
-use smpl_jwt::Jwt;
+use spool::Spool;
 use std::{
-    env,
     fmt::{self, Debug, Formatter},
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+use timeouts::Operation;
+use tracing::{debug, warn};
 
-const BASE_URL_ENV_VAR: &str = "PUB_SUB_BASE_URL";
-const DEFAULT_BASE_URL: &str = "https://pubsub.googleapis.com";
+const USER_PROJECT_HEADER: &str = "x-goog-user-project";
+const API_CLIENT_HEADER: &str = "x-goog-api-client";
 
+/// Client for Google Cloud Pub/Sub.
+///
+/// Cloning is cheap and clones share the cached access token, the spool, the retry budget, etc.,
+/// hence a single client can be used across tasks, e.g. by all request handlers of a web server.
+///
+/// Topic, subscription, snapshot and schema IDs are relative to the project of the client, but
+/// fully qualified names, e.g. `projects/other/topics/test`, can be used instead to access
+/// resources of other projects, e.g. to pull from a subscription in one project and publish to a
+/// topic in another.
+#[derive(Clone)]
 pub struct PubSubClient {
+    project_id: String,
+    api_url: String,
     project_url: String,
-    token_fetcher: TokenFetcher,
+    token_fetcher: Option<Arc<TokenFetcher>>,
     reqwest_client: reqwest::Client,
+    spool: Option<Arc<Spool>>,
+    publish_retry: Option<Arc<PublishRetry>>,
+    request_retry: Option<Arc<RequestRetryPolicy>>,
+    compression: Option<CompressionConfig>,
+    cipher: Option<Cipher>,
+    crc32c: bool,
+    default_timeouts: DefaultTimeouts,
+    interceptors: Interceptors,
+    region: Option<String>,
+    quota_project_id: Option<String>,
+    user_agent: String,
+    api_client: String,
+    #[cfg(feature = "avro")]
+    avro_codecs: Arc<std::sync::Mutex<std::collections::HashMap<String, Arc<AvroCodec>>>>,
 }
 
 impl PubSubClient {
@@ -30,46 +150,257 @@ impl PubSubClient {
     where
         T: AsRef<str>,
     {
-        let key_path = key_path.as_ref();
-        let credentials =
-            Credentials::from_file(key_path).map_err(|source| Error::Initialization {
-                reason: format!("missing or malformed service account key at `{key_path}`"),
-                source: source.into(),
-            })?;
-
-        let base_url = env::var(BASE_URL_ENV_VAR).unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
-        let project_id = credentials.project();
-        let project_url = format!("{base_url}/v1/projects/{project_id}");
-
-        let jwt = Jwt::new(
-            JwtClaims::new(
-                credentials.iss(),
-                &Scope::PubSub,
-                credentials.token_uri(),
-                None,
-                None,
-            ),
-            credentials
-                .rsa_key()
-                .map_err(|source| Error::Initialization {
-                    reason: format!("malformed private key in service account key at `{key_path}`"),
-                    source: source.into(),
-                })?,
-            None,
-        );
+        Self::builder(key_path)
+            .with_refresh_buffer(refresh_buffer)
+            .build()
+    }
+
+    /// Create a [PubSubClient] for the service account key – or other credentials file – at the
+    /// given path and verify it, see [PubSubClientBuilder::build_verified].
+    pub async fn new_verified<T>(
+        key_path: T,
+        refresh_buffer: Duration,
+        topic_id: Option<&str>,
+    ) -> Result<Self, Error>
+    where
+        T: AsRef<str>,
+    {
+        Self::builder(key_path)
+            .with_refresh_buffer(refresh_buffer)
+            .build_verified(topic_id, None)
+            .await
+    }
+
+    /// Create a [PubSubClientBuilder] for the service account key – or other credentials file,
+    /// see [PubSubClient::json_builder] – at the given path, allowing for further configuration.
+    pub fn builder<T>(key_path: T) -> PubSubClientBuilder
+    where
+        T: AsRef<str>,
+    {
+        PubSubClientBuilder::new(CredentialsSource::KeyPath(key_path.as_ref().to_string()))
+    }
+
+    /// Create a [PubSubClient] for the service account key file at the path given by the
+    /// `GOOGLE_APPLICATION_CREDENTIALS` environment variable, see [PubSubClient::env_builder].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::env_builder()?.build()
+    }
+
+    /// Create a [PubSubClientBuilder] for the service account key file at the path given by the
+    /// `GOOGLE_APPLICATION_CREDENTIALS` environment variable, allowing for further configuration.
+    /// Like for all other ways to create a [PubSubClient], the base URL of the Pub/Sub service
+    /// can be overridden with the `PUB_SUB_BASE_URL` environment variable and the emulator is used
+    /// without authentication if the `PUBSUB_EMULATOR_HOST` environment variable is set, in which
+    /// case `GOOGLE_APPLICATION_CREDENTIALS` is optional.
+    pub fn env_builder() -> Result<PubSubClientBuilder, Error> {
+        let credentials = CredentialsSource::from_env()?;
+        Ok(PubSubClientBuilder::new(credentials))
+    }
+
+    /// Create a [PubSubClient] for the given credentials JSON, i.e. the content of a service
+    /// account key file, e.g. taken from a secrets manager or an environment variable such that
+    /// the key never needs to be written to disk, or of another credentials file, see
+    /// [PubSubClient::json_builder].
+    pub fn from_json<T>(credentials_json: T, refresh_buffer: Duration) -> Result<Self, Error>
+    where
+        T: AsRef<str>,
+    {
+        Self::json_builder(credentials_json)?
+            .with_refresh_buffer(refresh_buffer)
+            .build()
+    }
 
-        let refresh_buffer = refresh_buffer
-            .try_into()
-            .map_err(|source| Error::Initialization {
-                reason: format!("invalid refresh_buffer `{refresh_buffer:?}`"),
-                source: Box::new(source),
-            })?;
+    /// Create a [PubSubClientBuilder] for the given credentials JSON of type `service_account`,
+    /// `external_account` – for workload identity federation, e.g. from AWS or an OIDC provider
+    /// like GitHub Actions – or `authorized_user` – as written by
+    /// `gcloud auth application-default login` for local development – allowing for further
+    /// configuration.
+    pub fn json_builder<T>(credentials_json: T) -> Result<PubSubClientBuilder, Error>
+    where
+        T: AsRef<str>,
+    {
+        let credentials = CredentialsSource::from_json(credentials_json.as_ref())?;
+        Ok(PubSubClientBuilder::new(credentials))
+    }
 
-        Ok(Self {
-            project_url,
-            token_fetcher: TokenFetcher::new(jwt, credentials, refresh_buffer),
-            reqwest_client: reqwest::Client::new(),
-        })
+    /// Create a [PubSubClient] for the given service account [Credentials].
+    pub fn from_credentials(
+        credentials: Credentials,
+        refresh_buffer: Duration,
+    ) -> Result<Self, Error> {
+        Self::credentials_builder(credentials)
+            .with_refresh_buffer(refresh_buffer)
+            .build()
+    }
+
+    /// Create a [PubSubClientBuilder] for the given service account [Credentials], allowing for
+    /// further configuration.
+    pub fn credentials_builder(credentials: Credentials) -> PubSubClientBuilder {
+        PubSubClientBuilder::new(CredentialsSource::ServiceAccount(Box::new(credentials)))
+    }
+
+    /// Create a [PubSubClient] for the Pub/Sub emulator at the given base URL, e.g.
+    /// `http://localhost:8085`, see [PubSubClient::emulator_builder].
+    pub fn emulator<T, U>(project_id: T, base_url: U) -> Result<Self, Error>
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        Self::emulator_builder(project_id, base_url).build()
+    }
+
+    /// Create a [PubSubClientBuilder] for the Pub/Sub emulator at the given base URL, allowing for
+    /// further configuration. Requests are not authenticated, hence no credentials are needed.
+    pub fn emulator_builder<T, U>(project_id: T, base_url: U) -> PubSubClientBuilder
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        PubSubClientBuilder::new(CredentialsSource::Anonymous)
+            .with_project_id(project_id)
+            .with_base_url(base_url)
+    }
+
+    /// Create a [PubSubClient] using Application Default Credentials, see
+    /// [PubSubClient::application_default_builder].
+    pub async fn application_default(refresh_buffer: Duration) -> Result<Self, Error> {
+        Self::application_default_builder()
+            .await?
+            .with_refresh_buffer(refresh_buffer)
+            .build()
+    }
+
+    /// Create a [PubSubClientBuilder] using Application Default Credentials, allowing for further
+    /// configuration. These are resolved like by all Google client libraries, such that the same
+    /// code runs locally as well as on GCE, GKE or Cloud Run:
+    /// - the service account key file at the path given by the `GOOGLE_APPLICATION_CREDENTIALS`
+    ///   environment variable,
+    /// - the well-known file written by `gcloud auth application-default login`,
+    /// - the instance metadata server, in which case the project ID is also taken from there.
+    pub async fn application_default_builder() -> Result<PubSubClientBuilder, Error> {
+        let credentials = CredentialsSource::application_default().await?;
+        Ok(PubSubClientBuilder::new(credentials))
+    }
+
+    /// Create a [PubSubClient] fetching access tokens from the instance metadata server, see
+    /// [PubSubClient::metadata_server_builder].
+    pub async fn metadata_server(refresh_buffer: Duration) -> Result<Self, Error> {
+        Self::metadata_server_builder()
+            .await?
+            .with_refresh_buffer(refresh_buffer)
+            .build()
+    }
+
+    /// Create a [PubSubClientBuilder] fetching access tokens for the service account attached to
+    /// the instance – e.g. via workload identity on GKE – from the metadata server on GCE, GKE or
+    /// Cloud Run, which does not require any exported service account key; the project ID is also
+    /// taken from the metadata server. Fails if the metadata server cannot be reached.
+    pub async fn metadata_server_builder() -> Result<PubSubClientBuilder, Error> {
+        let credentials = CredentialsSource::metadata_server().await?;
+        Ok(PubSubClientBuilder::new(credentials))
+    }
+
+    /// Returns this client with the given retry policy instead of the one configured via
+    /// [PubSubClientBuilder::with_request_retry], e.g. `None` to disable retrying for a single
+    /// non-idempotent call; as cloning is cheap, use `client.clone().with_request_retry(...)` to
+    /// keep the original client.
+    pub fn with_request_retry(mut self, request_retry: Option<RequestRetryPolicy>) -> Self {
+        self.request_retry = request_retry.map(Arc::new);
+        self
+    }
+
+    /// Verify the configuration by fetching an access token and – if a topic ID is given – getting
+    /// that topic, such that e.g. a bad service account key or a missing IAM role are detected at
+    /// startup rather than when publishing or pulling for the first time. If a region is
+    /// configured, the message storage policy of the topic must allow it.
+    #[tracing::instrument]
+    pub async fn verify(
+        &self,
+        topic_id: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        if let Some(token_fetcher) = &self.token_fetcher {
+            token_fetcher.fetch_token().await?;
+            debug!("successfully fetched token");
+        }
+
+        if let Some(topic_id) = topic_id {
+            let topic = self.get_topic(topic_id, timeout).await?;
+            debug!(topic_id, "successfully got topic");
+            self.check_region(topic.message_storage_policy.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Send an authenticated request with the given method, path – relative to the project URL,
+    /// e.g. `topics/my-topic`, or a fully qualified path, e.g. `projects/other/topics/my-topic` –
+    /// and optional JSON body, which allows for calling Pub/Sub endpoints not (yet) wrapped by this
+    /// client. Responses with a status code other than 2xx are mapped to
+    /// [Error::UnexpectedHttpStatusCode].
+    #[tracing::instrument(skip(body))]
+    pub async fn send_authenticated<R>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&R>,
+        timeout: Option<Duration>,
+    ) -> Result<Response, Error>
+    where
+        R: Serialize,
+    {
+        let path = path.trim_start_matches('/');
+        let url = if path.starts_with("projects/") {
+            format!("{}/{path}", self.api_url)
+        } else {
+            format!("{}/{path}", self.project_url)
+        };
+        let response = self
+            .send_request_with_method(method, &url, body, timeout, Operation::Admin)
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Error::unexpected_http_status_code(response).await);
+        }
+
+        Ok(response)
+    }
+
+    /// ID of the project of this client, to which topic, subscription, etc. IDs are relative.
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
+    /// Returns the fully qualified name of the topic with the given ID, e.g.
+    /// `projects/my-project/topics/my-topic`; fully qualified names are returned as is.
+    pub fn topic_name(&self, topic_id: &str) -> String {
+        self.resource_name("topics", topic_id)
+    }
+
+    /// Returns the fully qualified name of the subscription with the given ID, e.g.
+    /// `projects/my-project/subscriptions/my-subscription`; fully qualified names are returned as
+    /// is.
+    pub fn subscription_name(&self, subscription_id: &str) -> String {
+        self.resource_name("subscriptions", subscription_id)
+    }
+
+    pub(crate) fn snapshot_name(&self, snapshot_id: &str) -> String {
+        self.resource_name("snapshots", snapshot_id)
+    }
+
+    pub(crate) fn schema_name(&self, schema_id: &str) -> String {
+        self.resource_name("schemas", schema_id)
+    }
+
+    /// Returns the fully qualified name of the resource with the given ID in the given collection
+    /// of the project, unless the ID already is a fully qualified name.
+    fn resource_name(&self, collection: &str, id: &str) -> String {
+        if is_qualified(id) {
+            id.to_string()
+        } else {
+            let project_id = &self.project_id;
+            format!("projects/{project_id}/{collection}/{id}")
+        }
     }
 
     async fn send_request<R>(
@@ -77,26 +408,165 @@ impl PubSubClient {
         url: &str,
         request: &R,
         timeout: Option<Duration>,
+        operation: Operation,
+    ) -> Result<Response, Error>
+    where
+        R: Serialize,
+    {
+        self.send_request_with_method(Method::POST, url, Some(request), timeout, operation)
+            .await
+    }
+
+    async fn send_request_with_method<R>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&R>,
+        timeout: Option<Duration>,
+        operation: Operation,
     ) -> Result<Response, Error>
     where
         R: Serialize,
     {
-        let token = self.token_fetcher.fetch_token().await.map_err(Box::new)?;
+        let body = body
+            .map(serde_json::to_vec)
+            .transpose()
+            .map_err(Error::Serialize)?
+            .map(Bytes::from);
+        self.send_serialized_with_method(method, url, body, timeout, operation)
+            .await
+    }
+
+    /// Send a request with the given already serialized JSON body, which is shared by retries.
+    async fn send_serialized(
+        &self,
+        url: &str,
+        body: Bytes,
+        timeout: Option<Duration>,
+        operation: Operation,
+    ) -> Result<Response, Error> {
+        self.send_serialized_with_method(Method::POST, url, Some(body), timeout, operation)
+            .await
+    }
+
+    async fn send_serialized_with_method(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<Bytes>,
+        timeout: Option<Duration>,
+        operation: Operation,
+    ) -> Result<Response, Error> {
+        let timeout = self.default_timeouts.resolve(operation, timeout);
+
+        // Publish requests are retried by the dedicated publish retry, if configured.
+        let request_retry = self
+            .request_retry
+            .as_deref()
+            .filter(|_| operation != Operation::Publish || self.publish_retry.is_none());
+        let Some(request_retry) = request_retry else {
+            return self.send_request_once(method, url, body, timeout).await;
+        };
+
+        let deadline = request_retry
+            .deadline
+            .map(|deadline| Instant::now() + deadline);
+        let mut retries = 0;
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+                }
+                None => timeout,
+            };
+            let result = self
+                .send_request_once(method.clone(), url, body.clone(), timeout)
+                .await;
+
+            let retryable = match &result {
+                Ok(response) => request_retry.is_retryable(response.status()),
+                Err(error) => error.is_transient(),
+            };
+            let backoff = retryable
+                .then(|| request_retry.backoff(retries))
+                .flatten()
+                .filter(|backoff| {
+                    deadline.is_none_or(|deadline| Instant::now() + *backoff < deadline)
+                });
+            match backoff {
+                Some(backoff) => {
+                    let reason = match &result {
+                        Ok(response) => response.status().to_string(),
+                        Err(error) => error.to_string(),
+                    };
+                    warn!(reason, ?backoff, url, "request failed, retrying");
+                    tokio::time::sleep(backoff).await;
+                    retries += 1;
+                }
+                None => return result,
+            }
+        }
+    }
 
+    async fn send_request_once(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<Bytes>,
+        timeout: Option<Duration>,
+    ) -> Result<Response, Error> {
         let request = self
             .reqwest_client
-            .post(url)
-            .bearer_auth(token.access_token())
-            .json(request);
+            .request(method, url)
+            .header(USER_AGENT, &self.user_agent)
+            .header(API_CLIENT_HEADER, &self.api_client);
+        let request = match &self.token_fetcher {
+            Some(token_fetcher) => {
+                let token = token_fetcher.fetch_token().await?;
+                request.bearer_auth(token.access_token())
+            }
+            None => request,
+        };
+        let request = self
+            .quota_project_id
+            .iter()
+            .fold(request, |r, p| r.header(USER_PROJECT_HEADER, p));
+        let request = body.into_iter().fold(request, |r, b| {
+            r.header(CONTENT_TYPE, "application/json").body(b)
+        });
         let request = timeout.into_iter().fold(request, |r, t| r.timeout(t));
 
-        request
-            .send()
+        let mut request = request.build().map_err(Error::HttpServiceCommunication)?;
+        self.interceptors
+            .on_request(&mut request)
+            .map_err(Error::Interceptor)?;
+        let response = self
+            .reqwest_client
+            .execute(request)
             .await
-            .map_err(Error::HttpServiceCommunication)
+            .map_err(Error::HttpServiceCommunication)?;
+        self.interceptors.on_response(&response);
+
+        Ok(response)
+    }
+}
+
+/// Returns the path of the resource with the given ID in the given collection, relative to the
+/// project URL, unless the ID is a fully qualified name, e.g. `projects/other/topics/test`, which
+/// is used as is and resolved relative to the API URL by [PubSubClient::send_authenticated].
+pub(crate) fn resource_path(collection: &str, id: &str) -> String {
+    if is_qualified(id) {
+        id.to_string()
+    } else {
+        format!("{collection}/{id}")
     }
 }
 
+fn is_qualified(id: &str) -> bool {
+    id.starts_with("projects/")
+}
+
 impl Debug for PubSubClient {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("PubSubClient")
@@ -107,16 +577,9 @@ impl Debug for PubSubClient {
 
 #[cfg(test)]
 mod tests {
-    use super::{Error, PubSubClient};
-    use serde::Deserialize;
+    use super::{resource_path, Error, PubSubClient};
     use std::time::Duration;
 
-    #[derive(Debug, Deserialize, PartialEq, Eq)]
-    enum Message {
-        Foo { text: String },
-        Bar { text: String },
-    }
-
     #[test]
     fn test_new_err_non_existent_key() {
         let result = PubSubClient::new("non_existent", Duration::from_secs(30));
@@ -155,4 +618,117 @@ mod tests {
             other => panic!("Expected Error::InvalidPrivateKey, but was `{other}`"),
         }
     }
+
+    #[test]
+    fn test_clone() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let pub_sub_client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        let cloned = pub_sub_client.clone();
+        assert_send_sync(&cloned);
+        assert_eq!(cloned.project_url, pub_sub_client.project_url);
+    }
+
+    #[tokio::test]
+    async fn test_build_verified_err_unreachable() {
+        let result = PubSubClient::emulator_builder("test", "http://localhost:1")
+            .build_verified(None, Some(Duration::from_secs(5)))
+            .await;
+        assert!(matches!(result, Err(Error::Initialization { .. })));
+    }
+
+    #[test]
+    fn test_build_with_base_url() {
+        let pub_sub_client = PubSubClient::emulator_builder("test", "http://localhost:8085")
+            .with_base_url("http://localhost:8086/")
+            .build()
+            .unwrap();
+        assert_eq!(
+            pub_sub_client.project_url,
+            "http://localhost:8086/v1/projects/test"
+        );
+    }
+
+    #[test]
+    fn test_resource_names() {
+        let pub_sub_client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        assert_eq!(pub_sub_client.project_id(), "test");
+        assert_eq!(
+            pub_sub_client.subscription_name("subscription"),
+            "projects/test/subscriptions/subscription"
+        );
+        assert_eq!(
+            pub_sub_client.topic_name("topic"),
+            "projects/test/topics/topic"
+        );
+        assert_eq!(
+            pub_sub_client.topic_name("projects/other/topics/topic"),
+            "projects/other/topics/topic"
+        );
+        assert_eq!(
+            pub_sub_client.subscription_url("projects/other/subscriptions/subscription", "pull"),
+            "http://localhost:8085/v1/projects/other/subscriptions/subscription:pull"
+        );
+
+        assert_eq!(resource_path("topics", "topic"), "topics/topic");
+        assert_eq!(
+            resource_path("topics", "projects/other/topics/topic"),
+            "projects/other/topics/topic"
+        );
+    }
+
+    #[test]
+    fn test_from_json_err_invalid_json() {
+        let result = PubSubClient::from_json("{}", Duration::from_secs(30));
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            Error::Initialization {
+                reason: _,
+                source: _,
+            } => (),
+            other => panic!("Expected Error::Initialization, but was `{other}`"),
+        }
+    }
+
+    #[test]
+    fn test_build_err_invalid_clock_skew() {
+        let result = PubSubClient::builder("tests/invalid_key.json")
+            .with_clock_skew(Duration::from_secs(7200))
+            .build();
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            Error::Initialization { reason, source: _ } => {
+                assert!(reason.starts_with("invalid clock_skew"))
+            }
+            other => panic!("Expected Error::Initialization, but was `{other}`"),
+        }
+    }
+
+    #[test]
+    fn test_build_err_invalid_region() {
+        let result = PubSubClient::emulator_builder("test", "http://localhost:8085")
+            .with_region("US Central")
+            .build();
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            Error::Initialization { reason, source: _ } => {
+                assert!(reason.starts_with("invalid region"))
+            }
+            other => panic!("Expected Error::Initialization, but was `{other}`"),
+        }
+    }
+
+    #[test]
+    fn test_build_err_invalid_token_lifetime() {
+        let result = PubSubClient::builder("tests/invalid_key.json")
+            .with_token_lifetime(Duration::from_secs(7200))
+            .build();
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            Error::Initialization { reason, source: _ } => {
+                assert!(reason.starts_with("invalid token_lifetime"))
+            }
+            other => panic!("Expected Error::Initialization, but was `{other}`"),
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use crate::{error::Error, PubSubClient, PublishedMessageEnvelope};
+use futures::lock::Mutex as AsyncMutex;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::warn;
+
+/// Publishes messages with ordering keys to a topic, honoring the ordering semantics of the
+/// Pub/Sub service: publishes for the same ordering key are sequenced, i.e. sent one after the
+/// other, and a failed publish pauses the ordering key, i.e. further publishes for it fail with
+/// [Error::OrderingKeyPaused] until [OrderingKeyPublisher::resume_publish] is called. This
+/// prevents messages from being published out of order after a failure.
+#[derive(Debug)]
+pub struct OrderingKeyPublisher<'a> {
+    client: &'a PubSubClient,
+    topic_id: String,
+    sequencer: Sequencer,
+}
+
+impl<'a> OrderingKeyPublisher<'a> {
+    pub fn new<T>(client: &'a PubSubClient, topic_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            client,
+            topic_id: topic_id.into(),
+            sequencer: Sequencer::default(),
+        }
+    }
+
+    /// Publish the given messages with the given ordering key, overriding any ordering keys of
+    /// the messages, once preceding publishes for this ordering key have completed.
+    pub async fn publish<M, E>(
+        &self,
+        ordering_key: &str,
+        envelopes: Vec<E>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error>
+    where
+        M: Serialize + Debug,
+        E: Into<PublishedMessageEnvelope<M>>,
+    {
+        let lock = self.sequencer.lock(ordering_key);
+        let _guard = lock.lock().await;
+        self.sequencer.check(ordering_key)?;
+
+        let envelopes = envelopes
+            .into_iter()
+            .map(|envelope| {
+                let mut envelope = envelope.into();
+                envelope.ordering_key = None;
+                envelope
+            })
+            .collect::<Vec<_>>();
+        let result = self
+            .client
+            .publish::<M, _>(&self.topic_id, envelopes, Some(ordering_key), timeout)
+            .await;
+
+        if let Err(error) = &result {
+            warn!(
+                %error,
+                topic_id = self.topic_id,
+                ordering_key,
+                "publishing failed, pausing ordering key"
+            );
+            self.sequencer.pause(ordering_key);
+        }
+        result
+    }
+
+    /// Resume publishing for the given ordering key after it has been paused because of a failed
+    /// publish.
+    pub fn resume_publish(&self, ordering_key: &str) {
+        self.sequencer.resume(ordering_key);
+    }
+
+    /// Whether publishing for the given ordering key is paused.
+    pub fn is_paused(&self, ordering_key: &str) -> bool {
+        self.sequencer.check(ordering_key).is_err()
+    }
+}
+
+/// Sequences publishes per ordering key and keeps track of paused ordering keys.
+#[derive(Debug, Default)]
+struct Sequencer {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    paused: Mutex<HashSet<String>>,
+}
+
+impl Sequencer {
+    fn lock(&self, ordering_key: &str) -> Arc<AsyncMutex<()>> {
+        let mut locks = self.locks.lock().expect("lock locks");
+        // Forget locks not in use, i.e. only referenced by the map.
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(ordering_key.to_string()).or_default().clone()
+    }
+
+    fn check(&self, ordering_key: &str) -> Result<(), Error> {
+        if self
+            .paused
+            .lock()
+            .expect("lock paused")
+            .contains(ordering_key)
+        {
+            return Err(Error::OrderingKeyPaused(ordering_key.to_string()));
+        }
+        Ok(())
+    }
+
+    fn pause(&self, ordering_key: &str) {
+        self.paused
+            .lock()
+            .expect("lock paused")
+            .insert(ordering_key.to_string());
+    }
+
+    fn resume(&self, ordering_key: &str) {
+        self.paused
+            .lock()
+            .expect("lock paused")
+            .remove(ordering_key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sequencer;
+    use crate::Error;
+    use futures::executor::block_on;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pause_resume() {
+        let sequencer = Sequencer::default();
+        assert!(sequencer.check("a").is_ok());
+
+        sequencer.pause("a");
+        assert!(matches!(sequencer.check("a"), Err(Error::OrderingKeyPaused(key)) if key == "a"));
+        assert!(sequencer.check("b").is_ok());
+
+        sequencer.resume("a");
+        assert!(sequencer.check("a").is_ok());
+    }
+
+    #[test]
+    fn test_lock() {
+        let sequencer = Sequencer::default();
+        let a = sequencer.lock("a");
+        assert!(Arc::ptr_eq(&a, &sequencer.lock("a")));
+
+        let guard = block_on(a.lock());
+        assert!(a.try_lock().is_none());
+        assert!(sequencer.lock("b").try_lock().is_some());
+        drop(guard);
+
+        drop(a);
+        sequencer.lock("c");
+        assert_eq!(sequencer.locks.lock().unwrap().len(), 1);
+    }
+}
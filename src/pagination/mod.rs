@@ -0,0 +1,89 @@
+use crate::{error::Error, PubSubClient};
+use futures::{stream, Stream, TryStreamExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use tracing::debug;
+
+/// Characters encoded in query parameter values, i.e. all but unreserved ones.
+const QUERY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Response of a list request, i.e. one page of items.
+pub(crate) trait Page: DeserializeOwned {
+    type Item;
+
+    /// The items of this page and the token for the next page, if any.
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+impl PubSubClient {
+    /// Stream the items of all pages of the list request with the given path, which may already
+    /// contain a query, following the next page tokens transparently.
+    pub(crate) fn paginate<'a, P>(
+        &'a self,
+        path: String,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<P::Item, Error>> + 'a
+    where
+        P: Page + 'a,
+    {
+        stream::try_unfold(Some(None), move |page_token: Option<Option<String>>| {
+            let path = path.clone();
+            async move {
+                let Some(page_token) = page_token else {
+                    return Ok::<_, Error>(None);
+                };
+
+                let query = page_size
+                    .map(|page_size| format!("pageSize={page_size}"))
+                    .into_iter()
+                    .chain(page_token.map(|page_token| {
+                        format!(
+                            "pageToken={}",
+                            utf8_percent_encode(&page_token, QUERY_ENCODE_SET)
+                        )
+                    }))
+                    .collect::<Vec<_>>()
+                    .join("&");
+                let path = match (query.is_empty(), path.contains('?')) {
+                    (true, _) => path,
+                    (false, true) => format!("{path}&{query}"),
+                    (false, false) => format!("{path}?{query}"),
+                };
+
+                debug!(path, "listing page");
+                let (items, next_page_token) = self
+                    .send_authenticated::<()>(Method::GET, &path, None, timeout)
+                    .await?
+                    .json::<P>()
+                    .await
+                    .map_err(Error::UnexpectedHttpResponse)?
+                    .into_parts();
+                let next_page_token = next_page_token.filter(|token| !token.is_empty());
+
+                Ok(Some((items, next_page_token.map(Some))))
+            }
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QUERY_ENCODE_SET;
+    use percent_encoding::utf8_percent_encode;
+
+    #[test]
+    fn test_query_encode_set() {
+        let encode = |s| utf8_percent_encode(s, QUERY_ENCODE_SET).to_string();
+        assert_eq!(encode("abc-123_.~"), "abc-123_.~");
+        assert_eq!(encode("a+b/c=="), "a%2Bb%2Fc%3D%3D");
+    }
+}
@@ -0,0 +1,20 @@
+//! Support for partial updates of resources: patches serialize only the fields which are set,
+//! and these make up the update mask.
+
+use crate::error::Error;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Serialize the given patch and derive the update mask from its fields, e.g.
+/// `"ackDeadlineSeconds,labels"`.
+pub(crate) fn serialize<P>(patch: &P) -> Result<(Value, String), Error>
+where
+    P: Serialize,
+{
+    let patch = serde_json::to_value(patch).map_err(Error::Serialize)?;
+    let update_mask = patch
+        .as_object()
+        .map(|fields| fields.keys().cloned().collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    Ok((patch, update_mask))
+}
@@ -0,0 +1,73 @@
+use crate::{Decoder, Encoder, RawPulledMessageEnvelope};
+use prost::Message;
+use std::error::Error as StdError;
+
+/// [Encoder] for protobuf messages, i.e. types implementing [prost::Message], to be used with
+/// [PubSubClient::publish_with_encoder](crate::PubSubClient::publish_with_encoder).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufEncoder;
+
+impl<M> Encoder<M> for ProtobufEncoder
+where
+    M: Message,
+{
+    fn encode(&self, message: &M) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync + 'static>> {
+        Ok(message.encode_to_vec())
+    }
+}
+
+/// [Decoder] for protobuf messages, i.e. types implementing [prost::Message], to be used with
+/// [PubSubClient::pull_with_decoder](crate::PubSubClient::pull_with_decoder).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufDecoder;
+
+impl<M> Decoder<M> for ProtobufDecoder
+where
+    M: Message + Default,
+{
+    fn decode(
+        &self,
+        _envelope: &RawPulledMessageEnvelope,
+        data: &[u8],
+    ) -> Result<M, Box<dyn StdError + Send + Sync + 'static>> {
+        M::decode(data).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProtobufDecoder, ProtobufEncoder};
+    use crate::{Decoder, Encoder, RawPulledMessage, RawPulledMessageEnvelope};
+    use time::OffsetDateTime;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Message {
+        #[prost(string, tag = "1")]
+        text: String,
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let envelope = RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: None,
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let message = Message {
+            text: "test".to_string(),
+        };
+
+        let data = ProtobufEncoder.encode(&message).unwrap();
+        let decoded: Message = ProtobufDecoder.decode(&envelope, &data).unwrap();
+        assert_eq!(decoded, message);
+
+        let decoded: Result<Message, _> = ProtobufDecoder.decode(&envelope, &[0xff]);
+        assert!(decoded.is_err());
+    }
+}
@@ -1,38 +1,73 @@
-use crate::{error::Error, PubSubClient};
+use crate::{
+    compression::compress,
+    encryption::ENCRYPTED_ATTRIBUTE,
+    error::Error,
+    integrity::{add_checksum, CRC32C_ATTRIBUTE},
+    retry::{RetryBudget, RetryConfig, RetryTokens},
+    timeouts::Operation,
+    PubSubClient,
+};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Debug, time::Duration};
-use tracing::debug;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use serde::{
+    ser::{self, SerializeSeq},
+    Deserialize, Serialize, Serializer,
+};
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, time::Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
 
-pub struct PublishedMessageEnvelope<M>
-where
-    M: Serialize,
-{
-    message: M,
-    attributes: Option<HashMap<String, String>>,
+const MAX_DATA_BYTES: usize = 10_000_000;
+const MAX_ATTRIBUTES: usize = 100;
+const MAX_ATTRIBUTE_KEY_BYTES: usize = 256;
+const MAX_ATTRIBUTE_VALUE_BYTES: usize = 1_024;
+const RESERVED_ATTRIBUTE_KEY_PREFIX: &str = "goog";
+
+/// ID assigned to a published message by the Pub/Sub service.
+pub type MessageId = String;
+
+#[derive(Debug)]
+pub struct PublishedMessageEnvelope<M> {
+    pub(crate) message: M,
+    pub(crate) attributes: Option<HashMap<String, String>>,
+    pub(crate) ordering_key: Option<String>,
 }
 
-impl<M> From<M> for PublishedMessageEnvelope<M>
-where
-    M: Serialize,
-{
-    fn from(message: M) -> Self {
+impl<M> PublishedMessageEnvelope<M> {
+    pub fn new(message: M) -> Self {
         Self {
             message,
             attributes: None,
+            ordering_key: None,
         }
     }
+
+    pub fn with_attributes(mut self, attributes: HashMap<String, String>) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+
+    /// Use the given ordering key for this message instead of the one given for the whole batch,
+    /// if any.
+    pub fn with_ordering_key<T>(mut self, ordering_key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.ordering_key = Some(ordering_key.into());
+        self
+    }
 }
 
-impl<M> From<(M, HashMap<String, String>)> for PublishedMessageEnvelope<M>
-where
-    M: Serialize,
-{
+impl<M> From<M> for PublishedMessageEnvelope<M> {
+    fn from(message: M) -> Self {
+        Self::new(message)
+    }
+}
+
+impl<M> From<(M, HashMap<String, String>)> for PublishedMessageEnvelope<M> {
     fn from((message, attributes): (M, HashMap<String, String>)) -> Self {
-        Self {
-            message,
-            attributes: Some(attributes),
-        }
+        Self::new(message).with_attributes(attributes)
     }
 }
 
@@ -69,10 +104,123 @@ impl<'a> RawPublishedMessage<'a> {
     }
 }
 
+/// Retry settings for publishing, see
+/// [PubSubClientBuilder::with_publish_retry](crate::PubSubClientBuilder::with_publish_retry):
+/// publish requests failing with a transient error (see [Error::is_transient]) are retried with
+/// exponential backoff, by default with jitter and without a [RetryBudget].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishRetryConfig {
+    pub retry: RetryConfig,
+    pub jitter: bool,
+    pub budget: Option<RetryBudget>,
+}
+
+impl Default for PublishRetryConfig {
+    fn default() -> Self {
+        Self::new(RetryConfig::default())
+    }
+}
+
+impl PublishRetryConfig {
+    pub fn new(retry: RetryConfig) -> Self {
+        Self {
+            retry,
+            jitter: true,
+            budget: None,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+}
+
+/// A [PublishRetryConfig] along with the tokens of its [RetryBudget], if any, shared by all
+/// publish requests of a client.
+#[derive(Debug)]
+pub(crate) struct PublishRetry {
+    config: PublishRetryConfig,
+    tokens: Option<RetryTokens>,
+}
+
+impl PublishRetry {
+    pub(crate) fn new(config: PublishRetryConfig) -> Self {
+        let tokens = config.budget.map(RetryTokens::new);
+        Self { config, tokens }
+    }
+
+    /// Backoff before the given retry after the given error, or `None` if the error is not
+    /// transient or retrying is exhausted.
+    fn backoff(&self, error: &Error, retry: u32) -> Option<Duration> {
+        if !error.is_transient() {
+            return None;
+        }
+        let backoff = if self.config.jitter {
+            self.config.retry.jittered_backoff(retry)
+        } else {
+            self.config.retry.backoff(retry)
+        }?;
+        self.tokens
+            .as_ref()
+            .is_none_or(RetryTokens::withdraw)
+            .then_some(backoff)
+    }
+
+    fn succeeded(&self) {
+        if let Some(tokens) = &self.tokens {
+            tokens.deposit();
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct PublishRequest<'a> {
-    messages: Vec<RawPublishedMessage<'a>>,
+struct PublishRequest<M> {
+    messages: M,
+}
+
+/// Messages of a publish request which are prepared one at a time while they are serialized, such
+/// that only the request body gets buffered. Each message comes with its own ordering key, if any,
+/// which takes precedence over the one of the message. The first error stops serializing and is
+/// kept, such that it can be returned instead of the serialization error.
+struct LazyMessages<I> {
+    messages: RefCell<I>,
+    error: RefCell<Option<Error>>,
+}
+
+impl<'a, I> Serialize for LazyMessages<I>
+where
+    I: Iterator<Item = Result<(RawPublishedMessage<'a>, Option<String>), Error>>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(None)?;
+        for message in self.messages.borrow_mut().by_ref() {
+            match message {
+                Ok((message, ordering_key)) => {
+                    let message = RawPublishedMessage {
+                        ordering_key: ordering_key.as_deref().or(message.ordering_key),
+                        ..message
+                    };
+                    seq.serialize_element(&message)?;
+                }
+                Err(error) => {
+                    let reason = error.to_string();
+                    *self.error.borrow_mut() = Some(error);
+                    return Err(ser::Error::custom(reason));
+                }
+            }
+        }
+        seq.end()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,53 +230,318 @@ struct PublishResponse {
 }
 
 impl PubSubClient {
-    #[tracing::instrument]
+    /// Publish the given messages with the given ordering key, unless a message has its own one,
+    /// see [PublishedMessageEnvelope::with_ordering_key]. The messages are serialized, Base64
+    /// encoded and validated one at a time while the body of the publish request is written, i.e.
+    /// besides that body they are not buffered.
+    #[tracing::instrument(skip(envelopes))]
     pub async fn publish<M, E>(
         &self,
         topic_id: &str,
-        envelopes: Vec<E>,
+        envelopes: impl IntoIterator<Item = E>,
         ordering_key: Option<&'_ str>,
         timeout: Option<Duration>,
     ) -> Result<Vec<String>, Error>
     where
         M: Serialize,
-        E: Into<PublishedMessageEnvelope<M>> + Debug,
+        E: Into<PublishedMessageEnvelope<M>>,
     {
-        let bytes = envelopes
-            .into_iter()
-            .map(|envelope| {
-                let PublishedMessageEnvelope {
-                    message,
-                    attributes,
-                } = envelope.into();
-                serde_json::to_vec(&message).map(|bytes| (bytes, attributes))
-            })
-            .collect::<Result<Vec<_>, _>>();
+        self.publish_encoded(topic_id, envelopes, ordering_key, timeout, |message| {
+            serde_json::to_vec(message).map_err(Error::Serialize)
+        })
+        .await
+    }
 
-        let messages = bytes
-            .map_err(Error::Serialize)?
-            .into_iter()
-            .map(|(bytes, attributes)| RawPublishedMessage {
+    /// Publish the given messages encoded with the given function.
+    pub(crate) async fn publish_encoded<M, E, F>(
+        &self,
+        topic_id: &str,
+        envelopes: impl IntoIterator<Item = E>,
+        ordering_key: Option<&'_ str>,
+        timeout: Option<Duration>,
+        encode: F,
+    ) -> Result<Vec<String>, Error>
+    where
+        E: Into<PublishedMessageEnvelope<M>>,
+        F: Fn(&M) -> Result<Vec<u8>, Error>,
+    {
+        let messages = envelopes.into_iter().map(|envelope| {
+            let PublishedMessageEnvelope {
+                message,
+                attributes,
+                ordering_key: own_ordering_key,
+            } = envelope.into();
+            let (bytes, attributes) =
+                compress(self.compression.as_ref(), encode(&message)?, attributes)?;
+            let message = RawPublishedMessage {
                 data: Some(STANDARD.encode(bytes)),
                 attributes,
                 ordering_key,
+            };
+            Ok((message, own_ordering_key))
+        });
+
+        self.publish_lazily(topic_id, messages, timeout).await
+    }
+
+    /// Publish the given message with the given optional attributes and ordering key, returning
+    /// its message ID.
+    #[tracing::instrument(skip(message))]
+    pub async fn publish_one<M>(
+        &self,
+        topic_id: &str,
+        message: M,
+        attributes: Option<HashMap<String, String>>,
+        ordering_key: Option<&'_ str>,
+        timeout: Option<Duration>,
+    ) -> Result<MessageId, Error>
+    where
+        M: Serialize,
+    {
+        let envelope = PublishedMessageEnvelope {
+            message,
+            attributes,
+            ordering_key: None,
+        };
+        let mut message_ids = self
+            .publish::<M, _>(topic_id, vec![envelope], ordering_key, timeout)
+            .await?;
+        message_ids.pop().ok_or(Error::NoMessageId)
+    }
+
+    /// Publish the given binary data, e.g. protobuf, with the given optional attributes and
+    /// ordering key; the data is Base64 encoded as required by the Pub/Sub service.
+    #[tracing::instrument(skip(messages))]
+    pub async fn publish_bytes(
+        &self,
+        topic_id: &str,
+        messages: Vec<(Bytes, Option<HashMap<String, String>>)>,
+        ordering_key: Option<&'_ str>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error> {
+        let messages = messages.into_iter().map(|(data, attributes)| {
+            compress(self.compression.as_ref(), data.into(), attributes).map(
+                |(data, attributes)| {
+                    let message = RawPublishedMessage {
+                        data: Some(STANDARD.encode(data)),
+                        attributes,
+                        ordering_key,
+                    };
+                    (message, None)
+                },
+            )
+        });
+
+        self.publish_lazily(topic_id, messages, timeout).await
+    }
+
+    /// Publish the given messages to several topics concurrently, at most `max_concurrency` topics
+    /// at a time. The results are returned per topic, in the order of the given publications.
+    #[tracing::instrument(skip(publications))]
+    pub async fn publish_fanout<'a, M, E>(
+        &self,
+        publications: Vec<(&'a str, Vec<E>)>,
+        ordering_key: Option<&'_ str>,
+        max_concurrency: usize,
+        timeout: Option<Duration>,
+    ) -> Vec<(&'a str, Result<Vec<String>, Error>)>
+    where
+        M: Serialize,
+        E: Into<PublishedMessageEnvelope<M>>,
+    {
+        stream::iter(publications)
+            .map(|(topic_id, envelopes)| async move {
+                let result = self
+                    .publish(topic_id, envelopes, ordering_key, timeout)
+                    .await;
+                (topic_id, result)
             })
-            .collect::<Vec<_>>();
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Publish the given raw messages.
+    ///
+    /// With the `opentelemetry` feature, the trace context of the current span, e.g. the W3C
+    /// `traceparent` and `tracestate`, is added to the attributes of the messages, see
+    /// [PulledMessage::consumer_span](crate::PulledMessage::consumer_span) for the pull side.
+    ///
+    /// The messages are validated against the limits of the Pub/Sub service one at a time while
+    /// the body of the publish request is written: data of at most 10 MB, at most 100 attributes
+    /// with keys of at most 256 bytes, not starting with `goog`, and values of at most 1024 bytes.
+    /// Otherwise [Error::InvalidMessage] is returned and nothing is sent. As adding the trace
+    /// context, checksum or ciphertext might exceed these limits, too, the messages are validated
+    /// again afterwards, then with a reason naming these additions.
+    ///
+    /// If the client has been configured with a spool and the Pub/Sub service cannot be reached,
+    /// the messages are spooled to disk and [Error::Spooled] is returned; spooled messages are
+    /// delivered in order before any later ones, see also [PubSubClient::run_spool]. Notice that
+    /// requests which timed out are spooled, too, even if the Pub/Sub service might have received
+    /// them, which results in duplicates.
+    #[tracing::instrument(skip(messages))]
+    pub async fn publish_raw<'a>(
+        &self,
+        topic_id: &str,
+        messages: impl IntoIterator<Item = RawPublishedMessage<'a>>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error> {
+        let messages = messages.into_iter().map(|message| Ok((message, None)));
+        self.publish_lazily(topic_id, messages, timeout).await
+    }
+
+    /// Publish the given messages, each with its own ordering key, if any, see [LazyMessages].
+    async fn publish_lazily<'a>(
+        &self,
+        topic_id: &str,
+        messages: impl IntoIterator<Item = Result<(RawPublishedMessage<'a>, Option<String>), Error>>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error> {
+        let body = self.publish_request_body(messages)?;
+        match &self.spool {
+            Some(spool) => spool.publish(self, topic_id, body, timeout).await,
+            None => self.send_publish_request(topic_id, body, timeout).await,
+        }
+    }
+
+    /// Serialize the body of a publish request for the given messages, preparing them one at a
+    /// time, see [PubSubClient::prepare].
+    fn publish_request_body<'a>(
+        &self,
+        messages: impl IntoIterator<Item = Result<(RawPublishedMessage<'a>, Option<String>), Error>>,
+    ) -> Result<Bytes, Error> {
+        let messages = messages.into_iter().enumerate().map(|(index, message)| {
+            let (message, ordering_key) = message?;
+            let message = self.prepare(index, message)?;
+            Ok((message, ordering_key))
+        });
+        let messages = LazyMessages {
+            messages: RefCell::new(messages),
+            error: RefCell::new(None),
+        };
+        publish_request_body(&messages).map_err(|error| messages.error.take().unwrap_or(error))
+    }
+
+    /// Prepare the given message for publishing: validate it first, such that errors refer to what
+    /// the caller passed in, then add the trace context and checksum, encrypt it and validate it
+    /// again, because these additions might exceed the limits of the Pub/Sub service, too.
+    fn prepare<'a>(
+        &self,
+        index: usize,
+        message: RawPublishedMessage<'a>,
+    ) -> Result<RawPublishedMessage<'a>, Error> {
+        validate_message(&message).map_err(|reason| Error::InvalidMessage { index, reason })?;
+
+        let message = with_trace_context(message);
+        let message = if self.crc32c {
+            add_checksum(index, message)?
+        } else {
+            message
+        };
+        let message = match &self.cipher {
+            Some(cipher) => cipher.encrypt(message)?,
+            None => message,
+        };
+
+        validate_message(&message).map_err(|reason| Error::InvalidMessage {
+            index,
+            reason: format!("{reason} after adding {}", self.additions()),
+        })?;
+        Ok(message)
+    }
+
+    /// Describes what [PubSubClient::prepare] adds to messages.
+    fn additions(&self) -> String {
+        let mut additions = Vec::new();
+        if cfg!(feature = "opentelemetry") {
+            additions.push("trace context attributes".to_string());
+        }
+        if self.crc32c {
+            additions.push(format!("`{CRC32C_ATTRIBUTE}` attribute"));
+        }
+        if self.cipher.is_some() {
+            additions.push(format!(
+                "`{ENCRYPTED_ATTRIBUTE}` attribute and encryption overhead"
+            ));
+        }
+        additions.join(", ")
+    }
+
+    /// Publish the messages from the spool, if any, returning the number of published messages.
+    pub async fn flush_spool(&self, timeout: Option<Duration>) -> Result<usize, Error> {
+        match &self.spool {
+            Some(spool) => spool.flush(self, timeout).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Flush the spool, if any, every `interval` until the given token is cancelled, then flush a
+    /// last time, such that spooled messages are delivered once the Pub/Sub service can be reached
+    /// again, even if no further messages are published.
+    pub async fn run_spool(
+        &self,
+        interval: Duration,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), Error> {
+        while cancellation_token
+            .run_until_cancelled(tokio::time::sleep(interval))
+            .await
+            .is_some()
+        {
+            if let Err(error) = self.flush_spool(None).await {
+                warn!(%error, "cannot flush spool");
+            }
+        }
+        self.flush_spool(None).await.map(|_| ())
+    }
+
+    /// Send a publish request with the given body, see [publish_request_body], retrying according
+    /// to the configured [PublishRetryConfig], if any.
+    pub(crate) async fn send_publish_request(
+        &self,
+        topic_id: &str,
+        body: Bytes,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error> {
+        let Some(publish_retry) = &self.publish_retry else {
+            return self
+                .send_publish_request_once(topic_id, body, timeout)
+                .await;
+        };
 
-        self.publish_raw(topic_id, messages, timeout).await
+        let mut retries = 0;
+        loop {
+            match self
+                .send_publish_request_once(topic_id, body.clone(), timeout)
+                .await
+            {
+                Ok(message_ids) => {
+                    publish_retry.succeeded();
+                    return Ok(message_ids);
+                }
+                Err(error) => match publish_retry.backoff(&error, retries) {
+                    Some(backoff) => {
+                        warn!(%error, ?backoff, topic_id, "publishing failed, retrying");
+                        tokio::time::sleep(backoff).await;
+                        retries += 1;
+                    }
+                    None => return Err(error),
+                },
+            }
+        }
     }
 
-    #[tracing::instrument]
-    pub async fn publish_raw(
+    async fn send_publish_request_once(
         &self,
         topic_id: &str,
-        messages: Vec<RawPublishedMessage<'_>>,
+        body: Bytes,
         timeout: Option<Duration>,
     ) -> Result<Vec<String>, Error> {
         let url = self.topic_url(topic_id);
-        let request = PublishRequest { messages };
         debug!(url, "sending request");
-        let response = self.send_request(&url, &request, timeout).await?;
+        let response = self
+            .send_serialized(&url, body, timeout, Operation::Publish)
+            .await?;
 
         if !response.status().is_success() {
             return Err(Error::unexpected_http_status_code(response).await);
@@ -144,7 +557,240 @@ impl PubSubClient {
     }
 
     fn topic_url(&self, topic_id: &str) -> String {
-        let project_url = &self.project_url;
-        format!("{project_url}/topics/{topic_id}:publish")
+        let api_url = &self.api_url;
+        format!("{api_url}/{}:publish", self.topic_name(topic_id))
+    }
+}
+
+/// Inject the trace context of the current span into the attributes of the given message.
+#[cfg(feature = "opentelemetry")]
+fn with_trace_context(mut message: RawPublishedMessage<'_>) -> RawPublishedMessage<'_> {
+    crate::trace_context::inject(&mut message.attributes);
+    message
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+fn with_trace_context(message: RawPublishedMessage<'_>) -> RawPublishedMessage<'_> {
+    message
+}
+
+/// Serialize the body of a publish request for the given messages.
+pub(crate) fn publish_request_body<M>(messages: M) -> Result<Bytes, Error>
+where
+    M: Serialize,
+{
+    serde_json::to_vec(&PublishRequest { messages })
+        .map(Bytes::from)
+        .map_err(Error::Serialize)
+}
+
+/// Validate the given message against the limits of the Pub/Sub service.
+fn validate_message(message: &RawPublishedMessage<'_>) -> Result<(), String> {
+    let data_bytes = message.data.as_deref().map(decoded_len).unwrap_or_default();
+    if data_bytes > MAX_DATA_BYTES {
+        return Err(format!(
+            "data has {data_bytes} bytes, but at most {MAX_DATA_BYTES} are allowed"
+        ));
+    }
+
+    let Some(attributes) = &message.attributes else {
+        return Ok(());
+    };
+    if attributes.len() > MAX_ATTRIBUTES {
+        return Err(format!(
+            "{} attributes, but at most {MAX_ATTRIBUTES} are allowed",
+            attributes.len()
+        ));
+    }
+    for (key, value) in attributes {
+        if key.len() > MAX_ATTRIBUTE_KEY_BYTES {
+            return Err(format!(
+                "attribute key `{key}` has {} bytes, but at most {MAX_ATTRIBUTE_KEY_BYTES} are allowed",
+                key.len()
+            ));
+        }
+        if key.starts_with(RESERVED_ATTRIBUTE_KEY_PREFIX) {
+            return Err(format!(
+                "attribute key `{key}` starts with reserved prefix `{RESERVED_ATTRIBUTE_KEY_PREFIX}`"
+            ));
+        }
+        if value.len() > MAX_ATTRIBUTE_VALUE_BYTES {
+            return Err(format!(
+                "value of attribute `{key}` has {} bytes, but at most {MAX_ATTRIBUTE_VALUE_BYTES} are allowed",
+                value.len()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Length of the given Base64 encoded data once decoded.
+fn decoded_len(data: &str) -> usize {
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+    (data.len() * 3 / 4).saturating_sub(padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decoded_len, PublishRetry, PublishRetryConfig, MAX_ATTRIBUTES, MAX_DATA_BYTES};
+    use crate::{Error, PubSubClient, RawPublishedMessage, RetryBudget, RetryConfig};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use std::{collections::HashMap, time::Duration};
+
+    #[test]
+    fn test_publish_retry_backoff() {
+        let retry = RetryConfig {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let publish_retry = PublishRetry::new(
+            PublishRetryConfig::new(retry)
+                .with_jitter(false)
+                .with_budget(RetryBudget::new(2, 1.0)),
+        );
+        let transient =
+            Error::UnexpectedHttpStatusCode(StatusCode::TOO_MANY_REQUESTS, String::new());
+
+        assert_eq!(publish_retry.backoff(&Error::NoData, 0), None);
+        assert_eq!(
+            publish_retry.backoff(&transient, 0),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            publish_retry.backoff(&transient, 1),
+            Some(Duration::from_secs(2))
+        );
+
+        // Budget exhausted
+        assert_eq!(publish_retry.backoff(&transient, 0), None);
+        publish_retry.succeeded();
+        assert_eq!(
+            publish_retry.backoff(&transient, 0),
+            Some(Duration::from_secs(1))
+        );
+
+        // Retrying exhausted
+        publish_retry.succeeded();
+        assert_eq!(publish_retry.backoff(&transient, 3), None);
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        for data in ["", "a", "ab", "abc", "abcd"] {
+            assert_eq!(decoded_len(&STANDARD.encode(data)), data.len());
+        }
+    }
+
+    #[test]
+    fn test_publish_request_body() {
+        let client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        let messages = [
+            (RawPublishedMessage::new("dGVzdA==".to_string()), None),
+            (
+                RawPublishedMessage::new("dGVzdA==".to_string()).with_ordering_key("key"),
+                Some("own-key".to_string()),
+            ),
+        ];
+        let body = client
+            .publish_request_body(messages.into_iter().map(Ok))
+            .unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "messages": [
+                    { "data": "dGVzdA==", "attributes": null, "orderingKey": null },
+                    { "data": "dGVzdA==", "attributes": null, "orderingKey": "own-key" }
+                ]
+            })
+        );
+
+        // The first error is returned, not the serialization error
+        let messages = [
+            Ok((RawPublishedMessage::default(), None)),
+            Err(Error::NoData),
+        ];
+        let result = client.publish_request_body(messages);
+        assert!(matches!(result, Err(Error::NoData)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let client = PubSubClient::emulator("test", "http://localhost:8085").unwrap();
+        let validate = |messages: Vec<RawPublishedMessage<'static>>| {
+            client
+                .publish_request_body(messages.into_iter().map(|message| Ok((message, None))))
+                .map(|_| ())
+        };
+        let attributes =
+            |key: &str, value: &str| HashMap::from([(key.to_string(), value.to_string())]);
+        let valid = || RawPublishedMessage::new(STANDARD.encode("test"));
+
+        assert!(validate(vec![
+            valid(),
+            valid().with_attributes(attributes("key", "value"))
+        ])
+        .is_ok());
+
+        let too_large = RawPublishedMessage::new(STANDARD.encode(vec![0; MAX_DATA_BYTES + 1]));
+        let result = validate(vec![valid(), too_large]);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 1, .. })
+        ));
+
+        let too_many = (0..101).map(|n| (n.to_string(), n.to_string())).collect();
+        let result = validate(vec![valid().with_attributes(too_many)]);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 0, .. })
+        ));
+
+        let long_key = "k".repeat(257);
+        let result = validate(vec![valid().with_attributes(attributes(&long_key, "value"))]);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 0, .. })
+        ));
+
+        let result = validate(vec![valid().with_attributes(attributes("googkey", "value"))]);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 0, .. })
+        ));
+
+        let long_value = "v".repeat(1_025);
+        let result = validate(vec![valid().with_attributes(attributes("key", &long_value))]);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidMessage { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_additions() {
+        let client = PubSubClient::emulator_builder("test", "http://localhost:8085")
+            .with_crc32c(true)
+            .build()
+            .unwrap();
+        let max_attributes = (0..MAX_ATTRIBUTES)
+            .map(|n| (n.to_string(), n.to_string()))
+            .collect();
+        let messages = [
+            RawPublishedMessage::new(STANDARD.encode("test")),
+            RawPublishedMessage::new(STANDARD.encode("test")).with_attributes(max_attributes),
+        ];
+
+        let result = client.publish_request_body(messages.into_iter().map(|m| Ok((m, None))));
+        let Err(Error::InvalidMessage { index, reason }) = result else {
+            panic!("expected Error::InvalidMessage, but was {result:?}");
+        };
+        assert_eq!(index, 1);
+        assert!(reason.contains("after adding"));
+        assert!(reason.ends_with("`crc32c` attribute"));
     }
 }
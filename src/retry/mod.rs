@@ -0,0 +1,252 @@
+use rand::Rng;
+use reqwest::StatusCode;
+#[cfg(feature = "config")]
+use serde::Deserialize;
+use std::{sync::Mutex, time::Duration};
+
+/// Retry settings with exponential backoff.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+#[cfg_attr(feature = "config", serde(default))]
+pub struct RetryConfig {
+    /// Maximum number of retries after the initial attempt; zero disables retrying.
+    pub max_retries: u32,
+    /// Backoff before the first retry.
+    #[cfg_attr(feature = "config", serde(with = "humantime_serde"))]
+    pub initial_backoff: Duration,
+    /// Upper bound for the backoff.
+    #[cfg_attr(feature = "config", serde(with = "humantime_serde"))]
+    pub max_backoff: Duration,
+    /// Factor by which the backoff grows with each retry; less than one or not a number is
+    /// treated as one, i.e. a constant backoff.
+    pub multiplier: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Backoff before the given retry, starting with zero, or `None` if exhausted. A multiplier
+    /// less than one or not a number is treated as one.
+    pub fn backoff(&self, retry: u32) -> Option<Duration> {
+        (retry < self.max_retries).then(|| {
+            let multiplier = self.multiplier.max(1.0);
+            let backoff = self.initial_backoff.as_secs_f64() * multiplier.powi(retry as i32);
+            Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+        })
+    }
+
+    /// Like [RetryConfig::backoff], but randomly reduced by up to half to avoid retrying in
+    /// lockstep with others.
+    pub fn jittered_backoff(&self, retry: u32) -> Option<Duration> {
+        self.backoff(retry)
+            .map(|backoff| backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0)))
+    }
+}
+
+/// Client-wide retry policy, see
+/// [PubSubClientBuilder::with_request_retry](crate::PubSubClientBuilder::with_request_retry):
+/// requests failing with one of the retryable status codes or a transient error (see
+/// [Error::is_transient](crate::Error::is_transient)) are retried with exponential backoff, by
+/// default with jitter, as long as the optional deadline for the whole operation, including all
+/// attempts, is not exceeded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestRetryPolicy {
+    pub retry: RetryConfig,
+    pub jitter: bool,
+    pub retryable_status_codes: Vec<StatusCode>,
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RequestRetryPolicy {
+    fn default() -> Self {
+        Self::new(RetryConfig::default())
+    }
+}
+
+impl RequestRetryPolicy {
+    /// Retry policy with the given settings, retrying 429, 500, 502, 503 and 504 status codes.
+    pub fn new(retry: RetryConfig) -> Self {
+        Self {
+            retry,
+            jitter: true,
+            retryable_status_codes: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            deadline: None,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_retryable_status_codes(mut self, retryable_status_codes: Vec<StatusCode>) -> Self {
+        self.retryable_status_codes = retryable_status_codes;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Whether responses with the given status code are retried.
+    pub fn is_retryable(&self, status: StatusCode) -> bool {
+        self.retryable_status_codes.contains(&status)
+    }
+
+    /// Backoff before the given retry, starting with zero, or `None` if exhausted.
+    pub fn backoff(&self, retry: u32) -> Option<Duration> {
+        if self.jitter {
+            self.retry.jittered_backoff(retry)
+        } else {
+            self.retry.backoff(retry)
+        }
+    }
+}
+
+/// Budget limiting retries across requests, such that an outage does not multiply the load by
+/// the maximum number of retries: each retry withdraws a token, each successful request deposits
+/// `token_ratio` tokens, at most `max_tokens`, and retrying requires a whole token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryBudget {
+    pub max_tokens: u32,
+    pub token_ratio: f64,
+}
+
+impl RetryBudget {
+    pub fn new(max_tokens: u32, token_ratio: f64) -> Self {
+        Self {
+            max_tokens,
+            token_ratio,
+        }
+    }
+}
+
+/// The tokens of a [RetryBudget], initially the maximum.
+#[derive(Debug)]
+pub(crate) struct RetryTokens {
+    budget: RetryBudget,
+    tokens: Mutex<f64>,
+}
+
+impl RetryTokens {
+    pub(crate) fn new(budget: RetryBudget) -> Self {
+        Self {
+            budget,
+            tokens: Mutex::new(budget.max_tokens as f64),
+        }
+    }
+
+    /// Withdraw a token for a retry, if available.
+    pub(crate) fn withdraw(&self) -> bool {
+        let mut tokens = self.tokens.lock().expect("lock retry tokens");
+        let available = *tokens >= 1.0;
+        if available {
+            *tokens -= 1.0;
+        }
+        available
+    }
+
+    /// Deposit tokens for a successful request.
+    pub(crate) fn deposit(&self) {
+        let mut tokens = self.tokens.lock().expect("lock retry tokens");
+        *tokens = (*tokens + self.budget.token_ratio).min(self.budget.max_tokens as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RequestRetryPolicy, RetryBudget, RetryConfig, RetryTokens};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff() {
+        let retry_config = RetryConfig {
+            max_retries: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+        };
+        assert_eq!(retry_config.backoff(0), Some(Duration::from_secs(1)));
+        assert_eq!(retry_config.backoff(1), Some(Duration::from_secs(2)));
+        assert_eq!(retry_config.backoff(2), Some(Duration::from_secs(4)));
+        assert_eq!(retry_config.backoff(3), Some(Duration::from_secs(5)));
+        assert_eq!(retry_config.backoff(4), None);
+
+        let retry_config = RetryConfig {
+            multiplier: -1.5,
+            ..retry_config
+        };
+        assert_eq!(retry_config.backoff(1), Some(Duration::from_secs(1)));
+        let retry_config = RetryConfig {
+            multiplier: f64::NAN,
+            ..retry_config
+        };
+        assert_eq!(retry_config.backoff(1), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_jittered_backoff() {
+        let retry_config = RetryConfig {
+            max_retries: 1,
+            initial_backoff: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let backoff = retry_config.jittered_backoff(0).unwrap();
+        assert!(backoff >= Duration::from_secs(1) && backoff <= Duration::from_secs(2));
+        assert_eq!(retry_config.jittered_backoff(1), None);
+    }
+
+    #[test]
+    fn test_request_retry_policy() {
+        let request_retry = RequestRetryPolicy::new(RetryConfig {
+            max_retries: 1,
+            initial_backoff: Duration::from_secs(1),
+            ..Default::default()
+        })
+        .with_jitter(false);
+        assert_eq!(request_retry.backoff(0), Some(Duration::from_secs(1)));
+        assert_eq!(request_retry.backoff(1), None);
+        assert!(request_retry.is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!request_retry.is_retryable(StatusCode::NOT_FOUND));
+
+        let request_retry = request_retry.with_retryable_status_codes(vec![StatusCode::CONFLICT]);
+        assert!(request_retry.is_retryable(StatusCode::CONFLICT));
+        assert!(!request_retry.is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn test_retry_tokens() {
+        let tokens = RetryTokens::new(RetryBudget::new(2, 0.5));
+        assert!(tokens.withdraw());
+        assert!(tokens.withdraw());
+        assert!(!tokens.withdraw());
+
+        tokens.deposit();
+        assert!(!tokens.withdraw());
+        tokens.deposit();
+        assert!(tokens.withdraw());
+
+        // Deposits are capped at the maximum
+        (0..10).for_each(|_| tokens.deposit());
+        assert!(tokens.withdraw());
+        assert!(tokens.withdraw());
+        assert!(!tokens.withdraw());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{error::Error, pagination::Page, resource_path, PubSubClient, SchemaEncoding};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::Stream;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Schema resource as returned by the Pub/Sub service.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    /// Fully qualified name, i.e. `projects/{project}/schemas/{schema}`.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub schema_type: SchemaType,
+    #[serde(default)]
+    pub definition: String,
+    pub revision_id: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub revision_create_time: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SchemaType {
+    TypeUnspecified,
+    ProtocolBuffer,
+    Avro,
+}
+
+/// Type and definition of a schema, e.g. for creating one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDefinition {
+    #[serde(rename = "type")]
+    pub schema_type: SchemaType,
+    /// Protocol buffer definition or Avro schema in JSON format.
+    pub definition: String,
+}
+
+impl SchemaDefinition {
+    pub fn avro<T>(definition: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            schema_type: SchemaType::Avro,
+            definition: definition.into(),
+        }
+    }
+
+    pub fn protocol_buffer<T>(definition: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            schema_type: SchemaType::ProtocolBuffer,
+            definition: definition.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListSchemasResponse {
+    #[serde(default)]
+    schemas: Vec<Schema>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ListSchemasResponse {
+    type Item = Schema;
+
+    fn into_parts(self) -> (Vec<Schema>, Option<String>) {
+        (self.schemas, self.next_page_token)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SchemaRequest<'a> {
+    schema: &'a SchemaDefinition,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RollbackSchemaRequest<'a> {
+    revision_id: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidateMessageRequest {
+    name: String,
+    message: String,
+    encoding: SchemaEncoding,
+}
+
+impl PubSubClient {
+    #[tracing::instrument]
+    pub async fn create_schema(
+        &self,
+        schema_id: &str,
+        definition: SchemaDefinition,
+        timeout: Option<Duration>,
+    ) -> Result<Schema, Error> {
+        let schema = self
+            .send_authenticated(
+                Method::POST,
+                &format!("schemas?schemaId={schema_id}"),
+                Some(&definition),
+                timeout,
+            )
+            .await?
+            .json::<Schema>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(schema.name, "successfully created schema");
+        Ok(schema)
+    }
+
+    #[tracing::instrument]
+    pub async fn get_schema(
+        &self,
+        schema_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Schema, Error> {
+        self.send_authenticated::<()>(
+            Method::GET,
+            &resource_path("schemas", schema_id),
+            None,
+            timeout,
+        )
+        .await?
+        .json::<Schema>()
+        .await
+        .map_err(Error::UnexpectedHttpResponse)
+    }
+
+    /// Stream all schemas of the project including their definitions, transparently following the
+    /// pagination, optionally with the given page size.
+    pub fn list_schemas(
+        &self,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<Schema, Error>> + '_ {
+        self.paginate::<ListSchemasResponse>("schemas?view=FULL".to_string(), page_size, timeout)
+    }
+
+    /// Commit a new revision of the given schema with the given definition of the same type.
+    #[tracing::instrument]
+    pub async fn commit_schema_revision(
+        &self,
+        schema_id: &str,
+        definition: SchemaDefinition,
+        timeout: Option<Duration>,
+    ) -> Result<Schema, Error> {
+        let schema = self
+            .send_authenticated(
+                Method::POST,
+                &format!("{}:commit", resource_path("schemas", schema_id)),
+                Some(&SchemaRequest {
+                    schema: &definition,
+                }),
+                timeout,
+            )
+            .await?
+            .json::<Schema>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(
+            schema.name,
+            schema.revision_id, "successfully committed schema revision"
+        );
+        Ok(schema)
+    }
+
+    /// Roll back the given schema to the given revision by committing a copy of it as new
+    /// revision.
+    #[tracing::instrument]
+    pub async fn rollback_schema(
+        &self,
+        schema_id: &str,
+        revision_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Schema, Error> {
+        let schema = self
+            .send_authenticated(
+                Method::POST,
+                &format!("{}:rollback", resource_path("schemas", schema_id)),
+                Some(&RollbackSchemaRequest { revision_id }),
+                timeout,
+            )
+            .await?
+            .json::<Schema>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(
+            schema.name,
+            schema.revision_id, "successfully rolled back schema"
+        );
+        Ok(schema)
+    }
+
+    /// Stream all revisions of the given schema including their definitions, newest first,
+    /// transparently following the pagination, optionally with the given page size.
+    pub fn list_schema_revisions<'a>(
+        &'a self,
+        schema_id: &str,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<Schema, Error>> + 'a {
+        self.paginate::<ListSchemasResponse>(
+            format!(
+                "{}:listRevisions?view=FULL",
+                resource_path("schemas", schema_id)
+            ),
+            page_size,
+            timeout,
+        )
+    }
+
+    /// Delete the given revision of the given schema, which must not be the only one, returning
+    /// the deleted revision.
+    #[tracing::instrument]
+    pub async fn delete_schema_revision(
+        &self,
+        schema_id: &str,
+        revision_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Schema, Error> {
+        let schema = self
+            .send_authenticated::<()>(
+                Method::DELETE,
+                &format!(
+                    "{}@{revision_id}:deleteRevision",
+                    resource_path("schemas", schema_id)
+                ),
+                None,
+                timeout,
+            )
+            .await?
+            .json::<Schema>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(
+            schema.name,
+            revision_id, "successfully deleted schema revision"
+        );
+        Ok(schema)
+    }
+
+    /// Validate the given schema definition; an invalid one results in
+    /// [Error::UnexpectedHttpStatusCode] with 400 Bad Request and the reason.
+    #[tracing::instrument]
+    pub async fn validate_schema(
+        &self,
+        definition: &SchemaDefinition,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_authenticated(
+            Method::POST,
+            "schemas:validate",
+            Some(&SchemaRequest { schema: definition }),
+            timeout,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Validate the given encoded message against the given schema; an invalid message results in
+    /// [Error::UnexpectedHttpStatusCode] with 400 Bad Request and the reason.
+    #[tracing::instrument(skip(message))]
+    pub async fn validate_message(
+        &self,
+        schema_id: &str,
+        message: &[u8],
+        encoding: SchemaEncoding,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let request = ValidateMessageRequest {
+            name: self.schema_name(schema_id),
+            message: STANDARD.encode(message),
+            encoding,
+        };
+        self.send_authenticated(
+            Method::POST,
+            "schemas:validateMessage",
+            Some(&request),
+            timeout,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Delete the given schema; a non-existing schema results in
+    /// [Error::UnexpectedHttpStatusCode] with 404 Not Found.
+    #[tracing::instrument]
+    pub async fn delete_schema(
+        &self,
+        schema_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_authenticated::<()>(
+            Method::DELETE,
+            &resource_path("schemas", schema_id),
+            None,
+            timeout,
+        )
+        .await?;
+        debug!(schema_id, "successfully deleted schema");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RollbackSchemaRequest, Schema, SchemaDefinition, SchemaRequest, SchemaType,
+        ValidateMessageRequest,
+    };
+    use crate::SchemaEncoding;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize_schema_definition() {
+        let definition = SchemaDefinition::avro(r#"{"type":"string"}"#);
+        assert_eq!(
+            serde_json::to_value(definition).unwrap(),
+            json!({ "type": "AVRO", "definition": r#"{"type":"string"}"# })
+        );
+    }
+
+    #[test]
+    fn test_serialize_validate_requests() {
+        let definition = SchemaDefinition::protocol_buffer("syntax = \"proto3\";");
+        let request = SchemaRequest {
+            schema: &definition,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "schema": { "type": "PROTOCOL_BUFFER", "definition": "syntax = \"proto3\";" } })
+        );
+
+        let request = ValidateMessageRequest {
+            name: "projects/test/schemas/test".to_string(),
+            message: "e30=".to_string(),
+            encoding: SchemaEncoding::Json,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "name": "projects/test/schemas/test", "message": "e30=", "encoding": "JSON" })
+        );
+    }
+
+    #[test]
+    fn test_serialize_rollback_schema_request() {
+        let request = RollbackSchemaRequest { revision_id: "a" };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "revisionId": "a" })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_schema() {
+        let schema = json!({
+            "name": "projects/test/schemas/test",
+            "type": "PROTOCOL_BUFFER",
+            "definition": "syntax = \"proto3\";",
+            "revisionId": "a"
+        });
+        let schema = serde_json::from_value::<Schema>(schema).unwrap();
+        assert_eq!(schema.name, "projects/test/schemas/test");
+        assert_eq!(schema.schema_type, SchemaType::ProtocolBuffer);
+        assert_eq!(schema.definition, "syntax = \"proto3\";");
+        assert_eq!(schema.revision_id, Some("a".to_string()));
+        assert_eq!(schema.revision_create_time, None);
+    }
+}
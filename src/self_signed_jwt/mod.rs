@@ -0,0 +1,74 @@
+use crate::{
+    auth::{claims_window, Token},
+    credentials::{Credentials, SigningKey},
+    error::Error,
+};
+use serde_json::json;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Audience of self-signed JWTs, which must be the Pub/Sub service, even for regional endpoints.
+const AUDIENCE: &str = "https://pubsub.googleapis.com/";
+
+/// Signs JWTs with the private key of a service account which are directly used as access tokens,
+/// i.e. without a round trip to the token endpoint.
+pub(crate) struct SelfSignedJwt {
+    signing_key: SigningKey,
+    clock_skew: Duration,
+}
+
+impl SelfSignedJwt {
+    pub(crate) fn new(credentials: &Credentials, clock_skew: Duration) -> Result<Self, Error> {
+        let signing_key = credentials.signing_key()?;
+        Ok(Self {
+            signing_key,
+            clock_skew,
+        })
+    }
+
+    /// Returns a JWT signed at `now`, valid for `token_lifetime` from its backdated "issued at"
+    /// claim on.
+    pub(crate) fn token(
+        &self,
+        now: OffsetDateTime,
+        token_lifetime: Duration,
+    ) -> Result<Token, Error> {
+        let (iat, exp) = claims_window(now, self.clock_skew, token_lifetime);
+        let email = self.signing_key.email();
+        let jwt = self.signing_key.sign(&json!({
+            "iss": email,
+            "sub": email,
+            "aud": AUDIENCE,
+            "iat": iat,
+            "exp": exp,
+        }))?;
+
+        let expires_in = (exp - now.unix_timestamp()).max(0) as u64;
+        Ok(Token::new(jwt, expires_in))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SelfSignedJwt, AUDIENCE};
+    use crate::credentials::tests::{test_credentials, verify};
+    use std::time::Duration;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_token() {
+        let self_signed_jwt =
+            SelfSignedJwt::new(&test_credentials(), Duration::from_secs(60)).unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_000_000).unwrap();
+        let token = self_signed_jwt
+            .token(now, Duration::from_secs(3600))
+            .unwrap();
+
+        let (header, claims) = verify(token.access_token());
+        assert_eq!(header["kid"], "key-id");
+        assert_eq!(claims["sub"], "test@test.iam.gserviceaccount.com");
+        assert_eq!(claims["aud"], AUDIENCE);
+        assert_eq!(claims["iat"], 999_940);
+        assert_eq!(claims["exp"], 1_003_540);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{error::Error, pagination::Page, resource_path, PubSubClient};
+use futures::Stream;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Snapshot resource as returned by the Pub/Sub service, capturing the acknowledgment state of a
+/// subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// Fully qualified name, i.e. `projects/{project}/snapshots/{snapshot}`.
+    pub name: String,
+    /// Fully qualified name of the topic of the subscription the snapshot was created from.
+    pub topic: String,
+    /// When the snapshot expires; at most seven days after creation.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expire_time: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// Configuration for creating a snapshot.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+impl SnapshotConfig {
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateSnapshotRequest<'a> {
+    subscription: String,
+    #[serde(flatten)]
+    config: &'a SnapshotConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListSnapshotsResponse {
+    #[serde(default)]
+    snapshots: Vec<Snapshot>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ListSnapshotsResponse {
+    type Item = Snapshot;
+
+    fn into_parts(self) -> (Vec<Snapshot>, Option<String>) {
+        (self.snapshots, self.next_page_token)
+    }
+}
+
+impl PubSubClient {
+    /// Create a snapshot of the given subscription, i.e. of its unacknowledged messages and of
+    /// the messages published afterwards.
+    #[tracing::instrument]
+    pub async fn create_snapshot(
+        &self,
+        snapshot_id: &str,
+        subscription_id: &str,
+        config: SnapshotConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Snapshot, Error> {
+        let request = CreateSnapshotRequest {
+            subscription: self.subscription_name(subscription_id),
+            config: &config,
+        };
+        let snapshot = self
+            .send_authenticated(
+                Method::PUT,
+                &resource_path("snapshots", snapshot_id),
+                Some(&request),
+                timeout,
+            )
+            .await?
+            .json::<Snapshot>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(snapshot.name, "successfully created snapshot");
+        Ok(snapshot)
+    }
+
+    /// Stream all snapshots of the project, transparently following the pagination, optionally
+    /// with the given page size.
+    pub fn list_snapshots(
+        &self,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<Snapshot, Error>> + '_ {
+        self.paginate::<ListSnapshotsResponse>("snapshots".to_string(), page_size, timeout)
+    }
+
+    /// Delete the given snapshot; a non-existing snapshot results in
+    /// [Error::UnexpectedHttpStatusCode] with 404 Not Found.
+    #[tracing::instrument]
+    pub async fn delete_snapshot(
+        &self,
+        snapshot_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_authenticated::<()>(
+            Method::DELETE,
+            &resource_path("snapshots", snapshot_id),
+            None,
+            timeout,
+        )
+        .await?;
+        debug!(snapshot_id, "successfully deleted snapshot");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreateSnapshotRequest, Snapshot, SnapshotConfig};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_serialize_create_snapshot_request() {
+        let config = SnapshotConfig::default()
+            .with_labels(HashMap::from([("env".to_string(), "test".to_string())]));
+        let request = CreateSnapshotRequest {
+            subscription: "projects/test/subscriptions/test".to_string(),
+            config: &config,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "subscription": "projects/test/subscriptions/test", "labels": { "env": "test" } })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_snapshot() {
+        let snapshot = json!({
+            "name": "projects/test/snapshots/test",
+            "topic": "projects/test/topics/test",
+            "expireTime": "2023-01-08T12:00:00Z"
+        });
+        let snapshot = serde_json::from_value::<Snapshot>(snapshot).unwrap();
+        assert_eq!(snapshot.name, "projects/test/snapshots/test");
+        assert_eq!(snapshot.topic, "projects/test/topics/test");
+        assert_eq!(
+            snapshot.expire_time,
+            Some(OffsetDateTime::from_unix_timestamp(1_673_179_200).unwrap())
+        );
+        assert!(snapshot.labels.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{error::Error, publisher::publish_request_body, PubSubClient};
+use bytes::Bytes;
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+};
+use tracing::{debug, warn};
+
+/// Disk spool for publish requests which failed because the Pub/Sub service could not be reached.
+///
+/// Spooled publish requests are stored as JSON lines in the order they were made. While the spool
+/// is not empty, publishing via a client with a spool is serialized: before each publish request
+/// the spool gets flushed, such that messages are delivered in order once the Pub/Sub service can
+/// be reached again. While the spool is empty, publish requests are sent concurrently.
+#[derive(Debug)]
+pub(crate) struct Spool {
+    path: PathBuf,
+    max_bytes: u64,
+    lock: Mutex<()>,
+    /// Whether the spool might contain entries; initially unknown, hence `true`.
+    pending: AtomicBool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpooledPublish {
+    topic_id: String,
+    messages: Vec<SpooledMessage>,
+}
+
+/// Body of a publish request, see [publish_request_body].
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpooledRequest {
+    messages: Vec<SpooledMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpooledMessage {
+    data: Option<String>,
+    attributes: Option<HashMap<String, String>>,
+    ordering_key: Option<String>,
+}
+
+impl Spool {
+    pub(crate) fn new(path: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            path,
+            max_bytes,
+            lock: Mutex::new(()),
+            pending: AtomicBool::new(true),
+        }
+    }
+
+    pub(crate) async fn publish(
+        &self,
+        client: &PubSubClient,
+        topic_id: &str,
+        body: Bytes,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error> {
+        if !self.pending.load(Ordering::Acquire) {
+            let result = client
+                .send_publish_request(topic_id, body.clone(), timeout)
+                .await;
+            return match result {
+                Err(Error::HttpServiceCommunication(source)) => {
+                    let _lock = self.lock.lock().await;
+                    self.spool(topic_id, &body, source).await
+                }
+                other => other,
+            };
+        }
+
+        let _lock = self.lock.lock().await;
+        let result = match self.flush_locked(client, timeout).await {
+            Ok(_) => {
+                client
+                    .send_publish_request(topic_id, body.clone(), timeout)
+                    .await
+            }
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Err(Error::HttpServiceCommunication(source)) => {
+                self.spool(topic_id, &body, source).await
+            }
+            other => other,
+        }
+    }
+
+    pub(crate) async fn flush(
+        &self,
+        client: &PubSubClient,
+        timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        if !self.pending.load(Ordering::Acquire) {
+            return Ok(0);
+        }
+        let _lock = self.lock.lock().await;
+        self.flush_locked(client, timeout).await
+    }
+
+    /// Append the messages of the given publish request body to the spool; must be called with the
+    /// lock held.
+    async fn spool(
+        &self,
+        topic_id: &str,
+        body: &[u8],
+        source: reqwest::Error,
+    ) -> Result<Vec<String>, Error> {
+        self.append(topic_id, body).await.map_err(Error::Spool)?;
+        self.pending.store(true, Ordering::Release);
+        Err(Error::Spooled(source))
+    }
+
+    async fn flush_locked(
+        &self,
+        client: &PubSubClient,
+        timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        let entries = self.load().await.map_err(Error::Spool)?;
+        if entries.is_empty() {
+            self.pending.store(false, Ordering::Release);
+            return Ok(0);
+        }
+        debug!(path = ?self.path, entries = entries.len(), "flushing spool");
+
+        let mut flushed = 0;
+        let mut entries = entries.into_iter();
+        let mut result = Ok(());
+        for entry in entries.by_ref() {
+            let published = match publish_request_body(&entry.messages) {
+                Ok(body) => {
+                    client
+                        .send_publish_request(&entry.topic_id, body, timeout)
+                        .await
+                }
+                Err(error) => Err(error),
+            };
+            match published {
+                Ok(_) => flushed += entry.messages.len(),
+
+                // Client errors will not go away by retrying, hence drop the entry
+                Err(Error::UnexpectedHttpStatusCode(status, message))
+                    if status.is_client_error() =>
+                {
+                    warn!(
+                        topic_id = entry.topic_id,
+                        %status,
+                        message,
+                        "dropping spooled publish request"
+                    );
+                }
+
+                Err(error) => {
+                    result = Err((entry, error));
+                    break;
+                }
+            }
+        }
+
+        match result {
+            Ok(()) => {
+                self.store(Vec::new()).await.map_err(Error::Spool)?;
+                self.pending.store(false, Ordering::Release);
+                Ok(flushed)
+            }
+            Err((entry, error)) => {
+                let remaining = std::iter::once(entry).chain(entries).collect();
+                self.store(remaining).await.map_err(Error::Spool)?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn append(&self, topic_id: &str, body: &[u8]) -> io::Result<()> {
+        let SpooledRequest { messages } = serde_json::from_slice(body)?;
+        let entry = SpooledPublish {
+            topic_id: topic_id.to_string(),
+            messages,
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        let size = match fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        if size + line.len() as u64 > self.max_bytes {
+            return Err(io::Error::other(format!(
+                "spool at `{}` would exceed its maximum size of {} bytes",
+                self.path.display(),
+                self.max_bytes
+            )));
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
+        debug!(path = ?self.path, topic_id, "spooled publish request");
+        Ok(())
+    }
+
+    async fn load(&self) -> io::Result<Vec<SpooledPublish>> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    async fn store(&self, entries: Vec<SpooledPublish>) -> io::Result<()> {
+        let mut content = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut content, &entry)?;
+            content.push(b'\n');
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(&content).await?;
+        file.sync_data().await?;
+        fs::rename(tmp_path, &self.path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spool;
+    use crate::{publisher::publish_request_body, RawPublishedMessage};
+    use std::{env, fs, process};
+
+    #[tokio::test]
+    async fn test_append_load_store() {
+        let path = env::temp_dir().join(format!("pub-sub-client-spool-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let spool = Spool::new(path.clone(), 256);
+
+        let body = publish_request_body([
+            RawPublishedMessage::new("Zm9v".to_string()).with_ordering_key("key")
+        ])
+        .unwrap();
+        assert!(spool.append("topic-1", &body).await.is_ok());
+        let body = publish_request_body([RawPublishedMessage::new("YmFy".to_string())]).unwrap();
+        assert!(spool.append("topic-2", &body).await.is_ok());
+
+        let entries = spool.load().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].topic_id, "topic-1");
+        assert_eq!(entries[0].messages[0].ordering_key.as_deref(), Some("key"));
+        assert_eq!(entries[1].topic_id, "topic-2");
+        assert_eq!(entries[1].messages[0].data.as_deref(), Some("YmFy"));
+
+        // Exceeding the maximum size fails
+        let body = publish_request_body([RawPublishedMessage::new("x".repeat(256))]).unwrap();
+        assert!(spool.append("topic-3", &body).await.is_err());
+        assert_eq!(spool.load().await.unwrap().len(), 2);
+
+        let entries = spool.load().await.unwrap().into_iter().skip(1).collect();
+        assert!(spool.store(entries).await.is_ok());
+        let entries = spool.load().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].topic_id, "topic-2");
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    checkpoint::CheckpointStore,
+    dedup::{DedupConfig, Deduplicator},
+    error::Error,
+    retry::RetryConfig,
+    subscribe::unless_cancelled,
+    NonMatching, PubSubClient, PulledMessage, ReceivedMessage,
+};
+use futures::{stream, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_MESSAGES: u32 = 100;
+
+/// Options for [PubSubClient::stream].
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// Maximum number of messages per pull request.
+    pub max_messages: u32,
+    /// Timeout for pull requests.
+    pub pull_timeout: Option<Duration>,
+    /// Retry settings for pulling.
+    pub retry: RetryConfig,
+    /// Token for graceful shutdown: once cancelled, pulling is stopped and the stream ends after
+    /// the messages already pulled.
+    pub cancellation_token: Option<CancellationToken>,
+    /// Deduplication of messages by their message ID: messages already acknowledged via
+    /// [ReceivedMessage::ack] are acknowledged again and not emitted.
+    pub dedup: Option<DedupConfig>,
+    /// Store for the IDs of processed messages: messages already processed are acknowledged and
+    /// not emitted and [ReceivedMessage::ack] marks messages as processed before acknowledging
+    /// them.
+    pub checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            pull_timeout: None,
+            retry: RetryConfig::default(),
+            cancellation_token: None,
+            dedup: None,
+            checkpoint_store: None,
+        }
+    }
+}
+
+impl StreamOptions {
+    pub fn with_max_messages(mut self, max_messages: u32) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    pub fn with_pull_timeout(mut self, pull_timeout: Duration) -> Self {
+        self.pull_timeout = Some(pull_timeout);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn with_dedup(mut self, dedup: DedupConfig) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    pub fn with_checkpoint_store(mut self, checkpoint_store: Arc<dyn CheckpointStore>) -> Self {
+        self.checkpoint_store = Some(checkpoint_store);
+        self
+    }
+}
+
+/// Where to replay messages from, see [PubSubClient::replay].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayPoint {
+    /// Replay all retained messages published at or after the given time.
+    Time(OffsetDateTime),
+    /// Replay all messages unacknowledged when the snapshot with the given ID has been created.
+    Snapshot(String),
+}
+
+impl PubSubClient {
+    /// Seek the given subscription to the given [ReplayPoint] and then stream the replayed
+    /// messages like [PubSubClient::stream]. The timeout is used for the seek request.
+    pub async fn replay<'a, M>(
+        &'a self,
+        subscription_id: &str,
+        from: ReplayPoint,
+        options: StreamOptions,
+        timeout: Option<Duration>,
+    ) -> Result<impl Stream<Item = Result<ReceivedMessage<'a, M>, Error>> + 'a, Error>
+    where
+        M: DeserializeOwned + Debug + 'a,
+    {
+        match from {
+            ReplayPoint::Time(time) => self.seek_to_time(subscription_id, time, timeout).await?,
+            ReplayPoint::Snapshot(snapshot_id) => {
+                self.seek_to_snapshot(subscription_id, &snapshot_id, timeout)
+                    .await?
+            }
+        }
+        debug!(subscription_id, "sought subscription, replaying");
+
+        Ok(self.stream(subscription_id, options))
+    }
+
+    /// Continuously pull messages from the given subscription as a stream of [ReceivedMessage]s,
+    /// which can be acknowledged or negatively acknowledged by themselves. The next pull request
+    /// is only sent once all messages of the previous one have been consumed, hence a slow
+    /// consumer is not overwhelmed, but should keep the acknowledge deadline in mind. Empty pull
+    /// responses are skipped and failed pull requests are retried according to the given options;
+    /// if retrying is exhausted, the error is emitted and the stream ends. Drop the stream or
+    /// cancel the cancellation token of the options to stop pulling.
+    pub fn stream<'a, M>(
+        &'a self,
+        subscription_id: &str,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<ReceivedMessage<'a, M>, Error>> + 'a
+    where
+        M: DeserializeOwned + Debug + 'a,
+    {
+        self.stream_filtered(subscription_id, options, |_| true, NonMatching::default())
+    }
+
+    /// Like [PubSubClient::stream], but only emits the messages matching the given predicate,
+    /// whereas the others are acknowledged or negatively acknowledged, see
+    /// [PubSubClient::pull_filtered].
+    pub fn stream_filtered<'a, M, P>(
+        &'a self,
+        subscription_id: &str,
+        options: StreamOptions,
+        predicate: P,
+        non_matching: NonMatching,
+    ) -> impl Stream<Item = Result<ReceivedMessage<'a, M>, Error>> + 'a
+    where
+        M: DeserializeOwned + Debug + 'a,
+        P: Fn(&PulledMessage<M>) -> bool + Clone + 'a,
+    {
+        let subscription_id = subscription_id.to_string();
+        let deduplicator = options
+            .dedup
+            .map(|dedup| Arc::new(Mutex::new(Deduplicator::new(dedup))));
+        stream::unfold(Some((subscription_id, deduplicator)), move |state| {
+            let options = options.clone();
+            let predicate = predicate.clone();
+            async move {
+                let (subscription_id, deduplicator) = state?;
+                let pull = self.pull_retrying::<M>(
+                    &subscription_id,
+                    options.max_messages,
+                    options.pull_timeout,
+                    &options.retry,
+                );
+                match unless_cancelled(options.cancellation_token.as_ref(), pull).await? {
+                    Ok(messages) => {
+                        let mut messages = self
+                            .retain_matching(&subscription_id, messages, predicate, non_matching)
+                            .await;
+                        if let Some(checkpoint_store) = &options.checkpoint_store {
+                            messages = self
+                                .drop_processed(&subscription_id, messages, &**checkpoint_store)
+                                .await;
+                        }
+                        if let Some(deduplicator) = &deduplicator {
+                            messages = self
+                                .drop_duplicates(&subscription_id, messages, deduplicator)
+                                .await;
+                        }
+                        let messages = messages
+                            .into_iter()
+                            .map(|message| {
+                                let mut message =
+                                    ReceivedMessage::new(self, subscription_id.clone(), message);
+                                if let Some(deduplicator) = &deduplicator {
+                                    message = message.with_deduplicator(deduplicator.clone());
+                                }
+                                if let Some(checkpoint_store) = &options.checkpoint_store {
+                                    message =
+                                        message.with_checkpoint_store(checkpoint_store.clone());
+                                }
+                                Ok(message)
+                            })
+                            .collect::<Vec<_>>();
+                        Some((messages, Some((subscription_id, deduplicator))))
+                    }
+                    Err(error) => Some((vec![Err(error)], None)),
+                }
+            }
+        })
+        .flat_map(stream::iter)
+    }
+
+    /// Like [PubSubClient::stream], but for all of the given subscriptions at once, merging the
+    /// messages into a single stream. Each [ReceivedMessage] is tagged with the subscription it
+    /// has been pulled from, see [ReceivedMessage::subscription_id]. The stream ends once the
+    /// streams for all subscriptions have ended.
+    pub fn stream_many<'a, M>(
+        &'a self,
+        subscription_ids: &[&str],
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<ReceivedMessage<'a, M>, Error>> + 'a
+    where
+        M: DeserializeOwned + Debug + 'a,
+    {
+        let streams = subscription_ids
+            .iter()
+            .map(|subscription_id| Box::pin(self.stream(subscription_id, options.clone())));
+        stream::select_all(streams)
+    }
+
+    /// Pull messages until a pull response is not empty, retrying failed pull requests according
+    /// to the given retry settings.
+    pub(crate) async fn pull_retrying<M>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        pull_timeout: Option<Duration>,
+        retry: &RetryConfig,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+    {
+        let mut retries = 0;
+        loop {
+            match self
+                .pull::<M>(subscription_id, max_messages, pull_timeout)
+                .await
+            {
+                Ok(messages) if messages.is_empty() => {
+                    debug!(subscription_id, "pulled no messages");
+                    retries = 0;
+                }
+                Ok(messages) => return Ok(messages),
+                Err(error) => match retry.backoff(retries) {
+                    Some(backoff) => {
+                        warn!(%error, ?backoff, subscription_id, "pulling failed, retrying");
+                        tokio::time::sleep(backoff).await;
+                        retries += 1;
+                    }
+                    None => return Err(error),
+                },
+            }
+        }
+    }
+}
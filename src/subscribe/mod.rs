@@ -0,0 +1,582 @@
+use crate::{
+    checkpoint::CheckpointStore, consumer::FlowControlConfig, error::Error, retry::RetryConfig,
+    PubSubClient, PulledMessage, ReceivedMessage,
+};
+use futures::future;
+use serde::de::DeserializeOwned;
+#[cfg(feature = "config")]
+use serde::Deserialize;
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    sync::Arc,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+const DEFAULT_WORKERS: usize = 1;
+
+/// Settings for a [Subscriber]. With the `config` feature these can be deserialized like a
+/// [ConsumerConfig](crate::ConsumerConfig).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+pub struct SubscriberConfig {
+    pub subscription_id: String,
+    /// Number of workers concurrently pulling from the subscription.
+    #[cfg_attr(feature = "config", serde(default = "default_workers"))]
+    pub workers: usize,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub flow_control: FlowControlConfig,
+    /// Retry settings for pulling.
+    #[cfg_attr(feature = "config", serde(default))]
+    pub retry: RetryConfig,
+}
+
+impl SubscriberConfig {
+    pub fn new<T>(subscription_id: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            subscription_id: subscription_id.into(),
+            workers: DEFAULT_WORKERS,
+            flow_control: FlowControlConfig::default(),
+            retry: RetryConfig::default(),
+        }
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_flow_control(mut self, flow_control: FlowControlConfig) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[cfg(feature = "config")]
+fn default_workers() -> usize {
+    DEFAULT_WORKERS
+}
+
+/// Runs a number of workers concurrently pulling from the same subscription and handing the
+/// received messages to a shared handler according to its [SubscriberConfig].
+#[derive(Debug)]
+pub struct Subscriber<'a> {
+    client: &'a PubSubClient,
+    config: SubscriberConfig,
+    cancellation_token: Option<CancellationToken>,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl<'a> Subscriber<'a> {
+    pub fn from_config(client: &'a PubSubClient, config: SubscriberConfig) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::Initialization {
+            reason: "invalid subscriber config".to_string(),
+            source: reason.into(),
+        };
+        if config.subscription_id.is_empty() {
+            return Err(invalid("subscription_id must not be empty"));
+        }
+        if config.workers == 0 {
+            return Err(invalid("workers must be positive"));
+        }
+        if config.flow_control.max_messages == 0 {
+            return Err(invalid("flow_control.max_messages must be positive"));
+        }
+
+        Ok(Self {
+            client,
+            config,
+            cancellation_token: None,
+            checkpoint_store: None,
+        })
+    }
+
+    /// Gracefully shut down once the given token is cancelled: pulling is stopped, messages
+    /// already pulled are handled and then [Subscriber::run] resolves.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Skip and acknowledge messages already processed according to the given [CheckpointStore];
+    /// [ReceivedMessage::ack] marks messages as processed before acknowledging them.
+    pub fn with_checkpoint_store(mut self, checkpoint_store: Arc<dyn CheckpointStore>) -> Self {
+        self.checkpoint_store = Some(checkpoint_store);
+        self
+    }
+
+    pub fn config(&self) -> &SubscriberConfig {
+        &self.config
+    }
+
+    /// Run the configured number of workers, each pulling messages and handing them one after the
+    /// other to the given handler, which is responsible for acknowledging them. Hence the number
+    /// of workers also is the maximum number of messages handled concurrently.
+    ///
+    /// If pulling fails for one worker even after retrying, all other workers are stopped like
+    /// when shutting down gracefully, i.e. they finish handling the messages already pulled, and
+    /// once all have stopped, the first error is returned. If a cancellation token is set, all
+    /// workers stop once it is cancelled.
+    pub async fn run<M, H, F>(&self, handler: H) -> Result<(), Error>
+    where
+        M: DeserializeOwned + Debug + 'a,
+        H: Fn(ReceivedMessage<'a, M>) -> F,
+        F: Future<Output = ()>,
+    {
+        let cancellation_token = self
+            .cancellation_token
+            .as_ref()
+            .map_or_else(CancellationToken::new, CancellationToken::child_token);
+        let cancellation_token = &cancellation_token;
+        let handler = &handler;
+
+        let workers = (0..self.config.workers).map(|worker| async move {
+            let result = self.work(worker, handler, cancellation_token).await;
+            if result.is_err() {
+                cancellation_token.cancel();
+            }
+            result
+        });
+        future::join_all(workers)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map(|_| ())
+    }
+
+    async fn work<M, H, F>(
+        &self,
+        worker: usize,
+        handler: &H,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error>
+    where
+        M: DeserializeOwned + Debug + 'a,
+        H: Fn(ReceivedMessage<'a, M>) -> F,
+        F: Future<Output = ()>,
+    {
+        let SubscriberConfig {
+            subscription_id,
+            flow_control,
+            retry,
+            ..
+        } = &self.config;
+
+        loop {
+            let pull = self.client.pull_retrying::<M>(
+                subscription_id,
+                flow_control.max_messages,
+                flow_control.pull_timeout,
+                retry,
+            );
+            let Some(messages) = unless_cancelled(Some(cancellation_token), pull).await else {
+                debug!(worker, subscription_id, "cancelled, stopping worker");
+                return Ok(());
+            };
+            let mut messages = messages?;
+            if let Some(checkpoint_store) = &self.checkpoint_store {
+                messages = self
+                    .client
+                    .drop_processed(subscription_id, messages, &**checkpoint_store)
+                    .await;
+            }
+            debug!(
+                worker,
+                subscription_id,
+                count = messages.len(),
+                "pulled messages"
+            );
+
+            for message in messages {
+                let mut message =
+                    ReceivedMessage::new(self.client, subscription_id.clone(), message);
+                if let Some(checkpoint_store) = &self.checkpoint_store {
+                    message = message.with_checkpoint_store(checkpoint_store.clone());
+                }
+                handler(message).await;
+            }
+        }
+    }
+}
+
+/// Handles pulled messages for [PubSubClient::subscribe]. Implemented for all functions taking
+/// a [PulledMessage] and returning a future of a `Result`.
+pub trait MessageHandler<M>
+where
+    M: DeserializeOwned,
+{
+    type Error: Display;
+
+    fn handle(&self, message: PulledMessage<M>) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+impl<M, H, F, E> MessageHandler<M> for H
+where
+    M: DeserializeOwned,
+    H: Fn(PulledMessage<M>) -> F,
+    F: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    type Error = E;
+
+    fn handle(&self, message: PulledMessage<M>) -> impl Future<Output = Result<(), Self::Error>> {
+        self(message)
+    }
+}
+
+/// Settings for [PubSubClient::subscribe].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+pub struct SubscribeConfig {
+    /// Number of workers concurrently pulling from the subscription.
+    #[cfg_attr(feature = "config", serde(default = "default_workers"))]
+    pub workers: usize,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub flow_control: FlowControlConfig,
+    /// Retry settings for pulling.
+    #[cfg_attr(feature = "config", serde(default))]
+    pub retry: RetryConfig,
+    /// ACK deadline set for messages the handler has failed for; zero makes them available for
+    /// redelivery immediately.
+    #[cfg_attr(feature = "config", serde(default))]
+    pub nack_ack_deadline_seconds: u32,
+    /// Token for graceful shutdown, see [Subscriber::with_cancellation_token].
+    #[cfg_attr(feature = "config", serde(skip))]
+    pub cancellation_token: Option<CancellationToken>,
+    /// Store for the IDs of processed messages, see [Subscriber::with_checkpoint_store]; handled
+    /// messages are marked as processed before they are acknowledged.
+    #[cfg_attr(feature = "config", serde(skip))]
+    pub checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl Default for SubscribeConfig {
+    fn default() -> Self {
+        Self {
+            workers: DEFAULT_WORKERS,
+            flow_control: FlowControlConfig::default(),
+            retry: RetryConfig::default(),
+            nack_ack_deadline_seconds: 0,
+            cancellation_token: None,
+            checkpoint_store: None,
+        }
+    }
+}
+
+impl SubscribeConfig {
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_flow_control(mut self, flow_control: FlowControlConfig) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_nack_ack_deadline_seconds(mut self, nack_ack_deadline_seconds: u32) -> Self {
+        self.nack_ack_deadline_seconds = nack_ack_deadline_seconds;
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn with_checkpoint_store(mut self, checkpoint_store: Arc<dyn CheckpointStore>) -> Self {
+        self.checkpoint_store = Some(checkpoint_store);
+        self
+    }
+}
+
+/// Summary of [PubSubClient::pull_and_handle].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandleSummary {
+    /// Number of messages successfully handled and hence acknowledged.
+    pub handled: usize,
+    /// Number of messages the handler has failed for and hence negatively acknowledged.
+    pub failed: usize,
+    /// Number of messages which could not be deserialized and hence have been negatively
+    /// acknowledged without being handed to the handler.
+    pub deserialize_errors: usize,
+}
+
+impl PubSubClient {
+    /// Pull messages once and hand them to the given handler concurrently; messages are
+    /// acknowledged if the handler succeeds, else they are negatively acknowledged, like the ones
+    /// which could not be deserialized. Useful for batch consumers, e.g. run by cron.
+    pub async fn pull_and_handle<M, H>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        handler: H,
+    ) -> Result<HandleSummary, Error>
+    where
+        M: DeserializeOwned + Debug,
+        H: MessageHandler<M>,
+    {
+        let messages = self.pull::<M>(subscription_id, max_messages, None).await?;
+        let (summary, ack_ids, nack_ids) = handle_all(messages, &handler).await;
+
+        if !ack_ids.is_empty() {
+            let ack_ids = ack_ids.iter().map(|ack_id| ack_id.as_str()).collect();
+            self.acknowledge(subscription_id, ack_ids, None).await?;
+        }
+        if !nack_ids.is_empty() {
+            let nack_ids = nack_ids.iter().map(|ack_id| ack_id.as_str()).collect();
+            self.nack(subscription_id, nack_ids, None).await?;
+        }
+
+        debug!(subscription_id, ?summary, "pulled and handled messages");
+        Ok(summary)
+    }
+
+    /// Pull messages from the given subscription and hand them to the given handler until pulling
+    /// fails even after retrying. Messages are acknowledged if the handler succeeds, else they are
+    /// negatively acknowledged, i.e. their ACK deadline is modified according to the given config.
+    pub async fn subscribe<M, H>(
+        &self,
+        subscription_id: &str,
+        handler: H,
+        config: SubscribeConfig,
+    ) -> Result<(), Error>
+    where
+        M: DeserializeOwned + Debug,
+        H: MessageHandler<M>,
+    {
+        let SubscribeConfig {
+            workers,
+            flow_control,
+            retry,
+            nack_ack_deadline_seconds,
+            cancellation_token,
+            checkpoint_store,
+        } = config;
+        let config = SubscriberConfig {
+            subscription_id: subscription_id.to_string(),
+            workers,
+            flow_control,
+            retry,
+        };
+        let handler = &handler;
+
+        let mut subscriber = Subscriber::from_config(self, config)?;
+        if let Some(cancellation_token) = cancellation_token {
+            subscriber = subscriber.with_cancellation_token(cancellation_token);
+        }
+        if let Some(checkpoint_store) = &checkpoint_store {
+            subscriber = subscriber.with_checkpoint_store(checkpoint_store.clone());
+        }
+        let checkpoint_store = checkpoint_store.as_deref();
+        subscriber
+            .run(|message: ReceivedMessage<M>| async move {
+                let subscription_id = message.subscription_id().to_string();
+                let message = message.into_message();
+                let ack_id = message.ack_id.clone();
+                let id = message.id.clone();
+
+                match handler.handle(message).await {
+                    Ok(()) => {
+                        if let Some(checkpoint_store) = checkpoint_store {
+                            if let Err(error) = checkpoint_store.mark_processed(&id).await {
+                                warn!(%error, id, "cannot mark message as processed");
+                                return;
+                            }
+                        }
+                        if let Err(error) = self
+                            .acknowledge(&subscription_id, vec![&ack_id], None)
+                            .await
+                        {
+                            warn!(%error, id, "cannot acknowledge message");
+                        }
+                    }
+                    Err(error) => {
+                        warn!(%error, id, "handling message failed");
+                        if let Err(error) = self
+                            .modify_ack_deadline(
+                                &subscription_id,
+                                vec![&ack_id],
+                                nack_ack_deadline_seconds,
+                                None,
+                            )
+                            .await
+                        {
+                            warn!(%error, id, "cannot negatively acknowledge message");
+                        }
+                    }
+                }
+            })
+            .await
+    }
+}
+
+/// Hand the given messages to the given handler concurrently, returning the summary and the ACK
+/// IDs to be acknowledged and negatively acknowledged.
+async fn handle_all<M, H>(
+    messages: Vec<PulledMessage<M>>,
+    handler: &H,
+) -> (HandleSummary, Vec<String>, Vec<String>)
+where
+    M: DeserializeOwned,
+    H: MessageHandler<M>,
+{
+    let outcomes = messages.into_iter().map(|message| async move {
+        let ack_id = message.ack_id.clone();
+        let id = message.id.clone();
+        if let Err(error) = &message.message {
+            warn!(%error, id, "cannot deserialize message");
+            return (ack_id, None);
+        }
+        let result = handler.handle(message).await;
+        if let Err(error) = &result {
+            warn!(%error, id, "handling message failed");
+        }
+        (ack_id, Some(result.is_ok()))
+    });
+
+    let mut summary = HandleSummary::default();
+    let mut ack_ids = vec![];
+    let mut nack_ids = vec![];
+    for (ack_id, outcome) in future::join_all(outcomes).await {
+        match outcome {
+            Some(true) => {
+                summary.handled += 1;
+                ack_ids.push(ack_id);
+            }
+            Some(false) => {
+                summary.failed += 1;
+                nack_ids.push(ack_id);
+            }
+            None => {
+                summary.deserialize_errors += 1;
+                nack_ids.push(ack_id);
+            }
+        }
+    }
+    (summary, ack_ids, nack_ids)
+}
+
+/// Await the given future unless the given token, if any, is cancelled before it completes.
+pub(crate) async fn unless_cancelled<F>(
+    cancellation_token: Option<&CancellationToken>,
+    future: F,
+) -> Option<F::Output>
+where
+    F: Future,
+{
+    match cancellation_token {
+        Some(cancellation_token) => cancellation_token.run_until_cancelled(future).await,
+        None => Some(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_all, unless_cancelled, HandleSummary};
+    #[cfg(feature = "config")]
+    use super::{SubscribeConfig, SubscriberConfig};
+    use crate::{Error, PulledMessage};
+    use futures::executor::block_on;
+    #[cfg(feature = "config")]
+    use serde_json::json;
+    use std::future;
+    use time::OffsetDateTime;
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn test_handle_all() {
+        let message = |id: &str, message: Result<u32, Error>| PulledMessage {
+            ack_id: format!("ack-{id}"),
+            message,
+            attributes: None,
+            id: id.to_string(),
+            publish_time: OffsetDateTime::UNIX_EPOCH,
+            ordering_key: None,
+            delivery_attempt: 1,
+        };
+        let messages = vec![
+            message("1", Ok(1)),
+            message("2", Ok(2)),
+            message("3", Err(Error::NoData)),
+            message("4", Ok(4)),
+        ];
+        let handler = |message: PulledMessage<u32>| async move {
+            match message.message {
+                Ok(n) if n % 2 == 0 => Ok(()),
+                _ => Err("odd"),
+            }
+        };
+
+        let (summary, ack_ids, nack_ids) = block_on(handle_all(messages, &handler));
+        assert_eq!(
+            summary,
+            HandleSummary {
+                handled: 2,
+                failed: 1,
+                deserialize_errors: 1
+            }
+        );
+        assert_eq!(ack_ids, vec!["ack-2", "ack-4"]);
+        assert_eq!(nack_ids, vec!["ack-1", "ack-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_unless_cancelled() {
+        let result = unless_cancelled(None, future::ready(42)).await;
+        assert_eq!(result, Some(42));
+
+        let cancellation_token = CancellationToken::new();
+        let result = unless_cancelled(Some(&cancellation_token), future::ready(42)).await;
+        assert_eq!(result, Some(42));
+
+        cancellation_token.cancel();
+        let result = unless_cancelled(Some(&cancellation_token), future::pending::<()>()).await;
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_deserialize_subscriber_config() {
+        let config = json!({
+            "subscription_id": "test",
+            "workers": 4,
+            "flow_control": { "max_messages": 10 }
+        });
+        let config = serde_json::from_value::<SubscriberConfig>(config).unwrap();
+        assert_eq!(config.subscription_id, "test");
+        assert_eq!(config.workers, 4);
+        assert_eq!(config.flow_control.max_messages, 10);
+
+        let config =
+            serde_json::from_value::<SubscriberConfig>(json!({ "subscription_id": "test" }));
+        assert_eq!(config.unwrap(), SubscriberConfig::new("test"));
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_deserialize_subscribe_config() {
+        let config = json!({ "workers": 2, "nack_ack_deadline_seconds": 10 });
+        let config = serde_json::from_value::<SubscribeConfig>(config).unwrap();
+        assert_eq!(config.workers, 2);
+        assert_eq!(config.nack_ack_deadline_seconds, 10);
+
+        let config = serde_json::from_value::<SubscribeConfig>(json!({})).unwrap();
+        assert_eq!(config.workers, 1);
+        assert_eq!(config.nack_ack_deadline_seconds, 0);
+        assert!(config.cancellation_token.is_none());
+    }
+}
@@ -1,16 +1,42 @@
-use crate::{error::Error, PubSubClient};
+use crate::{
+    checkpoint::CheckpointStore, compression::decompress, dedup::Deduplicator,
+    encryption::check_decrypted, error::Error, integrity::verify_checksum, retry::RetryConfig,
+    timeouts::Operation, PubSubClient,
+};
 use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{future, stream, StreamExt};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, error::Error as StdError, fmt::Debug, time::Duration};
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::{Debug, Display},
+    future::Future,
+    ops::{Deref, Not},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use time::OffsetDateTime;
-use tracing::debug;
+use tracing::{debug, warn};
+
+/// Backoff between pulls returning no messages in [PubSubClient::pull_wait], to not hammer the
+/// Pub/Sub service while a subscription is idle.
+const EMPTY_PULL_BACKOFF: RetryConfig = RetryConfig {
+    max_retries: u32::MAX,
+    initial_backoff: Duration::from_millis(100),
+    max_backoff: Duration::from_secs(2),
+    multiplier: 2.0,
+};
+
+/// Maximum number of ACK IDs per acknowledge or modify ACK deadline request.
+const MAX_ACK_IDS_PER_REQUEST: usize = 2_500;
+
+/// Maximum size of the ACK IDs per acknowledge or modify ACK deadline request, leaving headroom
+/// below the request size limit of 512 KiB.
+const MAX_ACK_IDS_SIZE_PER_REQUEST: usize = 480 * 1024;
 
 #[derive(Debug)]
-pub struct PulledMessage<M>
-where
-    M: DeserializeOwned,
-{
+pub struct PulledMessage<M> {
     pub ack_id: String,
     pub message: Result<M, Error>,
     pub attributes: Option<HashMap<String, String>>,
@@ -20,6 +46,118 @@ where
     pub delivery_attempt: u32,
 }
 
+impl<M> PulledMessage<M> {
+    /// Deserialize the attributes – none are treated like empty ones – into the given type, e.g. a
+    /// struct with a field per attribute. As attribute values are strings, the fields must be
+    /// deserializable from strings.
+    pub fn attributes_as<A>(&self) -> Result<A, Error>
+    where
+        A: DeserializeOwned,
+    {
+        let attributes = match &self.attributes {
+            Some(attributes) => serde_json::to_value(attributes),
+            None => Ok(Value::Object(Default::default())),
+        };
+        attributes
+            .and_then(serde_json::from_value)
+            .map_err(Error::DeserializeAttributes)
+    }
+}
+
+/// A [PulledMessage] with its deserialized attributes, see [PubSubClient::pull_with_attributes].
+pub type PulledMessageWithAttributes<M, A> = (PulledMessage<M>, Result<A, Error>);
+
+/// A [PulledMessage] which knows where it came from and hence can be acknowledged or negatively
+/// acknowledged by itself; dereferences to the [PulledMessage].
+#[derive(Debug)]
+pub struct ReceivedMessage<'a, M>
+where
+    M: DeserializeOwned,
+{
+    client: &'a PubSubClient,
+    subscription_id: String,
+    message: PulledMessage<M>,
+    deduplicator: Option<Arc<Mutex<Deduplicator>>>,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl<'a, M> ReceivedMessage<'a, M>
+where
+    M: DeserializeOwned,
+{
+    pub(crate) fn new(
+        client: &'a PubSubClient,
+        subscription_id: String,
+        message: PulledMessage<M>,
+    ) -> Self {
+        Self {
+            client,
+            subscription_id,
+            message,
+            deduplicator: None,
+            checkpoint_store: None,
+        }
+    }
+
+    /// Remember the message ID with the given [Deduplicator] once acknowledged.
+    pub(crate) fn with_deduplicator(mut self, deduplicator: Arc<Mutex<Deduplicator>>) -> Self {
+        self.deduplicator = Some(deduplicator);
+        self
+    }
+
+    /// Mark the message as processed in the given [CheckpointStore] before acknowledging it.
+    pub(crate) fn with_checkpoint_store(
+        mut self,
+        checkpoint_store: Arc<dyn CheckpointStore>,
+    ) -> Self {
+        self.checkpoint_store = Some(checkpoint_store);
+        self
+    }
+
+    pub fn subscription_id(&self) -> &str {
+        &self.subscription_id
+    }
+
+    pub fn into_message(self) -> PulledMessage<M> {
+        self.message
+    }
+
+    /// Acknowledge this message, after marking it as processed if a [CheckpointStore] is used.
+    pub async fn ack(self) -> Result<(), Error> {
+        if let Some(checkpoint_store) = &self.checkpoint_store {
+            checkpoint_store.mark_processed(&self.message.id).await?;
+        }
+        self.client
+            .acknowledge(&self.subscription_id, vec![&self.message.ack_id], None)
+            .await?;
+        if let Some(deduplicator) = &self.deduplicator {
+            deduplicator
+                .lock()
+                .expect("lock deduplicator")
+                .record(&self.message.id);
+        }
+        Ok(())
+    }
+
+    /// Negatively acknowledge this message, i.e. make it available for redelivery immediately.
+    pub async fn nack(self) -> Result<(), Error> {
+        self.client
+            .nack(&self.subscription_id, vec![&self.message.ack_id], None)
+            .await
+    }
+}
+
+impl<M> Deref for ReceivedMessage<'_, M>
+where
+    M: DeserializeOwned,
+{
+    type Target = PulledMessage<M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.message
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RawPulledMessageEnvelope {
@@ -41,10 +179,90 @@ pub struct RawPulledMessage {
     pub ordering_key: Option<String>,
 }
 
+impl RawPulledMessage {
+    /// The Base64 decoded and – according to the `content-encoding` attribute – decompressed data,
+    /// e.g. for protobuf or other binary payloads; empty if there is no data.
+    pub fn data_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.data
+            .as_ref()
+            .map(|data| STANDARD.decode(data).map_err(Error::DecodeBase64))
+            .transpose()
+            .map(Option::unwrap_or_default)
+            .and_then(|data| {
+                check_decrypted(self.attributes.as_ref())?;
+                verify_checksum(self.attributes.as_ref(), &data)?;
+                decompress(self.attributes.as_ref(), data)
+            })
+    }
+}
+
+/// Options for pulling messages, see [PubSubClient::pull_raw_with_options].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PullOptions {
+    pub max_messages: u32,
+    /// Respond immediately even if there are no messages instead of waiting for some; discouraged
+    /// by Google, because it can considerably increase latency.
+    pub return_immediately: bool,
+    /// Acknowledge deadline to set for the pulled messages right after pulling them, at most 600.
+    pub ack_deadline_seconds: Option<u32>,
+}
+
+impl PullOptions {
+    pub fn new(max_messages: u32) -> Self {
+        Self {
+            max_messages,
+            return_immediately: false,
+            ack_deadline_seconds: None,
+        }
+    }
+
+    pub fn with_return_immediately(mut self, return_immediately: bool) -> Self {
+        self.return_immediately = return_immediately;
+        self
+    }
+
+    pub fn with_ack_deadline_seconds(mut self, ack_deadline_seconds: u32) -> Self {
+        self.ack_deadline_seconds = Some(ack_deadline_seconds);
+        self
+    }
+}
+
+/// What to do with pulled messages not matching a client-side predicate, see
+/// [PubSubClient::pull_filtered].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonMatching {
+    /// Negatively acknowledge them, i.e. make them available for redelivery immediately.
+    #[default]
+    Nack,
+    /// Acknowledge them, i.e. drop them.
+    Ack,
+}
+
+/// Result of a transform of the decoded data of a pulled message, see
+/// [PubSubClient::pull_with_raw_transform].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawTransformed {
+    /// Bytes to be parsed as JSON.
+    Bytes(Vec<u8>),
+    /// Already parsed JSON.
+    Value(Value),
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct PullRequest {
     max_messages: u32,
+    #[serde(skip_serializing_if = "Not::not")]
+    return_immediately: bool,
+}
+
+impl From<PullOptions> for PullRequest {
+    fn from(options: PullOptions) -> Self {
+        Self {
+            max_messages: options.max_messages,
+            return_immediately: options.return_immediately,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,8 +274,15 @@ struct PullResponse {
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct AcknowledgeRequest<'a> {
-    ack_ids: Vec<&'a str>,
+pub(crate) struct AcknowledgeRequest<'a> {
+    pub(crate) ack_ids: Vec<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ModifyAckDeadlineRequest<'a> {
+    pub(crate) ack_ids: Vec<&'a str>,
+    pub(crate) ack_deadline_seconds: u32,
 }
 
 impl PubSubClient {
@@ -75,6 +300,133 @@ impl PubSubClient {
             .await
     }
 
+    /// Like [PubSubClient::pull], but repeatedly pulls until at least one message is available or
+    /// the given deadline has elapsed, in which case an empty vector is returned. Messages of a
+    /// pull request still in flight at the deadline are not lost, but get redelivered after their
+    /// ACK deadline has expired.
+    #[tracing::instrument]
+    pub async fn pull_wait<M>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        deadline: Instant,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+    {
+        let pull = async {
+            let mut empty_pulls = 0;
+            loop {
+                let messages = self.pull(subscription_id, max_messages, None).await?;
+                if !messages.is_empty() {
+                    return Ok(messages);
+                }
+
+                let backoff = EMPTY_PULL_BACKOFF
+                    .jittered_backoff(empty_pulls)
+                    .unwrap_or(EMPTY_PULL_BACKOFF.max_backoff);
+                debug!(
+                    subscription_id,
+                    ?backoff,
+                    "pulled no messages, pulling again"
+                );
+                tokio::time::sleep(backoff).await;
+                empty_pulls = empty_pulls.saturating_add(1);
+            }
+        };
+
+        tokio::time::timeout_at(deadline.into(), pull)
+            .await
+            .unwrap_or_else(|_| Ok(vec![]))
+    }
+
+    /// Like [PubSubClient::pull], but only returns the messages matching the given predicate, e.g.
+    /// on their attributes or ordering key, whereas the others are acknowledged or negatively
+    /// acknowledged. This is a client-side alternative to filters for subscriptions which have
+    /// been created without one.
+    #[tracing::instrument(skip(predicate))]
+    pub async fn pull_filtered<M, P>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        predicate: P,
+        non_matching: NonMatching,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+        P: Fn(&PulledMessage<M>) -> bool,
+    {
+        let messages = self.pull(subscription_id, max_messages, timeout).await?;
+        Ok(self
+            .retain_matching(subscription_id, messages, predicate, non_matching)
+            .await)
+    }
+
+    /// Return the given messages matching the given predicate and acknowledge or negatively
+    /// acknowledge the others.
+    pub(crate) async fn retain_matching<M, P>(
+        &self,
+        subscription_id: &str,
+        messages: Vec<PulledMessage<M>>,
+        predicate: P,
+        non_matching: NonMatching,
+    ) -> Vec<PulledMessage<M>>
+    where
+        M: DeserializeOwned,
+        P: Fn(&PulledMessage<M>) -> bool,
+    {
+        let (matching, others) = messages
+            .into_iter()
+            .partition::<Vec<_>, _>(|message| predicate(message));
+        if others.is_empty() {
+            return matching;
+        }
+
+        debug!(
+            subscription_id,
+            count = others.len(),
+            ?non_matching,
+            "dropping non-matching messages"
+        );
+        let ack_ids = others
+            .iter()
+            .map(|message| message.ack_id.as_str())
+            .collect();
+        let result = match non_matching {
+            NonMatching::Nack => self.nack(subscription_id, ack_ids, None).await,
+            NonMatching::Ack => self.acknowledge(subscription_id, ack_ids, None).await,
+        };
+        if let Err(error) = result {
+            warn!(%error, subscription_id, ?non_matching, "cannot handle non-matching messages");
+        }
+
+        matching
+    }
+
+    /// Like [PubSubClient::pull], but additionally deserializes the attributes of each message,
+    /// see [PulledMessage::attributes_as].
+    #[tracing::instrument]
+    pub async fn pull_with_attributes<M, A>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<PulledMessageWithAttributes<M, A>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+        A: DeserializeOwned,
+    {
+        let messages = self.pull(subscription_id, max_messages, timeout).await?;
+        Ok(messages
+            .into_iter()
+            .map(|message| {
+                let attributes = message.attributes_as();
+                (message, attributes)
+            })
+            .collect())
+    }
+
     #[tracing::instrument(skip(transform))]
     pub async fn pull_with_transform<M, T>(
         &self,
@@ -97,62 +449,382 @@ impl PubSubClient {
         Ok(messages)
     }
 
+    /// Like [PubSubClient::pull_with_transform], but the transform may return `None` to skip a
+    /// message, e.g. a poison or irrelevant one: skipped messages are acknowledged and not
+    /// returned.
+    #[tracing::instrument(skip(transform))]
+    pub async fn pull_with_skipping_transform<M, T>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        transform: T,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+        T: Fn(
+            &RawPulledMessageEnvelope,
+            Value,
+        ) -> Result<Option<Value>, Box<dyn StdError + Send + Sync + 'static>>,
+    {
+        let envelopes = self
+            .pull_raw(subscription_id, max_messages, timeout)
+            .await?;
+        let (messages, skipped_ack_ids) = deserialize_skipping(envelopes, transform);
+
+        if !skipped_ack_ids.is_empty() {
+            debug!(
+                subscription_id,
+                count = skipped_ack_ids.len(),
+                "skipping messages"
+            );
+            let ack_ids = skipped_ack_ids.iter().map(|id| &id[..]).collect();
+            self.acknowledge(subscription_id, ack_ids, timeout).await?;
+        }
+
+        Ok(messages)
+    }
+
+    /// Like [PubSubClient::pull_with_transform], but the transform is applied to the decoded data
+    /// before it is parsed as JSON, e.g. to decompress or decrypt it or to unwrap a non-JSON
+    /// payload.
+    #[tracing::instrument(skip(transform))]
+    pub async fn pull_with_raw_transform<M, T>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        transform: T,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+        T: Fn(
+            &RawPulledMessageEnvelope,
+            &[u8],
+        ) -> Result<RawTransformed, Box<dyn StdError + Send + Sync + 'static>>,
+    {
+        let envelopes = self
+            .pull_raw(subscription_id, max_messages, timeout)
+            .await?;
+        let messages = deserialize_raw(envelopes, transform);
+        Ok(messages)
+    }
+
+    /// Like [PubSubClient::pull_with_transform], but with an asynchronous transform, e.g. to
+    /// consult a cache, a schema registry or a decryption service. As the returned future must
+    /// not borrow the envelope, copy whatever it needs from the envelope.
+    #[tracing::instrument(skip(transform))]
+    pub async fn pull_with_async_transform<M, T, F>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        transform: T,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+        T: Fn(&RawPulledMessageEnvelope, Value) -> F,
+        F: Future<Output = Result<Value, Box<dyn StdError + Send + Sync + 'static>>>,
+    {
+        let envelopes = self
+            .pull_raw(subscription_id, max_messages, timeout)
+            .await?;
+        let messages = deserialize_async(envelopes, transform).await;
+        Ok(messages)
+    }
+
+    /// Pull like [PubSubClient::pull], but acknowledge and skip messages which have already been
+    /// recorded as processed in the given [CheckpointStore]. After processing a returned message,
+    /// record it via [CheckpointStore::mark_processed] before acknowledging it.
+    #[tracing::instrument(skip(checkpoint_store))]
+    pub async fn pull_unprocessed<M>(
+        &self,
+        subscription_id: &str,
+        max_messages: u32,
+        timeout: Option<Duration>,
+        checkpoint_store: &dyn CheckpointStore,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+    {
+        let envelopes = self
+            .pull_raw(subscription_id, max_messages, timeout)
+            .await?;
+
+        let mut unprocessed = Vec::with_capacity(envelopes.len());
+        let mut processed_ack_ids = vec![];
+        for envelope in envelopes {
+            if checkpoint_store.is_processed(&envelope.message.id).await? {
+                debug!(id = envelope.message.id, "skipping processed message");
+                processed_ack_ids.push(envelope.ack_id);
+            } else {
+                unprocessed.push(envelope);
+            }
+        }
+
+        if !processed_ack_ids.is_empty() {
+            let ack_ids = processed_ack_ids.iter().map(|id| &id[..]).collect();
+            self.acknowledge(subscription_id, ack_ids, timeout).await?;
+        }
+
+        Ok(deserialize(unprocessed, |_, value| Ok(value)))
+    }
+
     #[tracing::instrument]
     pub async fn pull_raw(
         &self,
         subscription_id: &str,
         max_messages: u32,
         timeout: Option<Duration>,
+    ) -> Result<Vec<RawPulledMessageEnvelope>, Error> {
+        self.pull_raw_with_options(subscription_id, PullOptions::new(max_messages), timeout)
+            .await
+    }
+
+    /// Pull like [PubSubClient::pull], but with the given [PullOptions].
+    #[tracing::instrument]
+    pub async fn pull_with_options<M>(
+        &self,
+        subscription_id: &str,
+        options: PullOptions,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<PulledMessage<M>>, Error>
+    where
+        M: DeserializeOwned + Debug,
+    {
+        let envelopes = self
+            .pull_raw_with_options(subscription_id, options, timeout)
+            .await?;
+        Ok(deserialize(envelopes, |_, value| Ok(value)))
+    }
+
+    /// Pull like [PubSubClient::pull_raw], but with the given [PullOptions].
+    #[tracing::instrument]
+    pub async fn pull_raw_with_options(
+        &self,
+        subscription_id: &str,
+        options: PullOptions,
+        timeout: Option<Duration>,
     ) -> Result<Vec<RawPulledMessageEnvelope>, Error> {
         let url = self.subscription_url(subscription_id, "pull");
-        let request = PullRequest { max_messages };
+        let request = PullRequest::from(options);
         debug!(url, "sending request");
-        let response = self.send_request(&url, &request, timeout).await?;
+        let response = self
+            .send_request(&url, &request, timeout, Operation::Pull)
+            .await?;
 
         if !response.status().is_success() {
             return Err(Error::unexpected_http_status_code(response).await);
         }
 
-        let envelopes = response
+        let mut envelopes = response
             .json::<PullResponse>()
             .await
             .map_err(Error::UnexpectedHttpResponse)?
             .envelopes;
+        if let Some(cipher) = &self.cipher {
+            cipher.decrypt(&mut envelopes);
+        }
+
+        if let Some(ack_deadline_seconds) = options.ack_deadline_seconds {
+            if !envelopes.is_empty() {
+                let ack_ids = envelopes.iter().map(|e| &e.ack_id[..]).collect();
+                self.modify_ack_deadline(subscription_id, ack_ids, ack_deadline_seconds, timeout)
+                    .await?;
+            }
+        }
 
         Ok(envelopes)
     }
 
     /// According to how Google Cloud Pub/Sub works, passing at least one invalid ACK ID fails the
     /// whole request via a 400 Bad Request response.
+    ///
+    /// Large numbers of ACK IDs are transparently split into multiple requests; if more than one
+    /// request is sent and some fail, [Error::ChunkedRequest] is returned.
     pub async fn acknowledge(
         &self,
         subscription_id: &str,
         ack_ids: Vec<&str>,
         timeout: Option<Duration>,
     ) -> Result<(), Error> {
-        let request = AcknowledgeRequest { ack_ids };
-        let response = self
-            .send_request(
-                &self.subscription_url(subscription_id, "acknowledge"),
-                &request,
-                timeout,
-            )
-            .await?;
+        self.send_chunked(
+            subscription_id,
+            "acknowledge",
+            ack_ids,
+            |ack_ids| AcknowledgeRequest { ack_ids },
+            timeout,
+        )
+        .await
+    }
 
-        if !response.status().is_success() {
-            return Err(Error::unexpected_http_status_code(response).await);
+    /// Modify the acknowledge deadline of the messages with the given ACK IDs to the given number
+    /// of seconds from now, at most 600. Like for [PubSubClient::acknowledge], passing at least one
+    /// invalid ACK ID fails the whole request and large numbers of ACK IDs are split into multiple
+    /// requests.
+    pub async fn modify_ack_deadline(
+        &self,
+        subscription_id: &str,
+        ack_ids: Vec<&str>,
+        ack_deadline_seconds: u32,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_chunked(
+            subscription_id,
+            "modifyAckDeadline",
+            ack_ids,
+            |ack_ids| ModifyAckDeadlineRequest {
+                ack_ids,
+                ack_deadline_seconds,
+            },
+            timeout,
+        )
+        .await
+    }
+
+    /// Negatively acknowledge the messages with the given ACK IDs, i.e. make them available for
+    /// redelivery immediately by setting their acknowledge deadline to zero.
+    pub async fn nack(
+        &self,
+        subscription_id: &str,
+        ack_ids: Vec<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.modify_ack_deadline(subscription_id, ack_ids, 0, timeout)
+            .await
+    }
+
+    /// Send one request per chunk of the given ACK IDs; a single failed chunk results in its
+    /// error, multiple chunks in [Error::ChunkedRequest] if some fail.
+    async fn send_chunked<'a, R, F>(
+        &self,
+        subscription_id: &str,
+        action: &str,
+        ack_ids: Vec<&'a str>,
+        request: F,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error>
+    where
+        R: Serialize,
+        F: Fn(Vec<&'a str>) -> R,
+    {
+        let url = self.subscription_url(subscription_id, action);
+        let chunks = chunk_ack_ids(ack_ids);
+        let total = chunks.len();
+
+        let mut failed = vec![];
+        for chunk in chunks {
+            let ack_ids = chunk.iter().map(|ack_id| ack_id.to_string()).collect();
+            let result = match self
+                .send_request(&url, &request(chunk), timeout, Operation::Ack)
+                .await
+            {
+                Ok(response) if !response.status().is_success() => {
+                    Err(Error::unexpected_http_status_code(response).await)
+                }
+                Ok(_) => Ok(()),
+                Err(error) => Err(error),
+            };
+            if let Err(error) = result {
+                failed.push((ack_ids, error));
+            }
         }
 
-        Ok(())
+        match failed.len() {
+            0 => Ok(()),
+            1 if total == 1 => Err(failed.pop().expect("one failed chunk").1),
+            _ => Err(Error::ChunkedRequest { total, failed }),
+        }
+    }
+
+    pub(crate) fn subscription_url(&self, subscription_id: &str, action: &str) -> String {
+        let api_url = &self.api_url;
+        format!(
+            "{api_url}/{}:{action}",
+            self.subscription_name(subscription_id)
+        )
+    }
+}
+
+/// Hand the given messages to the given handler sequentially per ordering key, in the order they
+/// have been pulled, while processing messages with different or without ordering keys
+/// concurrently, at most `max_concurrency` at a time.
+///
+/// Once handling a message fails, the subsequent messages with the same ordering key are not
+/// handled, but returned, because handling them would break the ordering; these should be
+/// negatively acknowledged, like the failed one, to get all of them redelivered in order.
+pub async fn process_ordered<M, H, F, E>(
+    messages: Vec<PulledMessage<M>>,
+    max_concurrency: usize,
+    handler: H,
+) -> Vec<PulledMessage<M>>
+where
+    M: DeserializeOwned,
+    H: Fn(PulledMessage<M>) -> F,
+    F: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let mut groups = Vec::<Vec<PulledMessage<M>>>::new();
+    let mut group_indexes = HashMap::<String, usize>::new();
+    for message in messages {
+        match message
+            .ordering_key
+            .as_deref()
+            .filter(|key| !key.is_empty())
+        {
+            Some(key) => match group_indexes.get(key) {
+                Some(&n) => groups[n].push(message),
+                None => {
+                    group_indexes.insert(key.to_string(), groups.len());
+                    groups.push(vec![message]);
+                }
+            },
+            None => groups.push(vec![message]),
+        }
     }
 
-    fn subscription_url(&self, subscription_id: &str, action: &str) -> String {
-        let project_url = &self.project_url;
-        format!("{project_url}/subscriptions/{subscription_id}:{action}")
+    stream::iter(groups)
+        .map(|group| async {
+            let mut group = group.into_iter();
+            while let Some(message) = group.next() {
+                let id = message.id.clone();
+                if let Err(error) = handler(message).await {
+                    let skipped = group.collect::<Vec<_>>();
+                    warn!(%error, id, skipped = skipped.len(), "handling ordered message failed");
+                    return skipped;
+                }
+            }
+            vec![]
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .concat()
+        .await
+}
+
+/// Split the given ACK IDs into chunks which do not exceed the maximum number and size per request;
+/// there is always at least one chunk.
+pub(crate) fn chunk_ack_ids(ack_ids: Vec<&str>) -> Vec<Vec<&str>> {
+    let mut chunks = vec![vec![]];
+    let mut size = 0;
+    for ack_id in ack_ids {
+        // Quotes and comma in the JSON array.
+        let ack_id_size = ack_id.len() + 3;
+        let chunk = chunks.last_mut().expect("at least one chunk");
+        if !chunk.is_empty()
+            && (chunk.len() == MAX_ACK_IDS_PER_REQUEST
+                || size + ack_id_size > MAX_ACK_IDS_SIZE_PER_REQUEST)
+        {
+            chunks.push(vec![ack_id]);
+            size = ack_id_size;
+        } else {
+            chunk.push(ack_id);
+            size += ack_id_size;
+        }
     }
+    chunks
 }
 
-fn deserialize<M, T>(
+pub(crate) fn deserialize<M, T>(
     envelopes: Vec<RawPulledMessageEnvelope>,
     transform: T,
 ) -> Vec<PulledMessage<M>>
@@ -166,50 +838,173 @@ where
     envelopes
         .into_iter()
         .map(|envelope| {
-            let message = envelope
-                .message
-                .data
-                .as_ref()
-                .ok_or(Error::NoData)
-                .and_then(|data| STANDARD.decode(data).map_err(Error::DecodeBase64))
-                .and_then(|bytes| {
-                    serde_json::from_slice::<Value>(&bytes).map_err(Error::Deserialize)
-                })
-                .and_then(|value| transform(&envelope, value).map_err(Error::Transform))
-                .and_then(|value| serde_json::from_value(value).map_err(Error::Deserialize));
-            let RawPulledMessageEnvelope {
-                ack_id,
-                message:
-                    RawPulledMessage {
-                        data: _,
-                        attributes,
-                        id,
-                        publish_time,
-                        ordering_key,
-                    },
-                delivery_attempt,
-            } = envelope;
-            PulledMessage {
-                ack_id,
-                message,
+            let value = decode_json(&envelope)
+                .and_then(|value| transform(&envelope, value).map_err(Error::Transform));
+            into_pulled_message(envelope, value)
+        })
+        .collect()
+}
+
+/// Like [deserialize], but with an asynchronous transform, transforming all messages concurrently.
+pub(crate) async fn deserialize_async<M, T, F>(
+    envelopes: Vec<RawPulledMessageEnvelope>,
+    transform: T,
+) -> Vec<PulledMessage<M>>
+where
+    M: DeserializeOwned,
+    T: Fn(&RawPulledMessageEnvelope, Value) -> F,
+    F: Future<Output = Result<Value, Box<dyn StdError + Send + Sync + 'static>>>,
+{
+    let messages = envelopes.into_iter().map(|envelope| {
+        let value = decode_json(&envelope).map(|value| transform(&envelope, value));
+        async move {
+            let value = match value {
+                Ok(value) => value.await.map_err(Error::Transform),
+                Err(error) => Err(error),
+            };
+            into_pulled_message(envelope, value)
+        }
+    });
+    future::join_all(messages).await
+}
+
+/// Like [deserialize], but with a transform which may skip messages, whose ACK IDs are returned
+/// separately.
+pub(crate) fn deserialize_skipping<M, T>(
+    envelopes: Vec<RawPulledMessageEnvelope>,
+    transform: T,
+) -> (Vec<PulledMessage<M>>, Vec<String>)
+where
+    M: DeserializeOwned,
+    T: Fn(
+        &RawPulledMessageEnvelope,
+        Value,
+    ) -> Result<Option<Value>, Box<dyn StdError + Send + Sync + 'static>>,
+{
+    let mut messages = Vec::with_capacity(envelopes.len());
+    let mut skipped_ack_ids = vec![];
+    for envelope in envelopes {
+        let value = decode_json(&envelope)
+            .and_then(|value| transform(&envelope, value).map_err(Error::Transform));
+        match value {
+            Ok(None) => skipped_ack_ids.push(envelope.ack_id),
+            Ok(Some(value)) => messages.push(into_pulled_message(envelope, Ok(value))),
+            Err(error) => messages.push(into_pulled_message(envelope, Err(error))),
+        }
+    }
+    (messages, skipped_ack_ids)
+}
+
+/// Like [deserialize], but with a transform of the decoded data before it is parsed as JSON.
+pub(crate) fn deserialize_raw<M, T>(
+    envelopes: Vec<RawPulledMessageEnvelope>,
+    transform: T,
+) -> Vec<PulledMessage<M>>
+where
+    M: DeserializeOwned,
+    T: Fn(
+        &RawPulledMessageEnvelope,
+        &[u8],
+    ) -> Result<RawTransformed, Box<dyn StdError + Send + Sync + 'static>>,
+{
+    envelopes
+        .into_iter()
+        .map(|envelope| {
+            let value = decode_bytes(&envelope)
+                .and_then(|bytes| transform(&envelope, &bytes).map_err(Error::Transform))
+                .and_then(|transformed| match transformed {
+                    RawTransformed::Bytes(bytes) => parse_json(&bytes),
+                    RawTransformed::Value(value) => Ok(value),
+                });
+            into_pulled_message(envelope, value)
+        })
+        .collect()
+}
+
+/// Decode the Base64 encoded and possibly compressed data of the given envelope.
+fn decode_bytes(envelope: &RawPulledMessageEnvelope) -> Result<Vec<u8>, Error> {
+    envelope
+        .message
+        .data
+        .as_ref()
+        .ok_or(Error::NoData)
+        .and_then(|data| STANDARD.decode(data).map_err(Error::DecodeBase64))
+        .and_then(|data| {
+            let attributes = envelope.message.attributes.as_ref();
+            check_decrypted(attributes)?;
+            verify_checksum(attributes, &data)?;
+            decompress(attributes, data)
+        })
+}
+
+/// Decode the Base64 encoded data of the given envelope as JSON.
+fn decode_json(envelope: &RawPulledMessageEnvelope) -> Result<Value, Error> {
+    decode_bytes(envelope).and_then(|bytes| parse_json(&bytes))
+}
+
+fn parse_json(bytes: &[u8]) -> Result<Value, Error> {
+    serde_json::from_slice::<Value>(bytes).map_err(Error::Deserialize)
+}
+
+/// Deserialize the given (transformed) JSON value and combine it with the given envelope into a
+/// [PulledMessage].
+fn into_pulled_message<M>(
+    envelope: RawPulledMessageEnvelope,
+    value: Result<Value, Error>,
+) -> PulledMessage<M>
+where
+    M: DeserializeOwned,
+{
+    let message = value.and_then(|value| serde_json::from_value(value).map_err(Error::Deserialize));
+    pulled_message(envelope, message)
+}
+
+/// Combine the given envelope and message into a [PulledMessage].
+pub(crate) fn pulled_message<M>(
+    envelope: RawPulledMessageEnvelope,
+    message: Result<M, Error>,
+) -> PulledMessage<M> {
+    let RawPulledMessageEnvelope {
+        ack_id,
+        message:
+            RawPulledMessage {
+                data: _,
                 attributes,
                 id,
                 publish_time,
                 ordering_key,
-                delivery_attempt,
-            }
-        })
-        .collect()
+            },
+        delivery_attempt,
+    } = envelope;
+    PulledMessage {
+        ack_id,
+        message,
+        attributes,
+        id,
+        publish_time,
+        ordering_key,
+        delivery_attempt,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{deserialize, RawPulledMessage, RawPulledMessageEnvelope};
+    use super::{
+        chunk_ack_ids, deserialize, deserialize_async, deserialize_raw, deserialize_skipping,
+        process_ordered, Error, ModifyAckDeadlineRequest, PullOptions, PullRequest, PulledMessage,
+        RawPulledMessage, RawPulledMessageEnvelope, RawTransformed, MAX_ACK_IDS_PER_REQUEST,
+    };
+    use crate::TypeAttributeTransform;
     use anyhow::anyhow;
     use base64::{engine::general_purpose::STANDARD, Engine};
+    use futures::executor::block_on;
     use serde::Deserialize;
     use serde_json::{json, Value};
-    use std::{collections::HashMap, error::Error as StdError};
+    use std::{
+        collections::HashMap,
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
     use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
     const TIME: &str = "2022-02-20T22:02:20.123456789Z";
@@ -220,6 +1015,128 @@ mod tests {
         Bar { text: String },
     }
 
+    #[test]
+    fn test_attributes_as() {
+        #[derive(Debug, Deserialize, PartialEq, Eq)]
+        struct Attributes {
+            version: String,
+            tenant_id: Option<String>,
+        }
+
+        let mut message = PulledMessage::<Value> {
+            ack_id: "ack".to_string(),
+            message: Ok(Value::Null),
+            attributes: Some(HashMap::from([("version".to_string(), "v2".to_string())])),
+            id: "id".to_string(),
+            publish_time: OffsetDateTime::UNIX_EPOCH,
+            ordering_key: None,
+            delivery_attempt: 1,
+        };
+        let attributes = message.attributes_as::<Attributes>();
+        assert_eq!(
+            attributes.unwrap(),
+            Attributes {
+                version: "v2".to_string(),
+                tenant_id: None
+            }
+        );
+
+        message.attributes = None;
+        let attributes = message.attributes_as::<Attributes>();
+        assert!(matches!(attributes, Err(Error::DeserializeAttributes(_))));
+    }
+
+    #[test]
+    fn test_process_ordered() {
+        let message = |id: &str, ordering_key: Option<&str>| PulledMessage::<Value> {
+            ack_id: format!("ack-{id}"),
+            message: Ok(Value::Null),
+            attributes: None,
+            id: id.to_string(),
+            publish_time: OffsetDateTime::UNIX_EPOCH,
+            ordering_key: ordering_key.map(ToString::to_string),
+            delivery_attempt: 1,
+        };
+        let messages = vec![
+            message("1", Some("a")),
+            message("2", Some("b")),
+            message("3", None),
+            message("4", Some("a")),
+            message("5", Some("b")),
+            message("6", Some("b")),
+        ];
+
+        let handled = Arc::new(Mutex::new(vec![]));
+        let skipped = block_on(process_ordered(messages, 4, |message| {
+            let handled = handled.clone();
+            async move {
+                handled.lock().unwrap().push(message.id.clone());
+                if message.id == "2" {
+                    Err(anyhow!("boom"))
+                } else {
+                    Ok(())
+                }
+            }
+        }));
+
+        let skipped = skipped.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(skipped, vec!["5", "6"]);
+
+        let handled = handled.lock().unwrap();
+        assert_eq!(handled.len(), 4);
+        let position = |id| handled.iter().position(|h| h == id).unwrap();
+        assert!(position("1") < position("4"));
+        assert!(handled.contains(&"2".to_string()));
+        assert!(handled.contains(&"3".to_string()));
+    }
+
+    #[test]
+    fn test_serialize_pull_request() {
+        let request = PullRequest::from(PullOptions::new(42));
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "maxMessages": 42 })
+        );
+
+        let request = PullRequest::from(PullOptions::new(42).with_return_immediately(true));
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "maxMessages": 42, "returnImmediately": true })
+        );
+    }
+
+    #[test]
+    fn test_chunk_ack_ids() {
+        let chunks = chunk_ack_ids(vec![]);
+        assert_eq!(chunks, vec![Vec::<&str>::new()]);
+
+        let ack_ids = vec!["ack-id"; 2 * MAX_ACK_IDS_PER_REQUEST + 1];
+        let chunks = chunk_ack_ids(ack_ids);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![MAX_ACK_IDS_PER_REQUEST, MAX_ACK_IDS_PER_REQUEST, 1]
+        );
+
+        let ack_id = "a".repeat(200 * 1024);
+        let chunks = chunk_ack_ids(vec![&ack_id; 5]);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_serialize_modify_ack_deadline_request() {
+        let request = ModifyAckDeadlineRequest {
+            ack_ids: vec!["ack-id"],
+            ack_deadline_seconds: 0,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "ackIds": ["ack-id"], "ackDeadlineSeconds": 0 })
+        );
+    }
+
     #[test]
     fn test_deserialize_ok() {
         let envelopes = vec![
@@ -278,35 +1195,141 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_deserialize_async() {
+        let envelope = |data: Option<Value>| RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: data.map(|data| STANDARD.encode(data.to_string())),
+                attributes: Some(HashMap::from([("type".to_string(), "Foo".to_string())])),
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::parse(TIME, &Rfc3339).unwrap(),
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let envelopes = vec![envelope(Some(json!({"text": "test"}))), envelope(None)];
+        let pulled_messages = block_on(deserialize_async::<Message, _, _>(
+            envelopes,
+            |envelope, value| {
+                let result = transform(envelope, value);
+                async move { result }
+            },
+        ));
+        assert_eq!(pulled_messages.len(), 2);
+        assert_eq!(
+            pulled_messages[0].message.as_ref().unwrap(),
+            &Message::Foo {
+                text: "test".to_string()
+            }
+        );
+        assert!(matches!(pulled_messages[1].message, Err(Error::NoData)));
+    }
+
+    #[test]
+    fn test_deserialize_raw() {
+        let envelope = |data: &str| RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: Some(STANDARD.encode(data)),
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::parse(TIME, &Rfc3339).unwrap(),
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let envelopes = vec![
+            envelope(r#"PREFIX{"Foo": {"text": "test"}}"#),
+            envelope("Bar:test"),
+            envelope("invalid"),
+        ];
+        let pulled_messages = deserialize_raw::<Message, _>(envelopes, |_, bytes| {
+            if let Some(bytes) = bytes.strip_prefix(b"PREFIX") {
+                Ok(RawTransformed::Bytes(bytes.to_vec()))
+            } else if let Some(text) = bytes.strip_prefix(b"Bar:") {
+                let text = String::from_utf8(text.to_vec())?;
+                Ok(RawTransformed::Value(json!({ "Bar": { "text": text } })))
+            } else {
+                Err(anyhow!("unexpected data").into())
+            }
+        });
+        assert_eq!(pulled_messages.len(), 3);
+        assert_eq!(
+            pulled_messages[0].message.as_ref().unwrap(),
+            &Message::Foo {
+                text: "test".to_string()
+            }
+        );
+        assert_eq!(
+            pulled_messages[1].message.as_ref().unwrap(),
+            &Message::Bar {
+                text: "test".to_string()
+            }
+        );
+        assert!(matches!(
+            pulled_messages[2].message,
+            Err(Error::Transform(_))
+        ));
+    }
+
+    #[test]
+    fn test_data_bytes() {
+        let mut message = RawPulledMessage {
+            data: Some(STANDARD.encode([0, 1, 2, 255])),
+            attributes: None,
+            id: "id".to_string(),
+            publish_time: OffsetDateTime::parse(TIME, &Rfc3339).unwrap(),
+            ordering_key: None,
+        };
+        assert_eq!(message.data_bytes().unwrap(), vec![0, 1, 2, 255]);
+
+        message.data = None;
+        assert_eq!(message.data_bytes().unwrap(), Vec::<u8>::new());
+
+        message.data = Some("not base64!".to_string());
+        assert!(matches!(message.data_bytes(), Err(Error::DecodeBase64(_))));
+    }
+
+    #[test]
+    fn test_deserialize_skipping() {
+        let envelope = |ack_id: &str, data: Value| RawPulledMessageEnvelope {
+            ack_id: ack_id.to_string(),
+            message: RawPulledMessage {
+                data: Some(STANDARD.encode(data.to_string())),
+                attributes: None,
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::parse(TIME, &Rfc3339).unwrap(),
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        };
+        let envelopes = vec![
+            envelope("1", json!({"Foo": {"text": "test"}})),
+            envelope("2", json!({"Poison": {}})),
+            envelope("3", json!(42)),
+        ];
+        let (pulled_messages, skipped_ack_ids) =
+            deserialize_skipping::<Message, _>(envelopes, |_, value| match value {
+                Value::Object(ref map) if map.contains_key("Poison") => Ok(None),
+                Value::Object(_) => Ok(Some(value)),
+                other => Err(anyhow!("unexpected JSON value `{other}`").into()),
+            });
+        assert_eq!(skipped_ack_ids, vec!["2".to_string()]);
+        assert_eq!(pulled_messages.len(), 2);
+        assert_eq!(pulled_messages[0].ack_id, "1");
+        assert!(pulled_messages[0].message.is_ok());
+        assert_eq!(pulled_messages[1].ack_id, "3");
+        assert!(matches!(
+            pulled_messages[1].message,
+            Err(Error::Transform(_))
+        ));
+    }
+
     fn transform(
         envelope: &RawPulledMessageEnvelope,
-        mut value: Value,
+        value: Value,
     ) -> Result<Value, Box<dyn StdError + Send + Sync + 'static>> {
-        let attributes = &envelope.message.attributes;
-        match attributes {
-            Some(attributes) => match attributes.get("version").map(|v| &v[..]).unwrap_or("v1") {
-                "v1" => {
-                    let mut type_keys = attributes
-                        .keys()
-                        .filter(|key| **key == "type" || key.starts_with("type."))
-                        .map(|key| (&key[..], key.split(".").skip(1).collect::<Vec<_>>()))
-                        .collect::<Vec<_>>();
-                    type_keys.sort_unstable_by(|v1, v2| v2.1.len().cmp(&v1.1.len()));
-                    for (type_key, json_path) in type_keys {
-                        let sub_value = json_path
-                            .iter()
-                            .fold(Some(&mut value), |v, k| v.and_then(|v| v.get_mut(k)));
-                        if let Some(sub_value) = sub_value {
-                            let tpe = attributes.get(type_key).unwrap().to_string();
-                            *sub_value = json!({ tpe: sub_value });
-                        }
-                    }
-                    Ok(value)
-                }
-                "v2" => Ok(value),
-                unknown => Err(anyhow!("Unknow version `{unknown}`").into()),
-            },
-            None => Ok(value),
-        }
+        TypeAttributeTransform::default().transform(envelope, value)
     }
 }
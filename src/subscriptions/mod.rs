@@ -0,0 +1,989 @@
+use crate::{error::Error, pagination::Page, patch, resource_path, PubSubClient};
+use futures::Stream;
+use reqwest::{Method, StatusCode};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Subscription resource as returned by the Pub/Sub service.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    /// Fully qualified name, i.e. `projects/{project}/subscriptions/{subscription}`.
+    pub name: String,
+    /// Fully qualified name of the topic, i.e. `projects/{project}/topics/{topic}`.
+    pub topic: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub ack_deadline_seconds: Option<u32>,
+    #[serde(default, with = "crate::duration::option")]
+    pub message_retention_duration: Option<Duration>,
+    #[serde(default)]
+    pub retain_acked_messages: bool,
+    #[serde(default)]
+    pub enable_message_ordering: bool,
+    #[serde(default)]
+    pub enable_exactly_once_delivery: bool,
+    pub filter: Option<String>,
+    pub expiration_policy: Option<ExpirationPolicy>,
+    pub push_config: Option<PushConfig>,
+    pub bigquery_config: Option<BigQueryConfig>,
+    pub cloud_storage_config: Option<CloudStorageConfig>,
+    pub dead_letter_policy: Option<DeadLetterPolicy>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub state: Option<SubscriptionState>,
+}
+
+/// Configuration for creating a subscription.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ack_deadline_seconds: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub message_retention_duration: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retain_acked_messages: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_message_ordering: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_exactly_once_delivery: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_policy: Option<ExpirationPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_config: Option<PushConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bigquery_config: Option<BigQueryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_storage_config: Option<CloudStorageConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_letter_policy: Option<DeadLetterPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+impl SubscriptionConfig {
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Acknowledge deadline, between 10 and 600 seconds.
+    pub fn with_ack_deadline_seconds(mut self, ack_deadline_seconds: u32) -> Self {
+        self.ack_deadline_seconds = Some(ack_deadline_seconds);
+        self
+    }
+
+    /// Retention of unacknowledged messages, between ten minutes and seven days; also of
+    /// acknowledged messages if these are retained.
+    pub fn with_message_retention_duration(mut self, message_retention_duration: Duration) -> Self {
+        self.message_retention_duration = Some(message_retention_duration);
+        self
+    }
+
+    /// Retain acknowledged messages, e.g. for seeking to a time in the past.
+    pub fn with_retain_acked_messages(mut self, retain_acked_messages: bool) -> Self {
+        self.retain_acked_messages = Some(retain_acked_messages);
+        self
+    }
+
+    /// Deliver messages with the same ordering key in the order they were published; cannot be
+    /// changed after creation.
+    pub fn with_message_ordering(mut self, enable_message_ordering: bool) -> Self {
+        self.enable_message_ordering = Some(enable_message_ordering);
+        self
+    }
+
+    /// Do not redeliver messages once they have been acknowledged successfully before their
+    /// acknowledge deadline has expired.
+    pub fn with_exactly_once_delivery(mut self, enable_exactly_once_delivery: bool) -> Self {
+        self.enable_exactly_once_delivery = Some(enable_exactly_once_delivery);
+        self
+    }
+
+    /// Filter expression, see <https://cloud.google.com/pubsub/docs/subscription-message-filter>;
+    /// a [Filter](crate::Filter) can be given, too.
+    pub fn with_filter<T>(mut self, filter: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    pub fn with_expiration_policy(mut self, expiration_policy: ExpirationPolicy) -> Self {
+        self.expiration_policy = Some(expiration_policy);
+        self
+    }
+
+    pub fn with_push_config(mut self, push_config: PushConfig) -> Self {
+        self.push_config = Some(push_config);
+        self
+    }
+
+    /// Export messages to a BigQuery table instead of delivering them to subscribers.
+    pub fn with_bigquery_config(mut self, bigquery_config: BigQueryConfig) -> Self {
+        self.bigquery_config = Some(bigquery_config);
+        self
+    }
+
+    /// Export messages to Cloud Storage files instead of delivering them to subscribers.
+    pub fn with_cloud_storage_config(mut self, cloud_storage_config: CloudStorageConfig) -> Self {
+        self.cloud_storage_config = Some(cloud_storage_config);
+        self
+    }
+
+    /// Forward messages to a dead-letter topic after the maximum number of delivery attempts.
+    pub fn with_dead_letter_policy(mut self, dead_letter_policy: DeadLetterPolicy) -> Self {
+        self.dead_letter_policy = Some(dead_letter_policy);
+        self
+    }
+
+    /// Redeliver messages with exponential backoff instead of immediately.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Whether the given subscription matches the fields set on this configuration.
+    pub fn matches(&self, subscription: &Subscription) -> bool {
+        fn matches<T>(expected: &Option<T>, actual: Option<&T>) -> bool
+        where
+            T: PartialEq,
+        {
+            expected
+                .as_ref()
+                .is_none_or(|expected| Some(expected) == actual)
+        }
+
+        matches(&self.labels, Some(&subscription.labels))
+            && matches(
+                &self.ack_deadline_seconds,
+                subscription.ack_deadline_seconds.as_ref(),
+            )
+            && matches(
+                &self.message_retention_duration,
+                subscription.message_retention_duration.as_ref(),
+            )
+            && matches(
+                &self.retain_acked_messages,
+                Some(&subscription.retain_acked_messages),
+            )
+            && matches(
+                &self.enable_message_ordering,
+                Some(&subscription.enable_message_ordering),
+            )
+            && matches(
+                &self.enable_exactly_once_delivery,
+                Some(&subscription.enable_exactly_once_delivery),
+            )
+            && matches(&self.filter, subscription.filter.as_ref())
+            && matches(
+                &self.expiration_policy,
+                subscription.expiration_policy.as_ref(),
+            )
+            && matches(&self.push_config, subscription.push_config.as_ref())
+            && matches(&self.bigquery_config, subscription.bigquery_config.as_ref())
+            && matches(
+                &self.cloud_storage_config,
+                subscription.cloud_storage_config.as_ref(),
+            )
+            && matches(
+                &self.dead_letter_policy,
+                subscription.dead_letter_policy.as_ref(),
+            )
+            && matches(&self.retry_policy, subscription.retry_policy.as_ref())
+    }
+}
+
+/// Changes to a subscription: only the fields which are set are updated.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ack_deadline_seconds: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub message_retention_duration: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retain_acked_messages: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_exactly_once_delivery: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_policy: Option<ExpirationPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_config: Option<PushConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bigquery_config: Option<BigQueryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_storage_config: Option<CloudStorageConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_letter_policy: Option<DeadLetterPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+impl SubscriptionPatch {
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    pub fn with_ack_deadline_seconds(mut self, ack_deadline_seconds: u32) -> Self {
+        self.ack_deadline_seconds = Some(ack_deadline_seconds);
+        self
+    }
+
+    pub fn with_message_retention_duration(mut self, message_retention_duration: Duration) -> Self {
+        self.message_retention_duration = Some(message_retention_duration);
+        self
+    }
+
+    pub fn with_retain_acked_messages(mut self, retain_acked_messages: bool) -> Self {
+        self.retain_acked_messages = Some(retain_acked_messages);
+        self
+    }
+
+    pub fn with_exactly_once_delivery(mut self, enable_exactly_once_delivery: bool) -> Self {
+        self.enable_exactly_once_delivery = Some(enable_exactly_once_delivery);
+        self
+    }
+
+    pub fn with_expiration_policy(mut self, expiration_policy: ExpirationPolicy) -> Self {
+        self.expiration_policy = Some(expiration_policy);
+        self
+    }
+
+    pub fn with_push_config(mut self, push_config: PushConfig) -> Self {
+        self.push_config = Some(push_config);
+        self
+    }
+
+    pub fn with_bigquery_config(mut self, bigquery_config: BigQueryConfig) -> Self {
+        self.bigquery_config = Some(bigquery_config);
+        self
+    }
+
+    pub fn with_cloud_storage_config(mut self, cloud_storage_config: CloudStorageConfig) -> Self {
+        self.cloud_storage_config = Some(cloud_storage_config);
+        self
+    }
+
+    pub fn with_dead_letter_policy(mut self, dead_letter_policy: DeadLetterPolicy) -> Self {
+        self.dead_letter_policy = Some(dead_letter_policy);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+}
+
+/// When an inactive subscription expires; without a TTL it never expires.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpirationPolicy {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub ttl: Option<Duration>,
+}
+
+impl ExpirationPolicy {
+    /// The subscription never expires.
+    pub fn never() -> Self {
+        Self { ttl: None }
+    }
+
+    /// The subscription expires after having been inactive for the given TTL, at least one day.
+    pub fn after(ttl: Duration) -> Self {
+        Self { ttl: Some(ttl) }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushConfig {
+    pub push_endpoint: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, String>,
+}
+
+/// Configuration for exporting messages to a BigQuery table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BigQueryConfig {
+    /// Name of the table, i.e. `{project}.{dataset}.{table}`.
+    pub table: String,
+    /// Whether to use the schema of the topic as the columns to write to.
+    #[serde(default)]
+    pub use_topic_schema: bool,
+    /// Whether to write the message ID, publish time, attributes etc. to additional columns.
+    #[serde(default)]
+    pub write_metadata: bool,
+    /// Whether to drop fields not present in the table schema instead of failing to write.
+    #[serde(default)]
+    pub drop_unknown_fields: bool,
+}
+
+impl BigQueryConfig {
+    pub fn new<T>(table: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            table: table.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_use_topic_schema(mut self, use_topic_schema: bool) -> Self {
+        self.use_topic_schema = use_topic_schema;
+        self
+    }
+
+    pub fn with_write_metadata(mut self, write_metadata: bool) -> Self {
+        self.write_metadata = write_metadata;
+        self
+    }
+
+    pub fn with_drop_unknown_fields(mut self, drop_unknown_fields: bool) -> Self {
+        self.drop_unknown_fields = drop_unknown_fields;
+        self
+    }
+}
+
+/// Configuration for exporting messages to files in a Cloud Storage bucket. A new file is started
+/// when the maximum duration or the maximum number of bytes is exceeded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudStorageConfig {
+    /// Name of the bucket without the `gs://` prefix.
+    pub bucket: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename_suffix: Option<String>,
+    /// Between one and ten minutes, five minutes by default.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub max_duration: Option<Duration>,
+    /// Between 1 KB and 10 GiB.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_int64"
+    )]
+    pub max_bytes: Option<u64>,
+    /// Text by default.
+    #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<CloudStorageOutputFormat>,
+}
+
+impl CloudStorageConfig {
+    pub fn new<T>(bucket: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            bucket: bucket.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_filename_prefix<T>(mut self, filename_prefix: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.filename_prefix = Some(filename_prefix.into());
+        self
+    }
+
+    pub fn with_filename_suffix<T>(mut self, filename_suffix: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.filename_suffix = Some(filename_suffix.into());
+        self
+    }
+
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_output_format(mut self, output_format: CloudStorageOutputFormat) -> Self {
+        self.output_format = Some(output_format);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CloudStorageOutputFormat {
+    /// Message data written as raw text, separated by newlines.
+    #[serde(rename = "textConfig")]
+    Text {},
+    /// Messages written as Avro records, optionally including the message ID, publish time,
+    /// attributes etc.
+    #[serde(rename = "avroConfig", rename_all = "camelCase")]
+    Avro {
+        #[serde(default)]
+        write_metadata: bool,
+    },
+}
+
+/// Policy for forwarding undeliverable messages to a dead-letter topic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetterPolicy {
+    /// Fully qualified name of the dead-letter topic, i.e. `projects/{project}/topics/{topic}`.
+    pub dead_letter_topic: String,
+    /// Between 5 and 100, 5 by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delivery_attempts: Option<u32>,
+}
+
+impl DeadLetterPolicy {
+    const DEFAULT_MAX_DELIVERY_ATTEMPTS: u32 = 5;
+
+    pub fn new<T>(dead_letter_topic: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            dead_letter_topic: dead_letter_topic.into(),
+            max_delivery_attempts: None,
+        }
+    }
+
+    pub fn with_max_delivery_attempts(mut self, max_delivery_attempts: u32) -> Self {
+        self.max_delivery_attempts = Some(max_delivery_attempts);
+        self
+    }
+
+    /// The configured maximum number of delivery attempts or the default if not configured. A
+    /// pulled message with this `delivery_attempt` is the last delivery before dead-lettering.
+    pub fn effective_max_delivery_attempts(&self) -> u32 {
+        self.max_delivery_attempts
+            .unwrap_or(Self::DEFAULT_MAX_DELIVERY_ATTEMPTS)
+    }
+}
+
+/// Policy for redelivering messages which have not been acknowledged in time or have been
+/// negatively acknowledged; without a policy messages are redelivered immediately.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub minimum_backoff: Option<Duration>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub maximum_backoff: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Between zero and 600 seconds, ten seconds by default.
+    pub fn with_minimum_backoff(mut self, minimum_backoff: Duration) -> Self {
+        self.minimum_backoff = Some(minimum_backoff);
+        self
+    }
+
+    /// Between zero and 600 seconds, 600 seconds by default.
+    pub fn with_maximum_backoff(mut self, maximum_backoff: Duration) -> Self {
+        self.maximum_backoff = Some(maximum_backoff);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SubscriptionState {
+    StateUnspecified,
+    Active,
+    ResourceError,
+}
+
+/// Protobuf int64 values are represented as JSON strings, but numbers are accepted, too.
+fn deserialize_int64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Value::String(s)) => s.parse().map(Some).map_err(D::Error::custom),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("invalid int64 `{n}`"))),
+        Some(other) => Err(D::Error::custom(format!("invalid int64 `{other}`"))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateSubscriptionRequest<'a> {
+    topic: String,
+    #[serde(flatten)]
+    config: &'a SubscriptionConfig,
+}
+
+/// Target of seeking a subscription.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum SeekRequest {
+    #[serde(with = "time::serde::rfc3339")]
+    Time(OffsetDateTime),
+    /// Fully qualified name, i.e. `projects/{project}/snapshots/{snapshot}`.
+    Snapshot(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListSubscriptionsResponse {
+    #[serde(default)]
+    subscriptions: Vec<Subscription>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ListSubscriptionsResponse {
+    type Item = Subscription;
+
+    fn into_parts(self) -> (Vec<Subscription>, Option<String>) {
+        (self.subscriptions, self.next_page_token)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateSubscriptionRequest {
+    subscription: Value,
+    update_mask: String,
+}
+
+impl UpdateSubscriptionRequest {
+    fn from_patch(patch: &SubscriptionPatch) -> Result<Self, Error> {
+        let (subscription, update_mask) = patch::serialize(patch)?;
+        Ok(Self {
+            subscription,
+            update_mask,
+        })
+    }
+}
+
+impl PubSubClient {
+    #[tracing::instrument]
+    pub async fn create_subscription(
+        &self,
+        subscription_id: &str,
+        topic_id: &str,
+        config: SubscriptionConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Subscription, Error> {
+        let request = CreateSubscriptionRequest {
+            topic: self.topic_name(topic_id),
+            config: &config,
+        };
+        let subscription = self
+            .send_authenticated(
+                Method::PUT,
+                &resource_path("subscriptions", subscription_id),
+                Some(&request),
+                timeout,
+            )
+            .await?
+            .json::<Subscription>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(subscription.name, "successfully created subscription");
+        Ok(subscription)
+    }
+
+    /// Create the given subscription if it does not exist yet, i.e. 409 Conflict results in the
+    /// existing subscription instead of an error. Use [SubscriptionConfig::matches] to verify an
+    /// existing subscription; notice that its topic might differ from the given one.
+    #[tracing::instrument]
+    pub async fn ensure_subscription(
+        &self,
+        subscription_id: &str,
+        topic_id: &str,
+        config: SubscriptionConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Subscription, Error> {
+        match self
+            .create_subscription(subscription_id, topic_id, config, timeout)
+            .await
+        {
+            Err(Error::UnexpectedHttpStatusCode(StatusCode::CONFLICT, _)) => {
+                debug!(subscription_id, "subscription already exists");
+                self.get_subscription(subscription_id, timeout).await
+            }
+            result => result,
+        }
+    }
+
+    /// Update the given subscription with the fields set on the given patch.
+    #[tracing::instrument]
+    pub async fn update_subscription(
+        &self,
+        subscription_id: &str,
+        patch: SubscriptionPatch,
+        timeout: Option<Duration>,
+    ) -> Result<Subscription, Error> {
+        let request = UpdateSubscriptionRequest::from_patch(&patch)?;
+        let subscription = self
+            .send_authenticated(
+                Method::PATCH,
+                &resource_path("subscriptions", subscription_id),
+                Some(&request),
+                timeout,
+            )
+            .await?
+            .json::<Subscription>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(
+            subscription.name,
+            update_mask = request.update_mask,
+            "successfully updated subscription"
+        );
+        Ok(subscription)
+    }
+
+    #[tracing::instrument]
+    pub async fn get_subscription(
+        &self,
+        subscription_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Subscription, Error> {
+        self.send_authenticated::<()>(
+            Method::GET,
+            &resource_path("subscriptions", subscription_id),
+            None,
+            timeout,
+        )
+        .await?
+        .json::<Subscription>()
+        .await
+        .map_err(Error::UnexpectedHttpResponse)
+    }
+
+    /// Seek the given subscription to the given time: messages published before are marked
+    /// acknowledged, retained messages published afterwards are marked unacknowledged and hence
+    /// redelivered. Seeking to now purges the subscription.
+    #[tracing::instrument]
+    pub async fn seek_to_time(
+        &self,
+        subscription_id: &str,
+        time: OffsetDateTime,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.seek(subscription_id, &SeekRequest::Time(time), timeout)
+            .await
+    }
+
+    /// Seek the given subscription to the given snapshot, i.e. restore the acknowledgment state
+    /// captured by the snapshot. The snapshot must have been created from a subscription to the
+    /// same topic.
+    #[tracing::instrument]
+    pub async fn seek_to_snapshot(
+        &self,
+        subscription_id: &str,
+        snapshot_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let request = SeekRequest::Snapshot(self.snapshot_name(snapshot_id));
+        self.seek(subscription_id, &request, timeout).await
+    }
+
+    async fn seek(
+        &self,
+        subscription_id: &str,
+        request: &SeekRequest,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_authenticated(
+            Method::POST,
+            &format!("{}:seek", resource_path("subscriptions", subscription_id)),
+            Some(request),
+            timeout,
+        )
+        .await?;
+        debug!(
+            subscription_id,
+            ?request,
+            "successfully sought subscription"
+        );
+        Ok(())
+    }
+
+    /// Stream all subscriptions of the project, transparently following the pagination,
+    /// optionally with the given page size.
+    pub fn list_subscriptions(
+        &self,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<Subscription, Error>> + '_ {
+        self.paginate::<ListSubscriptionsResponse>("subscriptions".to_string(), page_size, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BigQueryConfig, CloudStorageConfig, CloudStorageOutputFormat, CreateSubscriptionRequest,
+        DeadLetterPolicy, ExpirationPolicy, PushConfig, RetryPolicy, SeekRequest, Subscription,
+        SubscriptionConfig, SubscriptionPatch, SubscriptionState, UpdateSubscriptionRequest,
+    };
+    use crate::Filter;
+    use serde_json::json;
+    use std::{collections::HashMap, time::Duration};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_serialize_create_subscription_request() {
+        let config = SubscriptionConfig::default();
+        let request = CreateSubscriptionRequest {
+            topic: "projects/test/topics/test".to_string(),
+            config: &config,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "topic": "projects/test/topics/test" })
+        );
+
+        let config = SubscriptionConfig::default()
+            .with_ack_deadline_seconds(60)
+            .with_message_retention_duration(Duration::from_secs(3600))
+            .with_message_ordering(true)
+            .with_exactly_once_delivery(true)
+            .with_filter(Filter::attr("type").eq("Foo"))
+            .with_retain_acked_messages(true)
+            .with_expiration_policy(ExpirationPolicy::after(Duration::from_secs(86400)))
+            .with_dead_letter_policy(DeadLetterPolicy::new("projects/test/topics/dead-letters"))
+            .with_retry_policy(
+                RetryPolicy::default()
+                    .with_minimum_backoff(Duration::from_millis(1500))
+                    .with_maximum_backoff(Duration::from_secs(300)),
+            );
+        let request = CreateSubscriptionRequest {
+            topic: "projects/test/topics/test".to_string(),
+            config: &config,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({
+                "topic": "projects/test/topics/test",
+                "ackDeadlineSeconds": 60,
+                "messageRetentionDuration": "3600s",
+                "retainAckedMessages": true,
+                "enableMessageOrdering": true,
+                "enableExactlyOnceDelivery": true,
+                "filter": "attributes.type = \"Foo\"",
+                "expirationPolicy": { "ttl": "86400s" },
+                "deadLetterPolicy": { "deadLetterTopic": "projects/test/topics/dead-letters" },
+                "retryPolicy": { "minimumBackoff": "1.500s", "maximumBackoff": "300s" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_bigquery_config() {
+        let config = SubscriptionConfig::default().with_bigquery_config(
+            BigQueryConfig::new("test.analytics.events")
+                .with_use_topic_schema(true)
+                .with_drop_unknown_fields(true),
+        );
+        assert_eq!(
+            serde_json::to_value(config).unwrap(),
+            json!({
+                "bigqueryConfig": {
+                    "table": "test.analytics.events",
+                    "useTopicSchema": true,
+                    "writeMetadata": false,
+                    "dropUnknownFields": true
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_cloud_storage_config() {
+        let config = CloudStorageConfig::new("test")
+            .with_filename_prefix("events-")
+            .with_max_duration(Duration::from_secs(300))
+            .with_max_bytes(1_000_000)
+            .with_output_format(CloudStorageOutputFormat::Avro {
+                write_metadata: true,
+            });
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "bucket": "test",
+                "filenamePrefix": "events-",
+                "maxDuration": "300s",
+                "maxBytes": 1_000_000,
+                "avroConfig": { "writeMetadata": true }
+            })
+        );
+
+        let deserialized = json!({
+            "bucket": "test",
+            "filenamePrefix": "events-",
+            "maxDuration": "300s",
+            "maxBytes": "1000000",
+            "avroConfig": { "writeMetadata": true }
+        });
+        let deserialized = serde_json::from_value::<CloudStorageConfig>(deserialized).unwrap();
+        assert_eq!(deserialized, config);
+
+        let config = serde_json::from_value::<CloudStorageConfig>(json!({ "bucket": "test" }));
+        assert_eq!(config.unwrap(), CloudStorageConfig::new("test"));
+
+        let config = json!({ "bucket": "test", "textConfig": {} });
+        let config = serde_json::from_value::<CloudStorageConfig>(config).unwrap();
+        assert_eq!(
+            config.output_format,
+            Some(CloudStorageOutputFormat::Text {})
+        );
+    }
+
+    #[test]
+    fn test_update_request() {
+        let patch = SubscriptionPatch::default()
+            .with_ack_deadline_seconds(60)
+            .with_dead_letter_policy(
+                DeadLetterPolicy::new("projects/test/topics/dead-letters")
+                    .with_max_delivery_attempts(10),
+            );
+        let request = UpdateSubscriptionRequest::from_patch(&patch).unwrap();
+        assert_eq!(request.update_mask, "ackDeadlineSeconds,deadLetterPolicy");
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({
+                "subscription": {
+                    "ackDeadlineSeconds": 60,
+                    "deadLetterPolicy": {
+                        "deadLetterTopic": "projects/test/topics/dead-letters",
+                        "maxDeliveryAttempts": 10
+                    }
+                },
+                "updateMask": "ackDeadlineSeconds,deadLetterPolicy"
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_seek_request() {
+        let request =
+            SeekRequest::Time(OffsetDateTime::from_unix_timestamp(1_673_179_200).unwrap());
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "time": "2023-01-08T12:00:00Z" })
+        );
+
+        let request = SeekRequest::Snapshot("projects/test/snapshots/test".to_string());
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({ "snapshot": "projects/test/snapshots/test" })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_subscription() {
+        let subscription = json!({
+            "name": "projects/test/subscriptions/test",
+            "topic": "projects/test/topics/test",
+            "pushConfig": {
+                "pushEndpoint": "https://example.com/push",
+                "attributes": { "x-goog-version": "v1" }
+            },
+            "ackDeadlineSeconds": 10,
+            "messageRetentionDuration": "604800s",
+            "deadLetterPolicy": {
+                "deadLetterTopic": "projects/test/topics/dead-letters",
+                "maxDeliveryAttempts": 5
+            },
+            "retryPolicy": { "minimumBackoff": "10s", "maximumBackoff": "600s" },
+            "state": "ACTIVE"
+        });
+        let subscription = serde_json::from_value::<Subscription>(subscription).unwrap();
+        assert_eq!(subscription.name, "projects/test/subscriptions/test");
+        assert_eq!(subscription.topic, "projects/test/topics/test");
+        assert_eq!(
+            subscription.push_config,
+            Some(PushConfig {
+                push_endpoint: "https://example.com/push".to_string(),
+                attributes: HashMap::from([("x-goog-version".to_string(), "v1".to_string())]),
+            })
+        );
+        assert_eq!(subscription.ack_deadline_seconds, Some(10));
+        assert_eq!(
+            subscription.message_retention_duration,
+            Some(Duration::from_secs(604800))
+        );
+        assert_eq!(
+            subscription.dead_letter_policy,
+            Some(DeadLetterPolicy {
+                dead_letter_topic: "projects/test/topics/dead-letters".to_string(),
+                max_delivery_attempts: Some(5),
+            })
+        );
+        assert_eq!(
+            subscription.retry_policy,
+            Some(RetryPolicy {
+                minimum_backoff: Some(Duration::from_secs(10)),
+                maximum_backoff: Some(Duration::from_secs(600)),
+            })
+        );
+        assert_eq!(
+            subscription
+                .dead_letter_policy
+                .as_ref()
+                .map(|policy| policy.effective_max_delivery_attempts()),
+            Some(5)
+        );
+        assert_eq!(subscription.state, Some(SubscriptionState::Active));
+
+        assert!(SubscriptionConfig::default()
+            .with_ack_deadline_seconds(10)
+            .with_message_ordering(false)
+            .matches(&subscription));
+        assert!(!SubscriptionConfig::default()
+            .with_filter(Filter::attr("type").eq("Foo"))
+            .matches(&subscription));
+        assert!(!subscription.retain_acked_messages);
+        assert!(!subscription.enable_message_ordering);
+        assert!(!subscription.enable_exactly_once_delivery);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{error::Error, retry::RetryConfig};
+use std::future::Future;
+use tokio::time::Instant;
+use tracing::{error, warn};
+
+/// Run the long-running future created by the given function, e.g. [Subscriber::run] or
+/// [Consumer::run], and restart it after transient errors (see [Error::is_transient]) with
+/// exponential backoff and jitter according to the given retry settings. A run which lasted
+/// longer than the maximum backoff is considered to have recovered, i.e. the backoff starts over.
+///
+/// Returns once a run has completed successfully, e.g. because it has been cancelled, or with
+/// the last error once it is not transient or restarting is exhausted.
+///
+/// [Subscriber::run]: crate::Subscriber::run
+/// [Consumer::run]: crate::Consumer::run
+pub async fn supervise<R, F>(restart: &RetryConfig, mut run: R) -> Result<(), Error>
+where
+    R: FnMut() -> F,
+    F: Future<Output = Result<(), Error>>,
+{
+    let mut restarts = 0;
+    loop {
+        let started = Instant::now();
+        let error = match run().await {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        if !error.is_transient() {
+            error!(%error, "run failed with non-transient error, not restarting");
+            return Err(error);
+        }
+        if started.elapsed() > restart.max_backoff {
+            restarts = 0;
+        }
+        match restart.jittered_backoff(restarts) {
+            Some(backoff) => {
+                warn!(%error, ?backoff, restarts, "run failed with transient error, restarting");
+                tokio::time::sleep(backoff).await;
+                restarts += 1;
+            }
+            None => {
+                error!(%error, restarts, "run failed, restarting exhausted");
+                return Err(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::supervise;
+    use crate::{Error, RetryConfig};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_supervise() {
+        let restart = RetryConfig {
+            max_retries: 2,
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let transient =
+            || Error::UnexpectedHttpStatusCode(StatusCode::SERVICE_UNAVAILABLE, String::new());
+
+        let mut runs = 0;
+        let result = supervise(&restart, || {
+            runs += 1;
+            let result = if runs < 3 { Err(transient()) } else { Ok(()) };
+            async move { result }
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(runs, 3);
+
+        let mut runs = 0;
+        let result = supervise(&restart, || {
+            runs += 1;
+            async { Err(transient()) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(runs, 3);
+
+        let mut runs = 0;
+        let result = supervise(&restart, || {
+            runs += 1;
+            async { Err(Error::NoData) }
+        })
+        .await;
+        assert!(matches!(result, Err(Error::NoData)));
+        assert_eq!(runs, 1);
+    }
+}
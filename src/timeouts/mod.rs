@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+/// Default timeouts per operation type, see
+/// [PubSubClientBuilder::with_default_timeouts](crate::PubSubClientBuilder::with_default_timeouts):
+/// each applies to requests of its type for which no timeout is given, i.e. the `timeout`
+/// argument is `None`; no timeout by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefaultTimeouts {
+    /// Timeout for publishing messages.
+    pub publish: Option<Duration>,
+    /// Timeout for pulling messages.
+    pub pull: Option<Duration>,
+    /// Timeout for acknowledging messages and modifying their acknowledgment deadlines.
+    pub ack: Option<Duration>,
+    /// Timeout for all other requests, e.g. managing topics, subscriptions, snapshots or schemas.
+    pub admin: Option<Duration>,
+}
+
+impl DefaultTimeouts {
+    /// Default timeouts with the given timeout for all operation types.
+    pub fn all(timeout: Duration) -> Self {
+        Self {
+            publish: Some(timeout),
+            pull: Some(timeout),
+            ack: Some(timeout),
+            admin: Some(timeout),
+        }
+    }
+
+    pub fn with_publish(mut self, publish: Duration) -> Self {
+        self.publish = Some(publish);
+        self
+    }
+
+    pub fn with_pull(mut self, pull: Duration) -> Self {
+        self.pull = Some(pull);
+        self
+    }
+
+    pub fn with_ack(mut self, ack: Duration) -> Self {
+        self.ack = Some(ack);
+        self
+    }
+
+    pub fn with_admin(mut self, admin: Duration) -> Self {
+        self.admin = Some(admin);
+        self
+    }
+
+    /// Returns the given timeout or, if `None`, the default timeout for the given operation type.
+    pub(crate) fn resolve(
+        &self,
+        operation: Operation,
+        timeout: Option<Duration>,
+    ) -> Option<Duration> {
+        timeout.or(match operation {
+            Operation::Publish => self.publish,
+            Operation::Pull => self.pull,
+            Operation::Ack => self.ack,
+            Operation::Admin => self.admin,
+        })
+    }
+}
+
+/// Type of a request, determining its default timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Publish,
+    Pull,
+    Ack,
+    Admin,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DefaultTimeouts, Operation};
+    use std::time::Duration;
+
+    #[test]
+    fn test_resolve() {
+        let timeouts = DefaultTimeouts::all(Duration::from_secs(30))
+            .with_pull(Duration::from_secs(90))
+            .with_ack(Duration::from_secs(10));
+
+        let timeout = timeouts.resolve(Operation::Pull, None);
+        assert_eq!(timeout, Some(Duration::from_secs(90)));
+        let timeout = timeouts.resolve(Operation::Ack, None);
+        assert_eq!(timeout, Some(Duration::from_secs(10)));
+        let timeout = timeouts.resolve(Operation::Admin, None);
+        assert_eq!(timeout, Some(Duration::from_secs(30)));
+        let timeout = timeouts.resolve(Operation::Publish, Some(Duration::from_secs(1)));
+        assert_eq!(timeout, Some(Duration::from_secs(1)));
+
+        let timeout = DefaultTimeouts::default().resolve(Operation::Publish, None);
+        assert_eq!(timeout, None);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{error::Error, MessageId, PubSubClient, PublishedMessageEnvelope};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, time::Duration};
+
+/// Handle for publishing messages of a certain type to a certain topic, optionally with default
+/// attributes and a default ordering key, created via [PubSubClient::topic].
+#[derive(Debug)]
+pub struct TopicHandle<'a, M> {
+    client: &'a PubSubClient,
+    topic_id: String,
+    attributes: Option<HashMap<String, String>>,
+    ordering_key: Option<String>,
+    #[cfg(feature = "json-schema")]
+    json_schema: Option<crate::JsonSchema>,
+    _message: PhantomData<fn(M)>,
+}
+
+impl<M> TopicHandle<'_, M>
+where
+    M: Serialize + Debug,
+{
+    /// Add the given attributes to all published messages; attributes of a message take
+    /// precedence.
+    pub fn with_attributes(mut self, attributes: HashMap<String, String>) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+
+    /// Use the given ordering key for all published messages without their own one.
+    pub fn with_ordering_key<T>(mut self, ordering_key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.ordering_key = Some(ordering_key.into());
+        self
+    }
+
+    /// Validate all messages against the given JSON schema before publishing them, failing with
+    /// [Error::JsonSchemaValidation] for the first invalid one.
+    #[cfg(feature = "json-schema")]
+    pub fn with_json_schema(mut self, json_schema: crate::JsonSchema) -> Self {
+        self.json_schema = Some(json_schema);
+        self
+    }
+
+    pub fn topic_id(&self) -> &str {
+        &self.topic_id
+    }
+
+    /// Publish the given messages, returning their message IDs.
+    pub async fn publish<E>(
+        &self,
+        envelopes: impl IntoIterator<Item = E>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, Error>
+    where
+        E: Into<PublishedMessageEnvelope<M>>,
+    {
+        let envelopes = envelopes
+            .into_iter()
+            .map(|envelope| with_default_attributes(envelope.into(), self.attributes.as_ref()));
+
+        #[cfg(feature = "json-schema")]
+        if let Some(json_schema) = &self.json_schema {
+            let index = std::cell::Cell::new(0);
+            return self
+                .client
+                .publish_encoded::<M, _, _>(
+                    &self.topic_id,
+                    envelopes,
+                    self.ordering_key.as_deref(),
+                    timeout,
+                    |message| json_schema.encode(message, index.replace(index.get() + 1)),
+                )
+                .await;
+        }
+
+        self.client
+            .publish::<M, _>(
+                &self.topic_id,
+                envelopes,
+                self.ordering_key.as_deref(),
+                timeout,
+            )
+            .await
+    }
+
+    /// Publish the given message, returning its message ID.
+    pub async fn publish_one<E>(
+        &self,
+        envelope: E,
+        timeout: Option<Duration>,
+    ) -> Result<MessageId, Error>
+    where
+        E: Into<PublishedMessageEnvelope<M>>,
+    {
+        let mut message_ids = self.publish([envelope], timeout).await?;
+        message_ids.pop().ok_or(Error::NoMessageId)
+    }
+}
+
+/// Add the given default attributes to the given envelope unless it has the same ones.
+fn with_default_attributes<M>(
+    mut envelope: PublishedMessageEnvelope<M>,
+    defaults: Option<&HashMap<String, String>>,
+) -> PublishedMessageEnvelope<M> {
+    if let Some(defaults) = defaults {
+        let attributes = envelope.attributes.get_or_insert_with(HashMap::new);
+        for (key, value) in defaults {
+            attributes
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+    envelope
+}
+
+impl PubSubClient {
+    /// Create a [TopicHandle] for publishing messages of type `M` to the topic with the given ID.
+    pub fn topic<M>(&self, topic_id: &str) -> TopicHandle<'_, M> {
+        TopicHandle {
+            client: self,
+            topic_id: topic_id.to_string(),
+            attributes: None,
+            ordering_key: None,
+            #[cfg(feature = "json-schema")]
+            json_schema: None,
+            _message: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_default_attributes;
+    use crate::PublishedMessageEnvelope;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_with_default_attributes() {
+        let attributes = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let defaults = attributes(&[("version", "v1"), ("source", "test")]);
+
+        let envelope = PublishedMessageEnvelope::new(42);
+        let envelope = with_default_attributes(envelope, Some(&defaults));
+        assert_eq!(envelope.attributes, Some(defaults.clone()));
+
+        let envelope =
+            PublishedMessageEnvelope::new(42).with_attributes(attributes(&[("version", "v2")]));
+        let envelope = with_default_attributes(envelope, Some(&defaults));
+        assert_eq!(
+            envelope.attributes,
+            Some(attributes(&[("version", "v2"), ("source", "test")]))
+        );
+
+        let envelope = with_default_attributes(PublishedMessageEnvelope::new(42), None);
+        assert_eq!(envelope.attributes, None);
+    }
+}
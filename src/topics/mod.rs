@@ -0,0 +1,544 @@
+use crate::{error::Error, pagination::Page, patch, resource_path, PubSubClient};
+use futures::Stream;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+use tracing::debug;
+
+/// Topic resource as returned by the Pub/Sub service.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Topic {
+    /// Fully qualified name, i.e. `projects/{project}/topics/{topic}`.
+    pub name: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default, with = "crate::duration::option")]
+    pub message_retention_duration: Option<Duration>,
+    pub schema_settings: Option<SchemaSettings>,
+    /// Name of the Cloud KMS key protecting access to messages, i.e.
+    /// `projects/{project}/locations/{location}/keyRings/{key_ring}/cryptoKeys/{key}`.
+    pub kms_key_name: Option<String>,
+    pub message_storage_policy: Option<MessageStoragePolicy>,
+}
+
+/// Settings for validating messages published against a schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaSettings {
+    /// Fully qualified name, i.e. `projects/{project}/schemas/{schema}`.
+    pub schema: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SchemaEncoding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_revision_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_revision_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SchemaEncoding {
+    EncodingUnspecified,
+    Json,
+    Binary,
+}
+
+/// Policy constraining where messages published to a topic may be stored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStoragePolicy {
+    /// Google Cloud regions, e.g. `"europe-west1"`; if empty, no constraints are in effect.
+    #[serde(default)]
+    pub allowed_persistence_regions: Vec<String>,
+}
+
+impl MessageStoragePolicy {
+    pub fn new<I, T>(allowed_persistence_regions: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            allowed_persistence_regions: allowed_persistence_regions
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+
+    /// Whether the given region is allowed, which is the case if no constraints are in effect.
+    pub fn allows(&self, region: &str) -> bool {
+        self.allowed_persistence_regions.is_empty()
+            || self.allowed_persistence_regions.iter().any(|r| r == region)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListTopicsResponse {
+    #[serde(default)]
+    topics: Vec<Topic>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ListTopicsResponse {
+    type Item = Topic;
+
+    fn into_parts(self) -> (Vec<Topic>, Option<String>) {
+        (self.topics, self.next_page_token)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListTopicSubscriptionsResponse {
+    #[serde(default)]
+    subscriptions: Vec<String>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ListTopicSubscriptionsResponse {
+    type Item = String;
+
+    fn into_parts(self) -> (Vec<String>, Option<String>) {
+        (self.subscriptions, self.next_page_token)
+    }
+}
+
+/// Configuration for creating a topic.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub message_retention_duration: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_settings: Option<SchemaSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kms_key_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_storage_policy: Option<MessageStoragePolicy>,
+}
+
+impl TopicConfig {
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Retention of published messages, between ten minutes and 31 days.
+    pub fn with_message_retention_duration(mut self, message_retention_duration: Duration) -> Self {
+        self.message_retention_duration = Some(message_retention_duration);
+        self
+    }
+
+    /// Validate messages published to the topic against the given schema.
+    pub fn with_schema_settings(mut self, schema_settings: SchemaSettings) -> Self {
+        self.schema_settings = Some(schema_settings);
+        self
+    }
+
+    /// Protect access to messages with the given customer-managed Cloud KMS key, i.e.
+    /// `projects/{project}/locations/{location}/keyRings/{key_ring}/cryptoKeys/{key}`.
+    pub fn with_kms_key_name<T>(mut self, kms_key_name: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.kms_key_name = Some(kms_key_name.into());
+        self
+    }
+
+    pub fn with_message_storage_policy(
+        mut self,
+        message_storage_policy: MessageStoragePolicy,
+    ) -> Self {
+        self.message_storage_policy = Some(message_storage_policy);
+        self
+    }
+
+    /// Whether the given topic matches the fields set on this configuration.
+    pub fn matches(&self, topic: &Topic) -> bool {
+        fn matches<T>(expected: &Option<T>, actual: Option<&T>) -> bool
+        where
+            T: PartialEq,
+        {
+            expected
+                .as_ref()
+                .is_none_or(|expected| Some(expected) == actual)
+        }
+
+        matches(&self.labels, Some(&topic.labels))
+            && matches(
+                &self.message_retention_duration,
+                topic.message_retention_duration.as_ref(),
+            )
+            && matches(&self.schema_settings, topic.schema_settings.as_ref())
+            && matches(&self.kms_key_name, topic.kms_key_name.as_ref())
+            && matches(
+                &self.message_storage_policy,
+                topic.message_storage_policy.as_ref(),
+            )
+    }
+}
+
+/// Changes to a topic: only the fields which are set are updated.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::duration::option"
+    )]
+    pub message_retention_duration: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_settings: Option<SchemaSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_storage_policy: Option<MessageStoragePolicy>,
+}
+
+impl TopicPatch {
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Retention of published messages, between ten minutes and 31 days.
+    pub fn with_message_retention_duration(mut self, message_retention_duration: Duration) -> Self {
+        self.message_retention_duration = Some(message_retention_duration);
+        self
+    }
+
+    pub fn with_schema_settings(mut self, schema_settings: SchemaSettings) -> Self {
+        self.schema_settings = Some(schema_settings);
+        self
+    }
+
+    pub fn with_message_storage_policy(
+        mut self,
+        message_storage_policy: MessageStoragePolicy,
+    ) -> Self {
+        self.message_storage_policy = Some(message_storage_policy);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateTopicRequest {
+    topic: Value,
+    update_mask: String,
+}
+
+impl UpdateTopicRequest {
+    fn from_patch(patch: &TopicPatch) -> Result<Self, Error> {
+        let (topic, update_mask) = patch::serialize(patch)?;
+        Ok(Self { topic, update_mask })
+    }
+}
+
+impl PubSubClient {
+    /// Create the given topic. If a region is configured, the message storage policy of the
+    /// given config, if any, must allow it.
+    #[tracing::instrument]
+    pub async fn create_topic(
+        &self,
+        topic_id: &str,
+        config: TopicConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Topic, Error> {
+        self.check_region(config.message_storage_policy.as_ref())?;
+
+        let topic = self
+            .send_authenticated(
+                Method::PUT,
+                &resource_path("topics", topic_id),
+                Some(&config),
+                timeout,
+            )
+            .await?
+            .json::<Topic>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(topic.name, "successfully created topic");
+        Ok(topic)
+    }
+
+    /// Update the given topic with the fields set on the given patch.
+    #[tracing::instrument]
+    pub async fn update_topic(
+        &self,
+        topic_id: &str,
+        patch: TopicPatch,
+        timeout: Option<Duration>,
+    ) -> Result<Topic, Error> {
+        let request = UpdateTopicRequest::from_patch(&patch)?;
+        let topic = self
+            .send_authenticated(
+                Method::PATCH,
+                &resource_path("topics", topic_id),
+                Some(&request),
+                timeout,
+            )
+            .await?
+            .json::<Topic>()
+            .await
+            .map_err(Error::UnexpectedHttpResponse)?;
+        debug!(
+            topic.name,
+            update_mask = request.update_mask,
+            "successfully updated topic"
+        );
+        Ok(topic)
+    }
+
+    /// Create the given topic if it does not exist yet, i.e. 409 Conflict results in the existing
+    /// topic instead of an error. Use [TopicConfig::matches] to verify an existing topic.
+    #[tracing::instrument]
+    pub async fn ensure_topic(
+        &self,
+        topic_id: &str,
+        config: TopicConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Topic, Error> {
+        match self.create_topic(topic_id, config, timeout).await {
+            Err(Error::UnexpectedHttpStatusCode(StatusCode::CONFLICT, _)) => {
+                debug!(topic_id, "topic already exists");
+                self.get_topic(topic_id, timeout).await
+            }
+            result => result,
+        }
+    }
+
+    /// Fails with [Error::RegionNotAllowed] if a region is configured which is not allowed by the
+    /// given message storage policy, because messages published via the regional endpoint could
+    /// not be stored.
+    pub(crate) fn check_region(
+        &self,
+        message_storage_policy: Option<&MessageStoragePolicy>,
+    ) -> Result<(), Error> {
+        match (&self.region, message_storage_policy) {
+            (Some(region), Some(policy)) if !policy.allows(region) => {
+                Err(Error::RegionNotAllowed {
+                    region: region.clone(),
+                    allowed_regions: policy.allowed_persistence_regions.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument]
+    pub async fn get_topic(
+        &self,
+        topic_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Topic, Error> {
+        self.send_authenticated::<()>(
+            Method::GET,
+            &resource_path("topics", topic_id),
+            None,
+            timeout,
+        )
+        .await?
+        .json::<Topic>()
+        .await
+        .map_err(Error::UnexpectedHttpResponse)
+    }
+
+    /// Stream all topics of the project, transparently following the pagination, optionally with
+    /// the given page size.
+    pub fn list_topics(
+        &self,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<Topic, Error>> + '_ {
+        self.paginate::<ListTopicsResponse>("topics".to_string(), page_size, timeout)
+    }
+
+    /// Stream the fully qualified names, i.e. `projects/{project}/subscriptions/{subscription}`, of
+    /// all subscriptions attached to the given topic, transparently following the pagination,
+    /// optionally with the given page size.
+    pub fn list_topic_subscriptions<'a>(
+        &'a self,
+        topic_id: &str,
+        page_size: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<String, Error>> + 'a {
+        self.paginate::<ListTopicSubscriptionsResponse>(
+            format!("{}/subscriptions", resource_path("topics", topic_id)),
+            page_size,
+            timeout,
+        )
+    }
+
+    /// Whether the given topic exists, i.e. 404 Not Found results in `false` instead of an error.
+    #[tracing::instrument]
+    pub async fn topic_exists(
+        &self,
+        topic_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<bool, Error> {
+        match self.get_topic(topic_id, timeout).await {
+            Ok(_) => Ok(true),
+            Err(Error::UnexpectedHttpStatusCode(StatusCode::NOT_FOUND, _)) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Delete the given topic; a non-existing topic results in [Error::UnexpectedHttpStatusCode]
+    /// with 404 Not Found.
+    #[tracing::instrument]
+    pub async fn delete_topic(
+        &self,
+        topic_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.send_authenticated::<()>(
+            Method::DELETE,
+            &resource_path("topics", topic_id),
+            None,
+            timeout,
+        )
+        .await?;
+        debug!(topic_id, "successfully deleted topic");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MessageStoragePolicy, SchemaEncoding, SchemaSettings, Topic, TopicConfig, TopicPatch,
+        UpdateTopicRequest,
+    };
+    use serde_json::json;
+    use std::{collections::HashMap, time::Duration};
+
+    #[test]
+    fn test_message_storage_policy_allows() {
+        let policy = MessageStoragePolicy::new(["europe-west1", "europe-west4"]);
+        assert!(policy.allows("europe-west1"));
+        assert!(!policy.allows("us-central1"));
+
+        let policy = MessageStoragePolicy::new(Vec::<String>::new());
+        assert!(policy.allows("us-central1"));
+    }
+
+    #[test]
+    fn test_serialize_topic_config() {
+        let config = TopicConfig::default();
+        assert_eq!(serde_json::to_value(config).unwrap(), json!({}));
+
+        let config = TopicConfig::default()
+            .with_labels(HashMap::from([("env".to_string(), "test".to_string())]))
+            .with_message_retention_duration(Duration::from_secs(600))
+            .with_schema_settings(SchemaSettings {
+                schema: "projects/test/schemas/test".to_string(),
+                encoding: Some(SchemaEncoding::Json),
+                first_revision_id: None,
+                last_revision_id: None,
+            })
+            .with_kms_key_name("projects/test/locations/europe-west1/keyRings/test/cryptoKeys/test")
+            .with_message_storage_policy(MessageStoragePolicy::new(["europe-west1"]));
+        assert_eq!(
+            serde_json::to_value(config).unwrap(),
+            json!({
+                "labels": { "env": "test" },
+                "messageRetentionDuration": "600s",
+                "schemaSettings": { "schema": "projects/test/schemas/test", "encoding": "JSON" },
+                "kmsKeyName": "projects/test/locations/europe-west1/keyRings/test/cryptoKeys/test",
+                "messageStoragePolicy": { "allowedPersistenceRegions": ["europe-west1"] }
+            })
+        );
+    }
+
+    #[test]
+    fn test_update_topic_request() {
+        let patch = TopicPatch::default()
+            .with_message_retention_duration(Duration::from_secs(3600))
+            .with_labels(HashMap::from([("env".to_string(), "test".to_string())]));
+        let request = UpdateTopicRequest::from_patch(&patch).unwrap();
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({
+                "topic": { "labels": { "env": "test" }, "messageRetentionDuration": "3600s" },
+                "updateMask": "labels,messageRetentionDuration"
+            })
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let topic = Topic {
+            name: "projects/test/topics/test".to_string(),
+            labels: HashMap::from([("env".to_string(), "test".to_string())]),
+            message_retention_duration: Some(Duration::from_secs(600)),
+            schema_settings: None,
+            kms_key_name: None,
+            message_storage_policy: None,
+        };
+        assert!(TopicConfig::default().matches(&topic));
+        assert!(TopicConfig::default()
+            .with_message_retention_duration(Duration::from_secs(600))
+            .matches(&topic));
+        assert!(!TopicConfig::default()
+            .with_labels(HashMap::new())
+            .matches(&topic));
+        assert!(!TopicConfig::default()
+            .with_kms_key_name("projects/test/locations/europe-west1/keyRings/test/cryptoKeys/test")
+            .matches(&topic));
+    }
+
+    #[test]
+    fn test_deserialize_topic() {
+        let topic = json!({ "name": "projects/test/topics/test" });
+        let topic = serde_json::from_value::<Topic>(topic).unwrap();
+        assert_eq!(topic.name, "projects/test/topics/test");
+        assert!(topic.labels.is_empty());
+        assert_eq!(topic.message_retention_duration, None);
+        assert_eq!(topic.schema_settings, None);
+
+        let topic = json!({
+            "name": "projects/test/topics/test",
+            "labels": { "env": "test" },
+            "messageRetentionDuration": "86400s",
+            "messageStoragePolicy": { "allowedPersistenceRegions": ["europe-west1"] },
+            "schemaSettings": {
+                "schema": "projects/test/schemas/test",
+                "encoding": "JSON",
+                "firstRevisionId": "a",
+            }
+        });
+        let topic = serde_json::from_value::<Topic>(topic).unwrap();
+        assert_eq!(
+            topic.labels,
+            HashMap::from([("env".to_string(), "test".to_string())])
+        );
+        assert_eq!(
+            topic.message_retention_duration,
+            Some(Duration::from_secs(86400))
+        );
+        assert_eq!(
+            topic.schema_settings,
+            Some(SchemaSettings {
+                schema: "projects/test/schemas/test".to_string(),
+                encoding: Some(SchemaEncoding::Json),
+                first_revision_id: Some("a".to_string()),
+                last_revision_id: None,
+            })
+        );
+        assert_eq!(topic.kms_key_name, None);
+        assert_eq!(
+            topic.message_storage_policy,
+            Some(MessageStoragePolicy::new(["europe-west1"]))
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{PulledMessage, RawPulledMessage};
+use opentelemetry::{global, trace::TraceContextExt};
+use std::collections::HashMap;
+use tracing::{info_span, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Inject the context of the current span, e.g. the W3C `traceparent` and `tracestate`, into the
+/// given attributes, using the globally configured text map propagator.
+pub(crate) fn inject(attributes: &mut Option<HashMap<String, String>>) {
+    let context = Span::current().context();
+    if !context.span().span_context().is_valid() {
+        return;
+    }
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, attributes.get_or_insert_with(HashMap::new))
+    });
+}
+
+/// Create a consumer span for the message with the given ID and attributes, linked to the span
+/// context extracted from the attributes, if any.
+fn consumer_span(id: &str, attributes: Option<&HashMap<String, String>>) -> Span {
+    let span = info_span!("pubsub.receive", message_id = id);
+    if let Some(attributes) = attributes {
+        let context = global::get_text_map_propagator(|propagator| propagator.extract(attributes));
+        let span_context = context.span().span_context().clone();
+        if span_context.is_valid() {
+            span.add_link(span_context);
+        }
+    }
+    span
+}
+
+impl RawPulledMessage {
+    /// Create a consumer span for this message, linked to the span it has been published in, if
+    /// its trace context has been propagated via its attributes.
+    pub fn consumer_span(&self) -> Span {
+        consumer_span(&self.id, self.attributes.as_ref())
+    }
+}
+
+impl<M> PulledMessage<M> {
+    /// Create a consumer span for this message, linked to the span it has been published in, if
+    /// its trace context has been propagated via its attributes.
+    pub fn consumer_span(&self) -> Span {
+        consumer_span(&self.id, self.attributes.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inject;
+
+    #[test]
+    fn test_inject_without_span() {
+        let mut attributes = None;
+        inject(&mut attributes);
+        assert_eq!(attributes, None);
+    }
+}
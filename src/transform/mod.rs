@@ -0,0 +1,151 @@
+use crate::RawPulledMessageEnvelope;
+use serde_json::{json, Value};
+use std::{cmp::Reverse, error::Error as StdError};
+
+const DEFAULT_TYPE_KEY: &str = "type";
+const DEFAULT_SEPARATOR: &str = ".";
+const VERSION_KEY: &str = "version";
+
+/// Transform for the type attribute protocol, to be used with
+/// [PubSubClient::pull_with_transform](crate::PubSubClient::pull_with_transform): for messages
+/// with version "v1" – the default if there is no "version" attribute – the JSON data is wrapped
+/// into an object with the value of the type attribute as key, which is how Serde expects
+/// externally tagged enums. Nested values are wrapped according to further type attributes with
+/// a path, e.g. "type.foo" for the value at key "foo". Messages with version "v2" already are
+/// externally tagged and are not transformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeAttributeTransform {
+    type_key: String,
+    separator: String,
+}
+
+impl Default for TypeAttributeTransform {
+    fn default() -> Self {
+        Self {
+            type_key: DEFAULT_TYPE_KEY.to_string(),
+            separator: DEFAULT_SEPARATOR.to_string(),
+        }
+    }
+}
+
+impl TypeAttributeTransform {
+    /// Use the given attribute key instead of "type".
+    pub fn with_type_key<T>(mut self, type_key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.type_key = type_key.into();
+        self
+    }
+
+    /// Use the given separator for the paths of nested values instead of ".".
+    pub fn with_separator<T>(mut self, separator: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.separator = separator.into();
+        self
+    }
+
+    pub fn transform(
+        &self,
+        envelope: &RawPulledMessageEnvelope,
+        mut value: Value,
+    ) -> Result<Value, Box<dyn StdError + Send + Sync + 'static>> {
+        let Some(attributes) = &envelope.message.attributes else {
+            return Ok(value);
+        };
+
+        match attributes.get(VERSION_KEY).map(|v| &v[..]).unwrap_or("v1") {
+            "v1" => {
+                let prefix = format!("{}{}", self.type_key, self.separator);
+                let mut type_keys = attributes
+                    .iter()
+                    .filter_map(|(key, tpe)| {
+                        if *key == self.type_key {
+                            Some((vec![], tpe))
+                        } else {
+                            key.strip_prefix(&prefix)
+                                .map(|path| (path.split(&self.separator).collect::<Vec<_>>(), tpe))
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                // Wrap the innermost values first, else their paths would no longer be valid.
+                type_keys.sort_unstable_by_key(|(path, _)| Reverse(path.len()));
+                for (path, tpe) in type_keys {
+                    let sub_value = path.iter().try_fold(&mut value, |v, k| v.get_mut(k));
+                    if let Some(sub_value) = sub_value {
+                        *sub_value = json!({ tpe: sub_value });
+                    }
+                }
+                Ok(value)
+            }
+            "v2" => Ok(value),
+            unknown => Err(format!("unknown version `{unknown}`").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeAttributeTransform;
+    use crate::{RawPulledMessage, RawPulledMessageEnvelope};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use time::OffsetDateTime;
+
+    fn envelope(attributes: &[(&str, &str)]) -> RawPulledMessageEnvelope {
+        RawPulledMessageEnvelope {
+            ack_id: "ack_id".to_string(),
+            message: RawPulledMessage {
+                data: None,
+                attributes: Some(HashMap::from_iter(
+                    attributes
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string())),
+                )),
+                id: "id".to_string(),
+                publish_time: OffsetDateTime::UNIX_EPOCH,
+                ordering_key: None,
+            },
+            delivery_attempt: 1,
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let transform = TypeAttributeTransform::default();
+
+        let envelope_v1 = envelope(&[("type", "Foo"), ("type.bar", "Bar")]);
+        let value = json!({ "bar": { "text": "test" } });
+        let value = transform.transform(&envelope_v1, value).unwrap();
+        assert_eq!(
+            value,
+            json!({ "Foo": { "bar": { "Bar": { "text": "test" } } } })
+        );
+
+        let envelope_v2 = envelope(&[("version", "v2"), ("type", "Foo")]);
+        let value = json!({ "Foo": { "text": "test" } });
+        let value = transform.transform(&envelope_v2, value).unwrap();
+        assert_eq!(value, json!({ "Foo": { "text": "test" } }));
+
+        let envelope_v3 = envelope(&[("version", "v3")]);
+        let result = transform.transform(&envelope_v3, json!({}));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_transform_configured() {
+        let transform = TypeAttributeTransform::default()
+            .with_type_key("kind")
+            .with_separator("/");
+
+        let envelope = envelope(&[("kind", "Foo"), ("kind/bar", "Bar"), ("type", "Baz")]);
+        let value = json!({ "bar": { "text": "test" } });
+        let value = transform.transform(&envelope, value).unwrap();
+        assert_eq!(
+            value,
+            json!({ "Foo": { "bar": { "Bar": { "text": "test" } } } })
+        );
+    }
+}
@@ -1,15 +1,21 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
-use pub_sub_client::{PubSubClient, RawPublishedMessage};
-use reqwest::{Client, StatusCode};
+use futures::{StreamExt, TryStreamExt};
+use pub_sub_client::{
+    Error, PubSubClient, RawPublishedMessage, SnapshotConfig, StreamOptions, SubscriptionConfig,
+    SubscriptionPatch, TopicConfig, TopicPatch,
+};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{collections::HashMap, env, time::Duration, vec};
+use std::{collections::HashMap, pin::pin, time::Duration, vec};
 use testcontainers::clients::Cli;
 use testcontainers_modules::google_cloud_sdk_emulators::{CloudSdk, PUBSUB_PORT};
+use time::OffsetDateTime;
 
 const PROJECT_ID: &str = "active-road-365118";
 const TOPIC_ID: &str = "test";
 const SUBSCRIPTION_ID: &str = "test";
+const SNAPSHOT_ID: &str = "test";
 const TEXT: &str = "test-text";
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -27,40 +33,118 @@ async fn test() {
     let base_url = format!("http://localhost:{pubsub_port}");
     let topic_name = format!("projects/{PROJECT_ID}/topics/{TOPIC_ID}");
     let subscription_name = format!("projects/{PROJECT_ID}/subscriptions/{SUBSCRIPTION_ID}");
+    let snapshot_name = format!("projects/{PROJECT_ID}/snapshots/{SNAPSHOT_ID}");
 
-    // For management we have to interact with Pub/Sub via HTTP
-    let reqwest_client = Client::new();
+    // Create PubSubClient
+    let pub_sub_client = PubSubClient::emulator(PROJECT_ID, base_url);
+    assert!(pub_sub_client.is_ok());
+    let pub_sub_client = pub_sub_client.unwrap();
 
     // Create topic
-    let response = reqwest_client
-        .put(format!("{base_url}/v1/{topic_name}"))
-        .send()
+    let result = pub_sub_client
+        .create_topic(
+            TOPIC_ID,
+            TopicConfig::default(),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().name, topic_name);
+
+    // Ensure existing topic
+    let result = pub_sub_client
+        .ensure_topic(
+            TOPIC_ID,
+            TopicConfig::default(),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().name, topic_name);
+
+    // Get topic
+    let result = pub_sub_client
+        .get_topic(TOPIC_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().name, topic_name);
+    let result = pub_sub_client
+        .topic_exists("non-existent", Some(Duration::from_secs(10)))
+        .await;
+    assert!(matches!(result, Ok(false)));
+
+    // Update topic
+    let result = pub_sub_client
+        .update_topic(
+            TOPIC_ID,
+            TopicPatch::default()
+                .with_labels(HashMap::from([("env".to_string(), "test".to_string())])),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().labels.get("env"), Some(&"test".to_string()));
+
+    // List topics
+    let result = pub_sub_client
+        .list_topics(Some(1), Some(Duration::from_secs(10)))
+        .try_collect::<Vec<_>>()
         .await;
-    assert!(response.is_ok());
-    let response = response.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].name, topic_name);
 
     // Create subscription
-    let response = reqwest_client
-        .put(format!("{base_url}/v1/{subscription_name}"))
-        .json(&json!({ "topic": topic_name }))
-        .send()
+    let result = pub_sub_client
+        .create_subscription(
+            SUBSCRIPTION_ID,
+            TOPIC_ID,
+            SubscriptionConfig::default(),
+            Some(Duration::from_secs(10)),
+        )
         .await;
-    assert!(response.is_ok());
-    let response = response.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.name, subscription_name);
+    assert_eq!(result.topic, topic_name);
 
-    // Create PubSubClient
-    // Notice: GitHub Actions write the `GCP_SERVICE_ACCOUNT` secret to the below key path,
-    // locally the file must be decrypted.
-    env::set_var("PUB_SUB_BASE_URL", base_url);
-    let pub_sub_client = PubSubClient::new(
-        "secrets/active-road-365118-0214022979ee.json",
-        Duration::from_secs(30),
-    );
-    env::set_var("PUB_SUB_BASE_URL", "");
-    assert!(pub_sub_client.is_ok());
-    let pub_sub_client = pub_sub_client.unwrap();
+    // Get subscription
+    let result = pub_sub_client
+        .get_subscription(SUBSCRIPTION_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().topic, topic_name);
+
+    // List subscriptions
+    let result = pub_sub_client
+        .list_subscriptions(None, Some(Duration::from_secs(10)))
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(result.is_ok());
+    assert!(result
+        .unwrap()
+        .iter()
+        .any(|subscription| subscription.name == subscription_name));
+
+    // List topic subscriptions
+    let result = pub_sub_client
+        .list_topic_subscriptions(TOPIC_ID, None, Some(Duration::from_secs(10)))
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), vec![subscription_name.clone()]);
+
+    // Update subscription
+    let result = pub_sub_client
+        .update_subscription(
+            SUBSCRIPTION_ID,
+            SubscriptionPatch::default().with_ack_deadline_seconds(30),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().ack_deadline_seconds, Some(30));
 
     // Publish raw
     let foo = STANDARD.encode(json!({ "Foo": { "text": TEXT } }).to_string());
@@ -224,4 +308,100 @@ async fn test() {
         result[0].attributes,
         Some(HashMap::from([("version".to_string(), "v1".to_string())]))
     );
+
+    // Nack
+    let id = result[0].id.clone();
+    let ack_ids = vec![&result[0].ack_id[..]];
+    let result = pub_sub_client
+        .nack(SUBSCRIPTION_ID, ack_ids, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+
+    // Pull again, raw, i.e. get the nacked message redelivered
+    let result = pub_sub_client
+        .pull_raw(SUBSCRIPTION_ID, 42, Some(Duration::from_secs(45)))
+        .await;
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].message.id, id);
+
+    // Nack again and stream, i.e. get the nacked message redelivered
+    let ack_ids = vec![&result[0].ack_id[..]];
+    let result = pub_sub_client
+        .nack(SUBSCRIPTION_ID, ack_ids, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+    let mut messages = pin!(pub_sub_client.stream::<Message>(
+        SUBSCRIPTION_ID,
+        StreamOptions::default().with_pull_timeout(Duration::from_secs(45)),
+    ));
+    let result = messages.next().await;
+    assert!(matches!(result, Some(Ok(ref message)) if message.id == id));
+
+    // Ack via the received message
+    let result = result.unwrap().unwrap().ack().await;
+    assert!(result.is_ok());
+
+    // Seek to time, i.e. purge the subscription
+    let result = pub_sub_client
+        .seek_to_time(
+            SUBSCRIPTION_ID,
+            OffsetDateTime::now_utc(),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+
+    // Create snapshot
+    let result = pub_sub_client
+        .create_snapshot(
+            SNAPSHOT_ID,
+            SUBSCRIPTION_ID,
+            SnapshotConfig::default(),
+            Some(Duration::from_secs(10)),
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.name, snapshot_name);
+    assert_eq!(result.topic, topic_name);
+
+    // List snapshots
+    let result = pub_sub_client
+        .list_snapshots(None, Some(Duration::from_secs(10)))
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(result.is_ok());
+    assert!(result
+        .unwrap()
+        .iter()
+        .any(|snapshot| snapshot.name == snapshot_name));
+
+    // Seek to snapshot
+    let result = pub_sub_client
+        .seek_to_snapshot(SUBSCRIPTION_ID, SNAPSHOT_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+
+    // Delete snapshot
+    let result = pub_sub_client
+        .delete_snapshot(SNAPSHOT_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+
+    // Delete topic
+    let result = pub_sub_client
+        .delete_topic(TOPIC_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(result.is_ok());
+
+    // Delete topic again
+    let result = pub_sub_client
+        .delete_topic(TOPIC_ID, Some(Duration::from_secs(10)))
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::UnexpectedHttpStatusCode(StatusCode::NOT_FOUND, _))
+    ));
 }